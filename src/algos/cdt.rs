@@ -0,0 +1,1047 @@
+//! Constrained Delaunay triangulation, with optional Ruppert refinement
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+use super::orient;
+use crate::core::Pose;
+use crate::kernel::{fxx, Vec2, Vec3};
+use crate::planar::Polygon;
+use crate::solid::{Mesh, Normals};
+
+/// Points closer than this many machine epsilons, scaled by the size of the input, are considered the same point
+const CDT_TOLERANCE_FACTOR: fxx = 1024.0;
+
+/// How many times larger than the input the temporary triangle around everything is
+const CDT_SUPER_TRIANGLE_SCALE: fxx = 100.0;
+
+/// Refinement stops after adding this many points, in case the requested quality can't be reached
+const CDT_MAX_STEINER_POINTS: usize = 100_000;
+
+/// Minimum angles above this are not guaranteed to converge, and are lowered to it
+const CDT_MAX_MIN_ANGLE: fxx = 0.58;
+
+/// Marks a missing neighbor
+const NONE: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CdtError {
+    /// The input contains infinite or NaN coordinates
+    NotFinite,
+    /// There are fewer than three points, or they are all collinear
+    Degenerate,
+    /// A constraint could not be inserted, typically because of nearly collinear points
+    Constraint,
+}
+
+impl Error for CdtError {}
+
+impl fmt::Display for CdtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CdtError::NotFinite => write!(
+                f,
+                "Cdt error: the input contains infinite or NaN coordinates"
+            ),
+            CdtError::Degenerate => write!(f, "Cdt error: the points don't span an area"),
+            CdtError::Constraint => write!(f, "Cdt error: a constraint could not be inserted"),
+        }
+    }
+}
+
+/// A constrained Delaunay triangulation of boundaries, segments and points on a plane.
+///
+/// Closed boundaries define the area to triangulate: nested boundaries alternate between outer boundaries and holes.
+/// Segments and points are forced into the triangulation, without changing what is inside.
+/// Without any boundaries, the triangulation fills the convex hull.
+///
+/// Delaunay triangles already have the best angles possible with the given points.
+/// With [`Cdt::with_min_angle`] or [`Cdt::with_max_area`], extra points are added until every triangle is good enough.
+///
+/// ```
+/// use hedron::algos::Cdt;
+/// use hedron::planar::Polygon;
+///
+/// let square = Polygon::new_regular(2.0, 4);
+/// let hole = Polygon::new_regular(1.0, 4).flip();
+/// let mesh = Cdt::from_polygon(&square).with_hole(&hole).triangulate().unwrap();
+/// assert_eq!(mesh.tri.len(), 8 * 3);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Cdt {
+    pose: Pose,
+    boundaries: Vec<Vec<Vec2>>,
+    segments: Vec<(Vec2, Vec2)>,
+    points: Vec<Vec2>,
+    min_angle: Option<fxx>,
+    max_area: Option<fxx>,
+}
+
+impl Cdt {
+    /// Triangulate on the xy plane of `pose`. All input is projected onto this plane.
+    pub fn new(pose: Pose) -> Self {
+        Self {
+            pose,
+            ..Default::default()
+        }
+    }
+
+    /// Triangulate the inside of `polygon`, on the plane found by [`Polygon::estimate_pose`]
+    pub fn from_polygon(polygon: &Polygon) -> Self {
+        Self::new(polygon.estimate_pose()).with_boundary(&polygon.verts)
+    }
+
+    /// The plane everything is projected on
+    pub fn pose(&self) -> &Pose {
+        &self.pose
+    }
+
+    /// Add a closed loop. Its orientation doesn't matter
+    pub fn with_boundary(mut self, verts: &[Vec3]) -> Self {
+        let boundary = verts.iter().map(|v| self.project(*v)).collect();
+        self.boundaries.push(boundary);
+        self
+    }
+
+    pub fn with_hole(self, hole: &Polygon) -> Self {
+        self.with_boundary(&hole.verts)
+    }
+
+    /// Add an edge which must appear in the triangulation, like a ridge in a terrain
+    pub fn with_segment(mut self, a: Vec3, b: Vec3) -> Self {
+        let segment = (self.project(a), self.project(b));
+        self.segments.push(segment);
+        self
+    }
+
+    /// Add a vertex which must appear in the triangulation
+    pub fn with_point(mut self, point: Vec3) -> Self {
+        let point = self.project(point);
+        self.points.push(point);
+        self
+    }
+
+    /// Add points until no triangle has an angle below `angle`, in radians.
+    /// Angles up to about 33 degrees are reachable. Small angles between input segments are left alone.
+    pub fn with_min_angle(mut self, angle: fxx) -> Self {
+        self.min_angle = Some(angle.min(CDT_MAX_MIN_ANGLE));
+        self
+    }
+
+    /// Add points until no triangle is larger than `area`
+    pub fn with_max_area(mut self, area: fxx) -> Self {
+        self.max_area = Some(area);
+        self
+    }
+
+    /// The triangulation as a mesh on the plane, with triangles facing the local z axis of the pose
+    pub fn triangulate(&self) -> Result<Mesh, CdtError> {
+        let (verts, triangles) = self.triangulate_2d()?;
+        let verts = verts
+            .into_iter()
+            .map(|v| self.pose.transform_point(v.extend(0.0)))
+            .collect();
+        Ok(Mesh::new(
+            verts,
+            triangles.concat(),
+            Vec::new(),
+            Normals::None,
+        ))
+    }
+
+    /// The triangulation in the coordinates of the plane, as counter clockwise triangles.
+    /// The input points come first, in order of appearance: boundaries, then segments, then points. Duplicates are skipped.
+    pub fn triangulate_2d(&self) -> Result<(Vec<Vec2>, Vec<[usize; 3]>), CdtError> {
+        let input = self
+            .boundaries
+            .iter()
+            .flatten()
+            .chain(self.segments.iter().flat_map(|(a, b)| [a, b]))
+            .chain(self.points.iter())
+            .copied()
+            .collect::<Vec<_>>();
+        if input.iter().any(|p| !p.is_finite()) {
+            return Err(CdtError::NotFinite);
+        }
+        let mut tri = Triangulation::new(&input).ok_or(CdtError::Degenerate)?;
+        let ids = input
+            .iter()
+            .map(|p| tri.insert_point(*p))
+            .collect::<Vec<_>>();
+        if tri.points.len() < 6 {
+            return Err(CdtError::Degenerate);
+        }
+
+        // every segment, as the vertices along it
+        let mut segments = Vec::new();
+        let mut offset = 0;
+        for boundary in self.boundaries.iter() {
+            let loop_ids = &ids[offset..offset + boundary.len()];
+            for i in 0..loop_ids.len() {
+                segments.push((loop_ids[i], loop_ids[(i + 1) % loop_ids.len()], true));
+            }
+            offset += boundary.len();
+        }
+        for i in 0..self.segments.len() {
+            segments.push((ids[offset + i * 2], ids[offset + i * 2 + 1], false));
+        }
+        for (a, b, boundary) in tri.split_crossings(segments) {
+            tri.insert_constraint(a, b, boundary)?;
+        }
+        if self.boundaries.is_empty() {
+            tri.insert_hull()?;
+        }
+
+        if self.min_angle.is_some() || self.max_area.is_some() {
+            tri.refine(
+                self.min_angle.unwrap_or(0.0),
+                self.max_area.unwrap_or(fxx::MAX),
+            );
+        }
+        let inside = tri.inside(!self.boundaries.is_empty());
+        let (verts, triangles) = tri.export(&inside);
+        match triangles.is_empty() {
+            true => Err(CdtError::Degenerate),
+            false => Ok((verts, triangles)),
+        }
+    }
+
+    fn project(&self, point: Vec3) -> Vec2 {
+        self.pose.transform_point_inv(point).truncate()
+    }
+}
+
+impl Polygon {
+    /// A constrained Delaunay triangulation of this polygon minus `holes`, on the plane found by [`Polygon::estimate_pose`].
+    /// Use [`Cdt`] directly for extra segments, points, or a minimum angle.
+    pub fn triangulate_cdt(&self, holes: &[Polygon]) -> Result<Mesh, CdtError> {
+        holes
+            .iter()
+            .fold(Cdt::from_polygon(self), |cdt, hole| cdt.with_hole(hole))
+            .triangulate()
+    }
+}
+
+/// Where a point is, relative to a triangulation
+enum Location {
+    Inside(usize),
+    /// On the edge `i` of triangle `t`
+    OnEdge(usize, usize),
+    Vertex(usize),
+}
+
+/// A triangulation of points on a plane, inside a large super triangle formed by its first three points.
+/// Triangles are counter clockwise. The neighbor of triangle `t` across edge `i`,
+/// from corner `i` to corner `i + 1`, is `adjacent[t][i]`.
+pub(crate) struct Triangulation {
+    pub points: Vec<Vec2>,
+    pub triangles: Vec<[usize; 3]>,
+    adjacent: Vec<[usize; 3]>,
+    /// Constrained edges as (smallest, largest) pairs, and whether they are a boundary
+    constrained: HashMap<(usize, usize), bool>,
+    /// Some triangle touching every vertex
+    vert_triangle: Vec<usize>,
+    tolerance: fxx,
+    last: usize,
+}
+
+impl Triangulation {
+    /// An empty triangulation, large enough to contain `points`
+    pub fn new(points: &[Vec2]) -> Option<Self> {
+        let min = points.iter().fold(Vec2::splat(fxx::MAX), |m, p| m.min(*p));
+        let max = points.iter().fold(Vec2::splat(fxx::MIN), |m, p| m.max(*p));
+        let extent = (max - min).max_element();
+        if points.is_empty() || extent <= 0.0 {
+            return None;
+        }
+        let center = (min + max) * 0.5;
+        let size = extent * CDT_SUPER_TRIANGLE_SCALE;
+        let magnitude = min.abs().max(max.abs()).max_element().max(extent);
+        Some(Self {
+            points: vec![
+                center + Vec2::new(-size, -size),
+                center + Vec2::new(size, -size),
+                center + Vec2::new(0.0, size),
+            ],
+            triangles: vec![[0, 1, 2]],
+            adjacent: vec![[NONE; 3]],
+            constrained: HashMap::new(),
+            vert_triangle: vec![0; 3],
+            tolerance: magnitude * fxx::EPSILON * CDT_TOLERANCE_FACTOR,
+            last: 0,
+        })
+    }
+
+    /// Add a point, or return the existing vertex at its position
+    pub fn insert_point(&mut self, point: Vec2) -> usize {
+        match self.locate(point) {
+            Location::Vertex(v) => v,
+            Location::Inside(t) => self.split_triangle(t, point),
+            Location::OnEdge(t, i) => self.split_edge(t, i, point),
+        }
+    }
+
+    /// Force the edge between vertices `a` and `b` into the triangulation.
+    /// A `boundary` separates the inside from the outside.
+    pub fn insert_constraint(
+        &mut self,
+        a: usize,
+        b: usize,
+        boundary: bool,
+    ) -> Result<(), CdtError> {
+        if a == b {
+            return Ok(());
+        }
+        if self.find_edge(a, b).is_some() {
+            let entry = self.constrained.entry(key(a, b)).or_insert(boundary);
+            *entry |= boundary;
+            return Ok(());
+        }
+
+        // walk from a to b, collecting the crossed edges, and splitting at vertices on the segment
+        let (mut left, mut right) = self.first_crossing(a, b)?;
+        if left == right {
+            self.insert_constraint(a, left, boundary)?;
+            return self.insert_constraint(left, b, boundary);
+        }
+        let mut crossed = VecDeque::from([(left, right)]);
+        loop {
+            let (t, i) = self.find_edge(left, right).ok_or(CdtError::Constraint)?;
+            let beyond = self.triangles[t][(i + 2) % 3];
+            if beyond == b {
+                break;
+            }
+            let side = self.side(a, b, self.points[beyond]);
+            if side == 0 {
+                self.insert_constraint(a, beyond, boundary)?;
+                return self.insert_constraint(beyond, b, boundary);
+            }
+            match side > 0 {
+                true => left = beyond,
+                false => right = beyond,
+            }
+            crossed.push_back((left, right));
+        }
+
+        // flip crossed edges away, see Sloan, A fast algorithm for generating constrained Delaunay triangulations
+        let mut created = Vec::new();
+        let mut attempts = 0;
+        while let Some((u, v)) = crossed.pop_front() {
+            attempts += 1;
+            if attempts > 100 * (self.points.len() + 10) {
+                return Err(CdtError::Constraint);
+            }
+            let (t, i) = self.find_edge(u, v).ok_or(CdtError::Constraint)?;
+            let c = self.triangles[t][(i + 2) % 3];
+            let n = self.adjacent[t][i];
+            let d = self.opposite(n, u, v);
+            let [pu, pv, pc, pd] = [u, v, c, d].map(|v| self.points[v]);
+            let convex = orient(pc, pd, pu) * orient(pc, pd, pv) < 0.0;
+            if !convex {
+                crossed.push_back((u, v));
+                continue;
+            }
+            self.flip(t, i);
+            let crosses = ![c, d].contains(&a)
+                && ![c, d].contains(&b)
+                && self.side(a, b, pc) * self.side(a, b, pd) < 0;
+            match crosses {
+                true => crossed.push_back((c, d)),
+                false => created.push((c, d)),
+            }
+        }
+        self.constrained.insert(key(a, b), boundary);
+
+        // restore the delaunay property around the new edges
+        let stack = created
+            .into_iter()
+            .filter(|(c, d)| !self.constrained.contains_key(&key(*c, *d)))
+            .filter_map(|(c, d)| self.find_edge(c, d))
+            .collect();
+        self.legalize(stack);
+        Ok(())
+    }
+
+    /// Constrain the edges of the convex hull. Without this, removing the super triangle can leave
+    /// small dents in the hull, where the super triangle was in the way of the last few flips
+    pub fn insert_hull(&mut self) -> Result<(), CdtError> {
+        let mut order = (3..self.points.len()).collect::<Vec<_>>();
+        order.sort_by(|a, b| {
+            let (pa, pb) = (self.points[*a], self.points[*b]);
+            pa.x.total_cmp(&pb.x).then(pa.y.total_cmp(&pb.y))
+        });
+
+        // Andrew's monotone chain: the lower hull from left to right, then the upper hull back
+        let mut hull: Vec<usize> = Vec::new();
+        for pass in [order.clone(), order.into_iter().rev().collect()] {
+            let start = hull.len();
+            for v in pass {
+                while hull.len() >= start + 2
+                    && orient(
+                        self.points[hull[hull.len() - 2]],
+                        self.points[hull[hull.len() - 1]],
+                        self.points[v],
+                    ) <= 0.0
+                {
+                    hull.pop();
+                }
+                hull.push(v);
+            }
+            hull.pop();
+        }
+        for i in 0..hull.len() {
+            self.insert_constraint(hull[i], hull[(i + 1) % hull.len()], false)?;
+        }
+        Ok(())
+    }
+
+    /// Split segments where they cross each other, and insert the crossings as vertices
+    pub fn split_crossings(
+        &mut self,
+        segments: Vec<(usize, usize, bool)>,
+    ) -> Vec<(usize, usize, bool)> {
+        let mut cuts = vec![Vec::new(); segments.len()];
+        for (i, (a, b, _)) in segments.iter().enumerate() {
+            for (j, (c, d, _)) in segments.iter().enumerate().skip(i + 1) {
+                if [a, b].contains(&c) || [a, b].contains(&d) {
+                    continue;
+                }
+                let [pa, pb, pc, pd] = [*a, *b, *c, *d].map(|v| self.points[v]);
+                let (ab, cd) = (pb - pa, pd - pc);
+                let denom = ab.perp_dot(cd);
+                if denom.abs() <= fxx::EPSILON * ab.length() * cd.length() {
+                    continue;
+                }
+                let s = (pc - pa).perp_dot(cd) / denom;
+                let t = (pc - pa).perp_dot(ab) / denom;
+                if s > 0.0 && s < 1.0 && t > 0.0 && t < 1.0 {
+                    let point = pa + ab * s;
+                    cuts[i].push((s, point));
+                    cuts[j].push((t, point));
+                }
+            }
+        }
+        let mut split = Vec::new();
+        for ((a, b, boundary), mut cuts) in segments.into_iter().zip(cuts) {
+            cuts.sort_by(|x, y| x.0.total_cmp(&y.0));
+            let mut from = a;
+            for (_, point) in cuts {
+                let to = self.insert_point(point);
+                split.push((from, to, boundary));
+                from = to;
+            }
+            split.push((from, b, boundary));
+        }
+        split
+    }
+
+    /// Ruppert's algorithm: split segments which have a vertex within their diametral circle,
+    /// and add the circumcenters of bad triangles, until everything is good enough
+    pub fn refine(&mut self, min_angle: fxx, max_area: fxx) {
+        let input = self.points.len();
+        let limit = input + CDT_MAX_STEINER_POINTS;
+        let has_boundaries = self.constrained.values().any(|boundary| *boundary);
+        while self.points.len() < limit {
+            let count = self.points.len();
+            let mut encroached = self
+                .constrained
+                .keys()
+                .copied()
+                .filter(|(a, b)| self.is_encroached(*a, *b))
+                .collect::<Vec<_>>();
+            encroached.sort();
+            if !encroached.is_empty() {
+                for (a, b) in encroached {
+                    self.split_segment(a, b, input);
+                }
+            } else {
+                let inside = self.inside(has_boundaries);
+                let bad = (0..self.triangles.len())
+                    .filter(|t| inside[*t] && self.is_bad(*t, min_angle, max_area))
+                    .map(|t| self.triangles[t])
+                    .collect::<Vec<_>>();
+                for verts in bad {
+                    let Some(t) = self.find_triangle(verts) else {
+                        continue;
+                    };
+                    let center = self.circumcenter(t);
+                    let mut encroached = self
+                        .constrained
+                        .keys()
+                        .copied()
+                        .filter(|(a, b)| {
+                            let (pa, pb) = (self.points[*a], self.points[*b]);
+                            (center - (pa + pb) * 0.5).length_squared()
+                                < (pb - pa).length_squared() * 0.25
+                        })
+                        .collect::<Vec<_>>();
+                    encroached.sort();
+                    if encroached.is_empty() {
+                        self.insert_point(center);
+                    }
+                    for (a, b) in encroached {
+                        self.split_segment(a, b, input);
+                    }
+                }
+            }
+            if self.points.len() == count {
+                return;
+            }
+        }
+    }
+
+    /// Split a constrained edge, if it still exists. Edges ending at one of the first `input` vertices
+    /// are split at a power of two away from it, so that segments meeting at small angles are split at
+    /// the same distances, and stop encroaching on each other
+    fn split_segment(&mut self, a: usize, b: usize, input: usize) {
+        if self.find_edge(a, b).is_none() {
+            return;
+        }
+        let (pa, pb) = (self.points[a], self.points[b]);
+        let length = pa.distance(pb);
+        let shell = (2.0 as fxx).powf((length * 0.5).log2().round()) / length;
+        let t = match (a < input, b < input) {
+            (true, false) => shell,
+            (false, true) => 1.0 - shell,
+            _ => 0.5,
+        };
+        self.insert_point(pa.lerp(pb, t));
+    }
+
+    /// Which triangles are inside the boundaries, found by flooding from the super triangle,
+    /// and switching between outside and inside at every boundary.
+    /// Without boundaries, every triangle not touching the super triangle is inside.
+    pub fn inside(&self, has_boundaries: bool) -> Vec<bool> {
+        let outer = |t: usize| self.triangles[t].iter().any(|v| *v < 3);
+        if !has_boundaries {
+            return (0..self.triangles.len()).map(|t| !outer(t)).collect();
+        }
+        let mut depth = vec![usize::MAX; self.triangles.len()];
+        let mut queue = VecDeque::new();
+        for t in (0..self.triangles.len()).filter(|t| outer(*t)) {
+            depth[t] = 0;
+            queue.push_back(t);
+        }
+        while let Some(t) = queue.pop_front() {
+            for i in 0..3 {
+                let n = self.adjacent[t][i];
+                if n == NONE {
+                    continue;
+                }
+                let edge = key(self.triangles[t][i], self.triangles[t][(i + 1) % 3]);
+                let crossing = self.constrained.get(&edge) == Some(&true);
+                let next = depth[t] + crossing as usize;
+                if next < depth[n] {
+                    depth[n] = next;
+                    match crossing {
+                        true => queue.push_back(n),
+                        false => queue.push_front(n),
+                    }
+                }
+            }
+        }
+        depth.into_iter().map(|d| d % 2 == 1).collect()
+    }
+
+    /// The vertices without the super triangle, and the triangles which are `inside`
+    pub fn export(&self, inside: &[bool]) -> (Vec<Vec2>, Vec<[usize; 3]>) {
+        let triangles = self
+            .triangles
+            .iter()
+            .zip(inside)
+            .filter(|(_, inside)| **inside)
+            .map(|(verts, _)| verts.map(|v| v - 3))
+            .collect();
+        (self.points[3..].to_vec(), triangles)
+    }
+
+    fn locate(&mut self, point: Vec2) -> Location {
+        let mut t = self.last;
+        let mut steps = 0;
+        'walk: while steps < self.triangles.len() * 3 {
+            let verts = self.triangles[t];
+            for k in 0..3 {
+                let i = (k + steps) % 3;
+                let (a, b) = (verts[i], verts[(i + 1) % 3]);
+                let n = self.adjacent[t][i];
+                if n != NONE && self.distance(a, b, point) < -self.tolerance {
+                    t = n;
+                    steps += 1;
+                    continue 'walk;
+                }
+            }
+            return self.locate_in(t, point);
+        }
+
+        // walking in circles, which can happen in a triangulation which isn't delaunay
+        let t = (0..self.triangles.len())
+            .find(|t| {
+                (0..3).all(|i| {
+                    let verts = self.triangles[*t];
+                    self.distance(verts[i], verts[(i + 1) % 3], point) >= -self.tolerance
+                })
+            })
+            .unwrap_or(0);
+        self.locate_in(t, point)
+    }
+
+    fn locate_in(&mut self, t: usize, point: Vec2) -> Location {
+        self.last = t;
+        let verts = self.triangles[t];
+        if let Some(v) = verts
+            .iter()
+            .find(|v| self.points[**v].distance(point) <= self.tolerance)
+        {
+            return Location::Vertex(*v);
+        }
+        match (0..3)
+            .find(|i| self.distance(verts[*i], verts[(i + 1) % 3], point).abs() <= self.tolerance)
+        {
+            Some(i) => Location::OnEdge(t, i),
+            None => Location::Inside(t),
+        }
+    }
+
+    fn split_triangle(&mut self, t: usize, point: Vec2) -> usize {
+        let p = self.add_point(point);
+        let [a, b, c] = self.triangles[t];
+        let [n0, n1, n2] = self.adjacent[t];
+        let (t1, t2) = (self.triangles.len(), self.triangles.len() + 1);
+        self.set(t, [a, b, p], [n0, t1, t2]);
+        self.push([b, c, p], [n1, t2, t]);
+        self.push([c, a, p], [n2, t, t1]);
+        self.replace_neighbor(n1, t, t1);
+        self.replace_neighbor(n2, t, t2);
+        self.legalize(vec![(t, 0), (t1, 0), (t2, 0)]);
+        p
+    }
+
+    fn split_edge(&mut self, t: usize, i: usize, point: Vec2) -> usize {
+        let [a, b, c] = rotate(self.triangles[t], i);
+        let [_, n_bc, n_ca] = rotate(self.adjacent[t], i);
+        let u = self.adjacent[t][i];
+        if u == NONE {
+            return self.split_triangle(t, point);
+        }
+        let j = self.edge_index(u, b, a);
+        let [_, _, d] = rotate(self.triangles[u], j);
+        let [_, m_ad, m_db] = rotate(self.adjacent[u], j);
+
+        let p = self.add_point(point);
+        let (t_new, u_new) = (self.triangles.len(), self.triangles.len() + 1);
+        self.set(t, [a, p, c], [u_new, t_new, n_ca]);
+        self.push([p, b, c], [u, n_bc, t]);
+        self.set(u, [b, p, d], [t_new, u_new, m_db]);
+        self.push([p, a, d], [t, m_ad, u]);
+        self.replace_neighbor(n_bc, t, t_new);
+        self.replace_neighbor(m_ad, u, u_new);
+
+        if let Some(boundary) = self.constrained.remove(&key(a, b)) {
+            self.constrained.insert(key(a, p), boundary);
+            self.constrained.insert(key(p, b), boundary);
+        }
+        self.legalize(vec![(t, 2), (t_new, 1), (u, 2), (u_new, 1)]);
+        p
+    }
+
+    /// Flip edges until every unconstrained edge is locally delaunay
+    fn legalize(&mut self, mut stack: Vec<(usize, usize)>) {
+        while let Some((t, i)) = stack.pop() {
+            let n = self.adjacent[t][i];
+            let [a, b, c] = rotate(self.triangles[t], i);
+            if n == NONE || self.constrained.contains_key(&key(a, b)) {
+                continue;
+            }
+            let d = self.opposite(n, a, b);
+            let [pa, pb, pc, pd] = [a, b, c, d].map(|v| self.points[v]);
+            if incircle(pa, pb, pc, pd) > 0.0 {
+                self.flip(t, i);
+                stack.extend([(t, 0), (t, 1), (n, 0), (n, 1)]);
+            }
+        }
+    }
+
+    /// Replace the edge `i` of triangle `t` by the other diagonal of the two triangles touching it
+    fn flip(&mut self, t: usize, i: usize) {
+        let [a, b, c] = rotate(self.triangles[t], i);
+        let [_, tb, ta] = rotate(self.adjacent[t], i);
+        let u = self.adjacent[t][i];
+        let j = self.edge_index(u, b, a);
+        let [_, _, d] = rotate(self.triangles[u], j);
+        let [_, ua, ub] = rotate(self.adjacent[u], j);
+
+        self.set(t, [c, a, d], [ta, ua, u]);
+        self.set(u, [d, b, c], [ub, tb, t]);
+        self.replace_neighbor(ua, u, t);
+        self.replace_neighbor(tb, t, u);
+    }
+
+    /// The first edge crossed when walking from vertex `a` to vertex `b`, as its vertices left and right of the walk
+    fn first_crossing(&self, a: usize, b: usize) -> Result<(usize, usize), CdtError> {
+        for (t, k) in self.fan(a) {
+            let verts = self.triangles[t];
+            let (right, left) = (verts[(k + 1) % 3], verts[(k + 2) % 3]);
+            let (pa, pb) = (self.points[a], self.points[b]);
+            for v in [right, left] {
+                let pv = self.points[v];
+                if self.side(a, b, pv) == 0 && (pv - pa).dot(pb - pa) > 0.0 {
+                    // a vertex right on the segment, returned as both sides
+                    return Ok((v, v));
+                }
+            }
+            if orient(pa, self.points[right], pb) > 0.0 && orient(pa, self.points[left], pb) < 0.0 {
+                return Ok((left, right));
+            }
+        }
+        Err(CdtError::Constraint)
+    }
+
+    /// The triangles around vertex `v`, with the corner `v` is at
+    fn fan(&self, v: usize) -> Vec<(usize, usize)> {
+        let start = self.vert_triangle[v];
+        let mut fan = Vec::new();
+        let mut t = start;
+        loop {
+            let k = self.triangles[t]
+                .iter()
+                .position(|x| *x == v)
+                .expect("vert_triangle is kept up to date");
+            fan.push((t, k));
+            t = self.adjacent[t][(k + 2) % 3];
+            if t == start || t == NONE || fan.len() > self.triangles.len() {
+                break;
+            }
+        }
+        fan
+    }
+
+    /// The triangle and index of the edge going from `a` to `b`
+    fn find_edge(&self, a: usize, b: usize) -> Option<(usize, usize)> {
+        self.fan(a)
+            .into_iter()
+            .find(|(t, k)| self.triangles[*t][(k + 1) % 3] == b)
+    }
+
+    /// Find a triangle by its vertices, if it still exists
+    fn find_triangle(&self, verts: [usize; 3]) -> Option<usize> {
+        let (t, _) = self.find_edge(verts[0], verts[1])?;
+        (self.triangles[t][(self.edge_index(t, verts[0], verts[1]) + 2) % 3] == verts[2])
+            .then_some(t)
+    }
+
+    fn is_encroached(&self, a: usize, b: usize) -> bool {
+        let Some((t, i)) = self.find_edge(a, b) else {
+            return false;
+        };
+        let (pa, pb) = (self.points[a], self.points[b]);
+        let center = (pa + pb) * 0.5;
+        let radius = (pb - pa).length_squared() * 0.25;
+        let n = self.adjacent[t][i];
+        let mut opposite = vec![self.triangles[t][(i + 2) % 3]];
+        if n != NONE {
+            opposite.push(self.opposite(n, a, b));
+        }
+        opposite
+            .into_iter()
+            .filter(|v| *v >= 3)
+            .any(|v| (self.points[v] - center).length_squared() < radius * (1.0 - 1e-6))
+    }
+
+    fn is_bad(&self, t: usize, min_angle: fxx, max_area: fxx) -> bool {
+        let [a, b, c] = self.triangles[t].map(|v| self.points[v]);
+        let area = orient(a, b, c) * 0.5;
+        if area > max_area {
+            return true;
+        }
+        let [ab, bc, ca] = [(b - a).length(), (c - b).length(), (a - c).length()];
+        let shortest = ab.min(bc).min(ca);
+        if shortest <= self.tolerance * 16.0 {
+            return false;
+        }
+
+        // small angles between two segments can't be improved
+        let verts = self.triangles[t];
+        let corner = [bc, ca, ab]
+            .iter()
+            .position(|l| *l == shortest)
+            .unwrap_or(0);
+        let [v, next, prev] = rotate(verts, corner);
+        let constrained = |u: usize| self.constrained.contains_key(&key(v, u));
+        if constrained(next) && constrained(prev) {
+            return false;
+        }
+        // sin of the smallest angle, which is opposite to the shortest edge
+        let sin = 2.0 * area / (ab * bc * ca) * shortest;
+        sin < min_angle.sin()
+    }
+
+    fn circumcenter(&self, t: usize) -> Vec2 {
+        let [a, b, c] = self.triangles[t].map(|v| self.points[v]);
+        let (b, c) = (b - a, c - a);
+        let d = 2.0 * b.perp_dot(c);
+        let x = c.y * b.length_squared() - b.y * c.length_squared();
+        let y = b.x * c.length_squared() - c.x * b.length_squared();
+        a + Vec2::new(x, y) / d
+    }
+
+    /// The vertex of triangle `t` which is not on edge `b` to `a`
+    fn opposite(&self, t: usize, a: usize, b: usize) -> usize {
+        self.triangles[t][(self.edge_index(t, b, a) + 2) % 3]
+    }
+
+    fn edge_index(&self, t: usize, a: usize, b: usize) -> usize {
+        let verts = self.triangles[t];
+        (0..3)
+            .find(|i| verts[*i] == a && verts[(i + 1) % 3] == b)
+            .expect("neighbors share an edge")
+    }
+
+    /// The signed distance of `point` to the line from vertex `a` to vertex `b`. Positive on the left
+    fn distance(&self, a: usize, b: usize, point: Vec2) -> fxx {
+        let (pa, pb) = (self.points[a], self.points[b]);
+        orient(pa, pb, point) / pa.distance(pb)
+    }
+
+    /// Which side of the line from vertex `a` to vertex `b` `point` is on. Zero within tolerance
+    fn side(&self, a: usize, b: usize, point: Vec2) -> i32 {
+        let distance = self.distance(a, b, point);
+        match distance.abs() <= self.tolerance {
+            true => 0,
+            false => distance.signum() as i32,
+        }
+    }
+
+    fn add_point(&mut self, point: Vec2) -> usize {
+        self.points.push(point);
+        self.vert_triangle.push(0);
+        self.points.len() - 1
+    }
+
+    fn push(&mut self, verts: [usize; 3], adjacent: [usize; 3]) {
+        self.triangles.push(verts);
+        self.adjacent.push(adjacent);
+        self.set(self.triangles.len() - 1, verts, adjacent);
+    }
+
+    fn set(&mut self, t: usize, verts: [usize; 3], adjacent: [usize; 3]) {
+        self.triangles[t] = verts;
+        self.adjacent[t] = adjacent;
+        for v in verts {
+            self.vert_triangle[v] = t;
+        }
+    }
+
+    fn replace_neighbor(&mut self, t: usize, old: usize, new: usize) {
+        if t == NONE {
+            return;
+        }
+        for n in self.adjacent[t].iter_mut().filter(|n| **n == old) {
+            *n = new;
+        }
+    }
+}
+
+fn key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// Rotate the corners of a triangle, so that corner `i` comes first
+fn rotate<T: Copy>(items: [T; 3], i: usize) -> [T; 3] {
+    [items[i], items[(i + 1) % 3], items[(i + 2) % 3]]
+}
+
+/// Positive if `d` lies inside the circle through the counter clockwise triangle `abc`.
+/// Zero if `d` lies on that circle, up to rounding errors, so that cocircular points don't flip back and forth
+fn incircle(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> fxx {
+    let [a, b, c] = [a - d, b - d, c - d];
+    let [la, lb, lc] = [a, b, c].map(|v| v.length_squared());
+    let det =
+        a.x * (b.y * lc - lb * c.y) - a.y * (b.x * lc - lb * c.x) + la * (b.x * c.y - b.y * c.x);
+    let scale = la.max(lb).max(lc);
+    match det.abs() <= scale * scale * fxx::EPSILON * CDT_TOLERANCE_FACTOR {
+        true => 0.0,
+        false => det,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::{vec2, vec3, Quat, TAU};
+    use crate::pts::Vectors;
+
+    fn polygon_2d(points: &[(fxx, fxx)]) -> Polygon {
+        Polygon::new(points.iter().map(|(x, y)| vec3(*x, *y, 0.0)).collect())
+    }
+
+    fn min_angle(verts: &[Vec2], triangles: &[[usize; 3]]) -> fxx {
+        triangles
+            .iter()
+            .flat_map(|t| {
+                (0..3).map(move |i| {
+                    let [a, b, c] = rotate(*t, i).map(|v| verts[v]);
+                    (b - a).angle_between(c - a).abs()
+                })
+            })
+            .fold(fxx::MAX, fxx::min)
+    }
+
+    #[test]
+    fn test_cdt_concave_with_hole() {
+        // an L shape, which a fan triangulation can't handle, with a square hole
+        let shape = polygon_2d(&[
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 4.0),
+            (0.0, 4.0),
+        ]);
+        let hole = polygon_2d(&[(2.0, 0.25), (2.5, 0.25), (2.5, 0.75), (2.0, 0.75)]);
+        let mesh = shape.triangulate_cdt(&[hole]).unwrap();
+        assert!((mesh.area() - (7.0 - 0.25)).abs() < 1e-4);
+        for (a, b, c) in mesh.iter_triangles() {
+            let center = (mesh.verts[a] + mesh.verts[b] + mesh.verts[c]) / 3.0;
+            let in_hole = center.x > 2.0 && center.x < 2.5 && center.y > 0.25 && center.y < 0.75;
+            let in_notch = center.x > 1.0 && center.y > 1.0;
+            assert!(!in_hole && !in_notch);
+        }
+        let normals = mesh.calc_flat_face_normals();
+        assert!(normals.iter().all(|n| n.dot(Vec3::Z) > 0.99));
+    }
+
+    #[test]
+    fn test_cdt_constraints() {
+        let square = polygon_2d(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        let cdt = Cdt::new(Pose::default())
+            .with_boundary(&square.verts)
+            .with_segment(vec3(0.1, 0.5, 0.0), vec3(0.9, 0.5, 0.0))
+            .with_segment(vec3(0.5, 0.1, 0.0), vec3(0.5, 0.9, 0.0))
+            .with_point(vec3(0.2, 0.8, 0.0));
+        let (verts, triangles) = cdt.triangulate_2d().unwrap();
+
+        // the crossing of the segments is added, and both segments are split there
+        let find = |p: Vec2| verts.iter().position(|v| v.distance(p) < 1e-5).unwrap();
+        let center = find(vec2(0.5, 0.5));
+        let has_edge = |a: usize, b: usize| {
+            triangles
+                .iter()
+                .any(|t| (0..3).any(|i| t[i] == a && t[(i + 1) % 3] == b))
+        };
+        for end in [
+            vec2(0.1, 0.5),
+            vec2(0.9, 0.5),
+            vec2(0.5, 0.1),
+            vec2(0.5, 0.9),
+        ] {
+            let end = find(end);
+            assert!(has_edge(center, end) || has_edge(end, center));
+        }
+        let point = find(vec2(0.2, 0.8));
+        assert!(triangles.iter().any(|t| t.contains(&point)));
+        let area = triangles
+            .iter()
+            .map(|[a, b, c]| orient(verts[*a], verts[*b], verts[*c]) * 0.5)
+            .sum::<fxx>();
+        assert!((area - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_cdt_refinement() {
+        // a long thin polygon produces slivers, unless it is refined
+        let sliver = polygon_2d(&[(0.0, 0.0), (10.0, 0.0), (10.0, 0.5), (5.0, 0.6), (0.0, 0.5)]);
+        let cdt = Cdt::from_polygon(&sliver);
+        let (verts, triangles) = cdt.triangulate_2d().unwrap();
+        assert!(min_angle(&verts, &triangles) < 0.1);
+
+        let (verts, triangles) = cdt.clone().with_min_angle(0.45).triangulate_2d().unwrap();
+        assert!(min_angle(&verts, &triangles) >= 0.45 - 1e-6);
+
+        let (verts, triangles) = cdt.with_max_area(0.1).triangulate_2d().unwrap();
+        for [a, b, c] in triangles.iter() {
+            assert!(orient(verts[*a], verts[*b], verts[*c]) * 0.5 <= 0.1);
+        }
+        let area = triangles
+            .iter()
+            .map(|[a, b, c]| orient(verts[*a], verts[*b], verts[*c]) * 0.5)
+            .sum::<fxx>();
+        assert!((area - 5.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_cdt_tilted() {
+        // a polygon in 3d is triangulated on its own plane, facing the same way
+        let rotation = Quat::from_axis_angle(vec3(1.0, 2.0, 0.5).normalize(), 0.8);
+        let shape = polygon_2d(&[(0.0, 0.0), (3.0, 0.0), (3.0, 2.0), (1.5, 0.5), (0.0, 2.0)]);
+        let tilted = Polygon::new(
+            shape
+                .verts
+                .iter()
+                .map(|v| rotation * *v + Vec3::ONE)
+                .collect(),
+        );
+        let mesh = tilted.triangulate_cdt(&[]).unwrap();
+        assert_eq!(mesh.verts.len(), 5);
+        assert_eq!(mesh.tri.len(), 3 * 3);
+        for (vert, original) in mesh.verts.iter().zip(tilted.verts.iter()) {
+            assert!(vert.distance(*original) < 1e-4);
+        }
+        let normal = tilted.average_normal();
+        assert!(mesh
+            .calc_flat_face_normals()
+            .iter()
+            .all(|n| n.dot(normal) > 0.99));
+    }
+
+    #[test]
+    fn test_cdt_cocircular() {
+        // points on a circle are all equally delaunay, which must not flip edges back and forth
+        let sides = 200;
+        let mut cdt = Cdt::new(Pose::default());
+        for vert in Polygon::new_regular(10.0, sides).verts {
+            cdt = cdt.with_point(vert);
+        }
+        let (verts, triangles) = cdt.triangulate_2d().unwrap();
+        assert_eq!(verts.len(), sides);
+        assert_eq!(triangles.len(), sides - 2);
+        let area = triangles
+            .iter()
+            .map(|[a, b, c]| orient(verts[*a], verts[*b], verts[*c]) * 0.5)
+            .sum::<fxx>();
+        let polygon = 0.5 * sides as fxx * 100.0 * (TAU / sides as fxx).sin();
+        assert!((area - polygon).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_cdt_points_fill_hull() {
+        // a slightly jittered grid has almost flat hull edges, where the super triangle gets in the way
+        let mut cdt = Cdt::new(Pose::default());
+        for i in 0..100 {
+            let x = (i % 10) as fxx + 0.001 * ((i * 7) % 13) as fxx;
+            let y = (i / 10) as fxx + 0.001 * ((i * 5) % 11) as fxx;
+            cdt = cdt.with_point(vec3(x, y, 0.0));
+        }
+        let (verts, triangles) = cdt.triangulate_2d().unwrap();
+        let area = triangles
+            .iter()
+            .map(|[a, b, c]| orient(verts[*a], verts[*b], verts[*c]) * 0.5)
+            .sum::<fxx>();
+
+        // the area of the convex hull, as the volume of a unit high prism
+        let prism = verts
+            .iter()
+            .flat_map(|v| [v.extend(0.0), v.extend(1.0)])
+            .collect();
+        let hull = Vectors::new(prism).convex_hull().unwrap();
+        assert!((area - hull.volume().unwrap()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cdt_errors() {
+        let line = polygon_2d(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)]);
+        let cdt = Cdt::new(Pose::default()).with_boundary(&line.verts);
+        assert_eq!(cdt.triangulate().unwrap_err(), CdtError::Degenerate);
+        let cdt = Cdt::new(Pose::default()).with_point(vec3(fxx::NAN, 0.0, 0.0));
+        assert_eq!(cdt.triangulate().unwrap_err(), CdtError::NotFinite);
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::kernel::{fxx, Vec3};
+use crate::util::{find, join};
+
+/// Points closer to a plane than this many machine epsilons, scaled by the size of the input, are considered on the plane
+const HULL_TOLERANCE_FACTOR: fxx = 64.0;
+
+/// A triangle of the hull under construction, with the points in front of it
+struct HullFace {
+    verts: [usize; 3],
+    normal: Vec3,
+    offset: fxx,
+    outside: Vec<usize>,
+    alive: bool,
+}
+
+impl HullFace {
+    fn new(points: &[Vec3], verts: [usize; 3]) -> Self {
+        let [a, b, c] = verts.map(|v| points[v]);
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        Self {
+            verts,
+            normal,
+            offset: normal.dot(a),
+            outside: Vec::new(),
+            alive: true,
+        }
+    }
+
+    fn distance(&self, point: Vec3) -> fxx {
+        self.normal.dot(point) - self.offset
+    }
+
+    fn edges(&self) -> [(usize, usize); 3] {
+        let [a, b, c] = self.verts;
+        [(a, b), (b, c), (c, a)]
+    }
+}
+
+/// The convex hull of `points`, using quickhull.
+/// Returns counter clockwise triangles as seen from the outside, as indices into `points`.
+///
+/// Returns `None` if the points don't enclose any volume, because there are too few of them, or because they are all coplanar.
+/// Points which don't stick out of the hull found so far are skipped, so duplicates and points in the middle of flat sides are left out.
+/// Non-finite points are ignored. The result only depends on the points and their order.
+pub fn convex_hull(points: &[Vec3]) -> Option<Vec<[usize; 3]>> {
+    let tolerance = hull_tolerance(points);
+    let [a, b, c, d] = initial_simplex(points, tolerance)?;
+
+    let mut faces = Vec::new();
+    for (verts, other) in [
+        ([a, b, c], d),
+        ([a, b, d], c),
+        ([a, c, d], b),
+        ([b, c, d], a),
+    ] {
+        let mut face = HullFace::new(points, verts);
+        if face.distance(points[other]) > 0.0 {
+            face = HullFace::new(points, [verts[0], verts[2], verts[1]]);
+        }
+        faces.push(face);
+    }
+    let mut edges = HashMap::new();
+    for (f, face) in faces.iter().enumerate() {
+        for edge in face.edges() {
+            edges.insert(edge, f);
+        }
+    }
+    let candidates = (0..points.len())
+        .filter(|p| points[*p].is_finite() && ![a, b, c, d].contains(p))
+        .collect::<Vec<_>>();
+    assign(points, &mut faces, 0, candidates, tolerance);
+
+    while let Some(f) = faces
+        .iter()
+        .position(|face| face.alive && !face.outside.is_empty())
+    {
+        // the point furthest away from this face is certainly on the hull
+        let mut eye = faces[f].outside[0];
+        for p in faces[f].outside.iter().copied() {
+            if faces[f].distance(points[p]) > faces[f].distance(points[eye]) {
+                eye = p;
+            }
+        }
+
+        // all connected faces which can see the eye
+        let mut visible = vec![f];
+        let mut seen = HashSet::from([f]);
+        let mut stack = vec![f];
+        while let Some(g) = stack.pop() {
+            for (from, to) in faces[g].edges() {
+                let neighbor = edges[&(to, from)];
+                if seen.insert(neighbor) && faces[neighbor].distance(points[eye]) > tolerance {
+                    visible.push(neighbor);
+                    stack.push(neighbor);
+                }
+            }
+        }
+        let visible_set = visible.iter().copied().collect::<HashSet<_>>();
+        let horizon = visible
+            .iter()
+            .flat_map(|g| faces[*g].edges())
+            .filter(|(from, to)| !visible_set.contains(&edges[&(*to, *from)]))
+            .collect::<Vec<_>>();
+
+        // replace the visible faces by a cone from the horizon to the eye
+        let mut orphans = Vec::new();
+        for g in visible {
+            faces[g].alive = false;
+            orphans.append(&mut faces[g].outside);
+            for edge in faces[g].edges() {
+                edges.remove(&edge);
+            }
+        }
+        let first = faces.len();
+        for (from, to) in horizon {
+            let face = HullFace::new(points, [from, to, eye]);
+            for edge in face.edges() {
+                edges.insert(edge, faces.len());
+            }
+            faces.push(face);
+        }
+        orphans.retain(|p| *p != eye);
+        assign(points, &mut faces, first, orphans, tolerance);
+    }
+
+    Some(
+        faces
+            .into_iter()
+            .filter(|face| face.alive)
+            .map(|face| face.verts)
+            .collect(),
+    )
+}
+
+/// [`convex_hull`], with neighboring coplanar triangles merged into convex polygons.
+/// Returns counter clockwise polygons as seen from the outside, as indices into `points`.
+pub fn convex_hull_faces(points: &[Vec3]) -> Option<Vec<Vec<usize>>> {
+    let tolerance = hull_tolerance(points);
+    let triangles = convex_hull(points)?;
+    let faces = triangles
+        .iter()
+        .map(|verts| HullFace::new(points, *verts))
+        .collect::<Vec<_>>();
+    let edges = faces
+        .iter()
+        .enumerate()
+        .flat_map(|(f, face)| face.edges().map(|edge| (edge, f)))
+        .collect::<HashMap<_, _>>();
+
+    // group triangles, if the corners of one lie on the plane of the other
+    let mut group = (0..faces.len()).collect::<Vec<_>>();
+    for (f, face) in faces.iter().enumerate() {
+        for (from, to) in face.edges() {
+            let neighbor = edges[&(to, from)];
+            let coplanar = faces[neighbor]
+                .verts
+                .iter()
+                .all(|v| face.distance(points[*v]).abs() <= tolerance);
+            if coplanar {
+                join(&mut group, f, neighbor);
+            }
+        }
+    }
+
+    // walk along the outline of every group
+    let mut outlines = Vec::<(usize, HashMap<usize, usize>)>::new();
+    let mut outline_of_group = HashMap::new();
+    for (f, face) in faces.iter().enumerate() {
+        let g = find(&mut group, f);
+        let outline = *outline_of_group.entry(g).or_insert_with(|| {
+            outlines.push((usize::MAX, HashMap::new()));
+            outlines.len() - 1
+        });
+        for (from, to) in face.edges() {
+            if find(&mut group, edges[&(to, from)]) != g {
+                outlines[outline].0 = outlines[outline].0.min(from);
+                outlines[outline].1.insert(from, to);
+            }
+        }
+    }
+    Some(
+        outlines
+            .into_iter()
+            .map(|(start, next)| {
+                let mut polygon = vec![start];
+                while let Some(v) = next.get(&polygon[polygon.len() - 1]).copied() {
+                    if v == start || polygon.len() >= next.len() {
+                        break;
+                    }
+                    polygon.push(v);
+                }
+                polygon
+            })
+            .collect(),
+    )
+}
+
+/// Scaled to the magnitude of the coordinates, since that is where rounding errors come from
+fn hull_tolerance(points: &[Vec3]) -> fxx {
+    let max = points
+        .iter()
+        .filter(|p| p.is_finite())
+        .fold(Vec3::ZERO, |max, p| max.max(p.abs()));
+    (max.x + max.y + max.z) * fxx::EPSILON * HULL_TOLERANCE_FACTOR
+}
+
+/// Four points spanning a tetrahedron which is as large as quickly possible, or `None` if they are all coplanar
+fn initial_simplex(points: &[Vec3], tolerance: fxx) -> Option<[usize; 4]> {
+    let finite = (0..points.len())
+        .filter(|p| points[*p].is_finite())
+        .collect::<Vec<_>>();
+    let furthest = |distance: &dyn Fn(Vec3) -> fxx| {
+        let mut best = *finite.first()?;
+        for p in finite.iter().copied() {
+            if distance(points[p]) > distance(points[best]) {
+                best = p;
+            }
+        }
+        Some(best)
+    };
+
+    // the two furthest apart of the extremes along the axes
+    let mut extremes = Vec::new();
+    for axis in 0..3 {
+        extremes.push(furthest(&|p: Vec3| -p[axis])?);
+        extremes.push(furthest(&|p: Vec3| p[axis])?);
+    }
+    let (mut a, mut b) = (extremes[0], extremes[1]);
+    for i in extremes.iter().copied() {
+        for j in extremes.iter().copied() {
+            if points[i].distance(points[j]) > points[a].distance(points[b]) {
+                (a, b) = (i, j);
+            }
+        }
+    }
+    let (pa, pb) = (points[a], points[b]);
+    if pa.distance(pb) <= tolerance {
+        return None;
+    }
+
+    // the point furthest from that line, and the point furthest from the plane through all three
+    let to_line = |p: Vec3| (p - pa).cross(pb - pa).length() / (pb - pa).length();
+    let c = furthest(&to_line).filter(|c| to_line(points[*c]) > tolerance)?;
+    let normal = (pb - pa).cross(points[c] - pa).normalize();
+    let to_plane = |p: Vec3| normal.dot(p - pa).abs();
+    let d = furthest(&to_plane).filter(|d| to_plane(points[*d]) > tolerance)?;
+    Some([a, b, c, d])
+}
+
+/// Give every point to the first face it is in front of, trying the faces from `first` onwards before the older ones.
+/// Points behind all faces are inside the hull.
+fn assign(
+    points: &[Vec3],
+    faces: &mut [HullFace],
+    first: usize,
+    candidates: Vec<usize>,
+    tolerance: fxx,
+) {
+    for p in candidates {
+        let face = (first..faces.len())
+            .chain(0..first)
+            .find(|f| faces[*f].alive && faces[*f].distance(points[p]) > tolerance);
+        if let Some(face) = face {
+            faces[face].outside.push(p);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::convex_hull;
+    use crate::algos::test_fixtures::scatter_cube;
+    use crate::kernel::{fxx, vec3, Vec3};
+    use crate::pts::Vectors;
+    use crate::solid::Mesh;
+
+    #[test]
+    fn test_convex_hull_grid() {
+        // a grid of points has lots of coplanar and collinear points, here also duplicated
+        let mut points = (0..125)
+            .map(|i| vec3((i % 5) as fxx, ((i / 5) % 5) as fxx, (i / 25) as fxx) * 0.5 - 1.0)
+            .collect::<Vec<_>>();
+        points.extend(points.clone());
+        let hull = Vectors::new(points.clone()).convex_hull().unwrap();
+        assert_eq!(hull.iter_naked_edges().count(), 0);
+        assert!((hull.volume().unwrap() - 8.0).abs() < 1e-4);
+        assert!(hull.verts.iter().all(|v| v.abs().min_element() > 0.99));
+
+        let hedron = Vectors::new(points).convex_hull_polyhedron().unwrap();
+        assert_eq!(hedron.faces.len(), 6);
+        for face in hedron.faces.iter() {
+            assert_eq!(face.normal.abs().max_element(), 1.0);
+            assert!(face.normal.dot(face.center) > 0.0);
+        }
+        assert!(hedron.edges.iter().all(|edge| edge.face.is_some()));
+    }
+
+    #[test]
+    fn test_convex_hull_scatter() {
+        let points = scatter_cube(500, 7);
+        let triangles = convex_hull(&points).unwrap();
+        assert_eq!(convex_hull(&points).unwrap(), triangles);
+
+        // every point is behind every face
+        for [a, b, c] in triangles.iter().map(|t| t.map(|v| points[v])) {
+            let normal = (b - a).cross(c - a).normalize();
+            assert!(points.iter().all(|p| normal.dot(*p - a) < 1e-4));
+        }
+
+        let hull = Vectors::new(points).convex_hull().unwrap();
+        assert_eq!(hull.iter_naked_edges().count(), 0);
+        let edges = hull.tri.len() / 2;
+        assert_eq!(hull.verts.len() + hull.tri.len() / 3 - edges, 2);
+    }
+
+    #[test]
+    fn test_convex_hull_degenerate() {
+        assert!(convex_hull(&[]).is_none());
+        assert!(convex_hull(&[Vec3::ZERO, Vec3::X, Vec3::Y]).is_none());
+        assert!(convex_hull(&[Vec3::ONE; 10]).is_none());
+
+        // flat, or flat with tiny noise
+        let flat = scatter_cube(50, 3)
+            .into_iter()
+            .map(|p| vec3(p.x, p.y, 0.0))
+            .collect::<Vec<_>>();
+        assert!(convex_hull(&flat).is_none());
+
+        // a tetrahedron, with nans and points inside
+        let mut points = vec![Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z, Vec3::NAN];
+        points.extend(scatter_cube(20, 5).into_iter().map(|p| p.abs() * 0.3));
+        let hull = convex_hull(&points).unwrap();
+        assert_eq!(hull.len(), 4);
+        assert!(hull.iter().flatten().all(|v| *v < 4));
+    }
+
+    #[test]
+    fn test_convex_hull_mesh() {
+        let ico = Mesh::new_icosahedron(1.0);
+        let hull = ico.convex_hull().unwrap();
+        assert_eq!(hull.tri.len(), 60);
+        assert_eq!(hull.verts.len(), 12);
+        assert!((hull.volume().unwrap() - ico.volume().unwrap()).abs() < 1e-6);
+        assert!(Mesh::new_hexagrid(1.0, 2).convex_hull().is_none());
+    }
+}
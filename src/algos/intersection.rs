@@ -1,7 +1,8 @@
 //! I like to split away these intersection / geometric predicates stuff from the main code
 use std::cmp::Ordering;
 
-use crate::kernel::{fxx, Vec3};
+use crate::kernel::{fxx, Vec2, Vec3};
+use crate::util::iter_pair_ids;
 
 // simple 2x2
 #[inline]
@@ -69,6 +70,35 @@ pub fn signed_volume(a: Vec3, b: Vec3, c: Vec3, d: Vec3) -> fxx {
     (1.0 / 6.0) * (a - d).dot((c - d).cross(b - d))
 }
 
+/// Twice the signed area of triangle `abc`, positive if counter clockwise
+#[inline]
+pub(crate) fn orient(a: Vec2, b: Vec2, c: Vec2) -> fxx {
+    (b - a).perp_dot(c - a)
+}
+
+/// The area of a polygon, positive if counter clockwise
+pub(crate) fn signed_area(polygon: &[Vec2]) -> fxx {
+    iter_pair_ids(polygon.len())
+        .map(|(a, b)| polygon[a].perp_dot(polygon[b]))
+        .sum::<fxx>()
+        * 0.5
+}
+
+/// even-odd ray casting
+pub(crate) fn polygon_contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for (a, b) in iter_pair_ids(polygon.len()) {
+        let (a, b) = (polygon[a], polygon[b]);
+        if (a.y > point.y) != (b.y > point.y) {
+            let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
 /// don't know if this works
 pub fn line_x_plane(a: Vec3, b: Vec3, plane_pos: Vec3, plane_normal: Vec3) -> Option<fxx> {
     let ba = a - b;
@@ -1,7 +1,16 @@
+mod cdt;
+mod convex_hull;
 mod earcut;
 mod intersection;
 mod marching_cubes;
+mod voronoi;
 
+#[cfg(test)]
+mod test_fixtures;
+
+pub use cdt::*;
+pub use convex_hull::*;
 pub use earcut::*;
 pub use intersection::*;
 pub use marching_cubes::*;
+pub use voronoi::*;
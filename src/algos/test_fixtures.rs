@@ -0,0 +1,24 @@
+//! Point sets shared by the tests of the algos module
+use crate::kernel::{fxx, vec2, vec3, Vec2, Vec3};
+
+/// A deterministic scatter of points within the unit square
+pub(crate) fn scatter_square(count: usize) -> Vec<Vec2> {
+    (0..count)
+        .map(|i| {
+            let i = i as fxx;
+            vec2((i * 0.618034).fract(), (i * 0.754878 + 0.1).fract())
+        })
+        .collect()
+}
+
+/// repeatable pseudo random points in the cube from -1 to 1
+pub(crate) fn scatter_cube(count: usize, seed: u64) -> Vec<Vec3> {
+    let mut state = seed;
+    let mut next = || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 11) as fxx / (1u64 << 53) as fxx * 2.0 - 1.0
+    };
+    (0..count).map(|_| vec3(next(), next(), next())).collect()
+}
@@ -0,0 +1,517 @@
+//! Delaunay triangulations and Voronoi diagrams of points on the xy plane
+use std::collections::HashSet;
+
+use super::{signed_area, CdtError, Triangulation};
+use crate::kernel::{fxx, Vec2, Vec3};
+use crate::math::Range2;
+use crate::planar::Polygon;
+use crate::pts::Vectors;
+use crate::solid::{Mesh, Normals, Polyhedron, TriMesh};
+
+/// Cell corners closer than this many machine epsilons, scaled by the size of the boundary, are welded together
+const VORONOI_WELD_FACTOR: fxx = 1024.0;
+
+/// The Delaunay triangulation of a set of points: no point lies within the circumcircle of any triangle.
+/// Together, the triangles fill the convex hull of the points.
+#[derive(Debug, Clone)]
+pub struct Delaunay {
+    pub points: Vec<Vec2>,
+    /// Counter clockwise triangles, as indices into `points`. Duplicate points are left out
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Delaunay {
+    pub fn new(points: &[Vec2]) -> Result<Self, CdtError> {
+        if points.iter().any(|p| !p.is_finite()) {
+            return Err(CdtError::NotFinite);
+        }
+        let mut tri = Triangulation::new(points).ok_or(CdtError::Degenerate)?;
+        let ids = points
+            .iter()
+            .map(|p| tri.insert_point(*p))
+            .collect::<Vec<_>>();
+        tri.insert_hull()?;
+
+        // every vertex of the triangulation is the first input point at its position
+        let mut input = vec![0; tri.points.len()];
+        for (i, id) in ids.iter().enumerate().rev() {
+            input[*id] = i;
+        }
+        let inside = tri.inside(false);
+        let (_, triangles) = tri.export(&inside);
+        if triangles.is_empty() {
+            return Err(CdtError::Degenerate);
+        }
+        Ok(Self {
+            points: points.to_vec(),
+            triangles: triangles
+                .into_iter()
+                .map(|t| t.map(|v| input[v + 3]))
+                .collect(),
+        })
+    }
+
+    /// The triangulation of the x and y coordinates of `vectors`
+    pub fn from_vectors(vectors: &Vectors) -> Result<Self, CdtError> {
+        Self::new(
+            &vectors
+                .data
+                .iter()
+                .map(|v| v.truncate())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// The points connected to every point, sorted. Duplicate points have no neighbors
+    pub fn neighbors(&self) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![Vec::new(); self.points.len()];
+        for [a, b, c] in self.triangles.iter().copied() {
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                neighbors[from].push(to);
+                neighbors[to].push(from);
+            }
+        }
+        for list in neighbors.iter_mut() {
+            list.sort();
+            list.dedup();
+        }
+        neighbors
+    }
+
+    /// The triangulation as a mesh on the xy plane, facing up
+    pub fn to_mesh(&self) -> Mesh {
+        Mesh::new(
+            self.points.iter().map(|p| p.extend(0.0)).collect(),
+            self.triangles.concat(),
+            Vec::new(),
+            Normals::None,
+        )
+    }
+}
+
+/// A Voronoi diagram: the area around every site which is closer to it than to any other site,
+/// clipped to a boundary.
+///
+/// The cells of a convex boundary are convex polygons. A concave boundary can cut a cell into several pieces.
+/// Sites outside the boundary can still have a cell reaching into it, and duplicate sites have no cell at all.
+///
+/// ```
+/// use hedron::algos::Voronoi;
+/// use hedron::kernel::vec2;
+/// use hedron::math::Range2;
+///
+/// let sites = vec![vec2(0.25, 0.5), vec2(0.75, 0.5)];
+/// let voronoi = Voronoi::new(&sites, &Range2::UNIT).unwrap();
+/// assert!((voronoi.area(0) - 0.5).abs() < 1e-4);
+///
+/// let partition = voronoi.to_polyhedron().unwrap();
+/// assert_eq!(partition.faces.len(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Voronoi {
+    sites: Vec<Vec2>,
+    boundary: Vec<Vec2>,
+    cells: Vec<Vec<Vec<Vec2>>>,
+}
+
+impl Voronoi {
+    /// The voronoi diagram of `sites`, within `bounds`
+    pub fn new(sites: &[Vec2], bounds: &Range2) -> Result<Self, CdtError> {
+        let (min, max) = (
+            Vec2::new(bounds.x.start, bounds.y.start),
+            Vec2::new(bounds.x.end, bounds.y.end),
+        );
+        let corners = vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+        Self::from_boundary(sites, corners)
+    }
+
+    /// The voronoi diagram of `sites`, within the x and y coordinates of `boundary`
+    pub fn new_in_polygon(sites: &[Vec2], boundary: &Polygon) -> Result<Self, CdtError> {
+        Self::from_boundary(sites, boundary.verts.iter().map(|v| v.truncate()).collect())
+    }
+
+    /// The voronoi diagram of the x and y coordinates of `vectors`, within `bounds`
+    pub fn from_vectors(vectors: &Vectors, bounds: &Range2) -> Result<Self, CdtError> {
+        Self::new(
+            &vectors
+                .data
+                .iter()
+                .map(|v| v.truncate())
+                .collect::<Vec<_>>(),
+            bounds,
+        )
+    }
+
+    fn from_boundary(sites: &[Vec2], mut boundary: Vec<Vec2>) -> Result<Self, CdtError> {
+        if sites.iter().chain(boundary.iter()).any(|p| !p.is_finite()) {
+            return Err(CdtError::NotFinite);
+        }
+        if signed_area(&boundary) < 0.0 {
+            boundary.reverse();
+        }
+        if boundary.len() < 3 || signed_area(&boundary) <= 0.0 {
+            return Err(CdtError::Degenerate);
+        }
+        let mut voronoi = Self {
+            sites: sites.to_vec(),
+            boundary,
+            cells: Vec::new(),
+        };
+        voronoi.build();
+        Ok(voronoi)
+    }
+
+    /// Apply `iterations` of Lloyd's relaxation: move every site to the centroid of its cell, and rebuild the diagram.
+    /// The cells grow more and more alike, converging to a centroidal voronoi diagram.
+    /// Sites without a cell stay where they are.
+    pub fn relaxed(mut self, iterations: usize) -> Self {
+        for _ in 0..iterations {
+            self.sites = (0..self.sites.len())
+                .map(|site| self.centroid(site).unwrap_or(self.sites[site]))
+                .collect();
+            self.build();
+        }
+        self
+    }
+
+    pub fn sites(&self) -> &[Vec2] {
+        &self.sites
+    }
+
+    /// The counter clockwise boundary the cells are clipped to
+    pub fn boundary(&self) -> &[Vec2] {
+        &self.boundary
+    }
+
+    /// The counter clockwise polygons making up the cell of `site`.
+    /// Usually one, but none if the cell lies outside the boundary, and several if a concave boundary cuts it
+    pub fn cell(&self, site: usize) -> &[Vec<Vec2>] {
+        &self.cells[site]
+    }
+
+    pub fn area(&self, site: usize) -> fxx {
+        self.cells[site]
+            .iter()
+            .map(|piece| signed_area(piece))
+            .sum()
+    }
+
+    /// The center of mass of the cell of `site`, if it has any area
+    pub fn centroid(&self, site: usize) -> Option<Vec2> {
+        let mut weighted = Vec2::ZERO;
+        let mut area = 0.0;
+        for piece in self.cells[site].iter() {
+            for (i, a) in piece.iter().enumerate() {
+                let b = piece[(i + 1) % piece.len()];
+                let cross = a.perp_dot(b);
+                weighted += (*a + b) * cross;
+                area += cross * 0.5;
+            }
+        }
+        (area > 0.0).then(|| weighted / (6.0 * area))
+    }
+
+    /// The diagram as a planar partition on the xy plane, with a face for every piece of every cell.
+    /// Neighboring cells share their vertices and edges, and the boundary is a loop of edges without a face.
+    ///
+    /// Returns `None` if the cells don't fit together, which only happens with nearly degenerate sites.
+    pub fn to_polyhedron(&self) -> Option<Polyhedron> {
+        let pieces = self.cells.iter().flatten().collect::<Vec<_>>();
+        let soup = pieces
+            .iter()
+            .flat_map(|piece| piece.iter().map(|p| p.extend(0.0)))
+            .collect::<Vec<Vec3>>();
+
+        // weld the corners of neighboring cells
+        let root = TriMesh::weld_roots(&soup, self.tolerance());
+        let mut index = vec![usize::MAX; soup.len()];
+        let mut verts = Vec::new();
+        for i in 0..soup.len() {
+            let r = root[i];
+            if index[r] == usize::MAX {
+                index[r] = verts.len();
+                verts.push(soup[r]);
+            }
+            index[i] = index[r];
+        }
+
+        let mut offset = 0;
+        let mut faces = Vec::new();
+        for piece in pieces {
+            let mut face = (offset..offset + piece.len())
+                .map(|i| index[i])
+                .collect::<Vec<_>>();
+            face.dedup();
+            while face.len() > 1 && face.first() == face.last() {
+                face.pop();
+            }
+            if face.len() > 2 && face.iter().collect::<HashSet<_>>().len() == face.len() {
+                faces.push(face);
+            }
+            offset += piece.len();
+        }
+        Polyhedron::from_faces(&verts, &faces)
+    }
+
+    /// Clip the boundary with the half planes between every site and its neighbors
+    fn build(&mut self) {
+        let neighbors = match Delaunay::new(&self.sites) {
+            Ok(delaunay) => delaunay.neighbors(),
+            // collinear sites, or too few of them. Fall back to comparing every pair
+            Err(_) => (0..self.sites.len())
+                .map(|i| (0..self.sites.len()).filter(|j| *j != i).collect())
+                .collect(),
+        };
+        let tolerance = self.tolerance();
+        self.cells = (0..self.sites.len())
+            .map(|i| {
+                if neighbors[i].is_empty() {
+                    // a duplicate, left out of the triangulation, or the only site
+                    return match self.sites.len() {
+                        1 => vec![self.boundary.clone()],
+                        _ => Vec::new(),
+                    };
+                }
+                let site = self.sites[i];
+                let mut pieces = vec![self.boundary.clone()];
+                for j in neighbors[i].iter().copied() {
+                    let other = self.sites[j];
+                    if other == site {
+                        // only the first of several duplicates keeps its cell
+                        if j < i {
+                            return Vec::new();
+                        }
+                        continue;
+                    }
+                    pieces = pieces
+                        .into_iter()
+                        .flat_map(|piece| clip(&piece, (site + other) * 0.5, other - site))
+                        .collect();
+                }
+                pieces.retain(|piece| signed_area(piece) > tolerance * tolerance);
+                pieces
+            })
+            .collect();
+    }
+
+    fn tolerance(&self) -> fxx {
+        let extent = self
+            .boundary
+            .iter()
+            .fold(0.0 as fxx, |m, p| m.max(p.abs().max_element()));
+        extent * fxx::EPSILON * VORONOI_WELD_FACTOR
+    }
+}
+
+impl Vectors {
+    /// The delaunay triangulation of the x and y coordinates of these vectors
+    pub fn delaunay(&self) -> Result<Delaunay, CdtError> {
+        Delaunay::from_vectors(self)
+    }
+
+    /// The voronoi diagram of the x and y coordinates of these vectors, within `bounds`
+    pub fn voronoi(&self, bounds: &Range2) -> Result<Voronoi, CdtError> {
+        Voronoi::from_vectors(self, bounds)
+    }
+}
+
+/// Clip a counter clockwise polygon to the half plane where `(p - origin).dot(normal) <= 0`.
+/// A concave polygon can fall apart into several pieces.
+fn clip(polygon: &[Vec2], origin: Vec2, normal: Vec2) -> Vec<Vec<Vec2>> {
+    let distance = polygon
+        .iter()
+        .map(|p| (*p - origin).dot(normal))
+        .collect::<Vec<_>>();
+    if distance.iter().all(|d| *d <= 0.0) {
+        return vec![polygon.to_vec()];
+    }
+    if distance.iter().all(|d| *d > 0.0) {
+        return Vec::new();
+    }
+
+    // the vertices inside, and the points where the outline crosses the line.
+    // a crossing is `Some(true)` when leaving the half plane
+    let mut nodes = Vec::new();
+    for i in 0..polygon.len() {
+        let j = (i + 1) % polygon.len();
+        let (a, b) = (distance[i], distance[j]);
+        if a <= 0.0 {
+            nodes.push((polygon[i], None));
+        }
+        if (a <= 0.0) != (b <= 0.0) {
+            let t = (a / (a - b)).clamp(0.0, 1.0);
+            nodes.push((polygon[i].lerp(polygon[j], t), Some(a <= 0.0)));
+        }
+    }
+
+    // along the line, the parts inside the polygon run between pairs of crossings. Every exit continues at the entry of its pair
+    let along = normal.perp();
+    let mut crossings = (0..nodes.len())
+        .filter(|n| nodes[*n].1.is_some())
+        .collect::<Vec<_>>();
+    crossings.sort_by(|a, b| nodes[*a].0.dot(along).total_cmp(&nodes[*b].0.dot(along)));
+    let mut jump = vec![None; nodes.len()];
+    for pair in crossings.chunks(2) {
+        if let [a, b] = pair {
+            match nodes[*a].1 == Some(true) {
+                true => jump[*a] = Some(*b),
+                false => jump[*b] = Some(*a),
+            }
+        }
+    }
+
+    let mut visited = vec![false; nodes.len()];
+    let mut pieces = Vec::new();
+    for start in 0..nodes.len() {
+        let mut piece = Vec::new();
+        let mut n = start;
+        while !visited[n] {
+            visited[n] = true;
+            piece.push(nodes[n].0);
+            n = match (nodes[n].1, jump[n]) {
+                (Some(true), Some(entry)) => entry,
+                _ => (n + 1) % nodes.len(),
+            };
+        }
+        piece.dedup();
+        while piece.len() > 1 && piece.first() == piece.last() {
+            piece.pop();
+        }
+        if piece.len() > 2 {
+            pieces.push(piece);
+        }
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algos::test_fixtures::scatter_square;
+    use crate::kernel::{vec2, vec3};
+
+    #[test]
+    fn test_delaunay() {
+        let mut points = scatter_square(50);
+        points.extend([Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y, Vec2::ONE]);
+        let delaunay = Delaunay::new(&points).unwrap();
+
+        // the triangles fill the hull, and the duplicate corner is not used
+        let area = delaunay
+            .triangles
+            .iter()
+            .map(|t| signed_area(&t.map(|v| points[v])))
+            .sum::<fxx>();
+        assert!((area - 1.0).abs() < 1e-4);
+        assert!(delaunay.triangles.iter().flatten().all(|v| *v != 54));
+        assert!(delaunay.neighbors()[54].is_empty());
+
+        // no point lies within the circumcircle of a triangle
+        for [a, b, c] in delaunay.triangles.iter().map(|t| t.map(|v| points[v])) {
+            let d = 2.0 * (b - a).perp_dot(c - a);
+            let center = a + vec2(
+                (c - a).y * (b - a).length_squared() - (b - a).y * (c - a).length_squared(),
+                (b - a).x * (c - a).length_squared() - (c - a).x * (b - a).length_squared(),
+            ) / d;
+            let radius = center.distance(a);
+            assert!(points.iter().all(|p| p.distance(center) > radius - 1e-4));
+        }
+
+        let collinear = [Vec2::ZERO, Vec2::X, vec2(2.0, 0.0)];
+        assert_eq!(Delaunay::new(&collinear).unwrap_err(), CdtError::Degenerate);
+    }
+
+    #[test]
+    fn test_voronoi_range() {
+        let sites = scatter_square(40);
+        let voronoi = Voronoi::new(&sites, &Range2::UNIT).unwrap();
+        let mut total = 0.0;
+        for (i, site) in sites.iter().enumerate() {
+            // every site lies in its own convex cell
+            let cell = voronoi.cell(i);
+            assert_eq!(cell.len(), 1);
+            let inside = (0..cell[0].len()).all(|j| {
+                (cell[0][(j + 1) % cell[0].len()] - cell[0][j]).perp_dot(*site - cell[0][j]) >= 0.0
+            });
+            assert!(inside);
+            total += voronoi.area(i);
+        }
+        assert!((total - 1.0).abs() < 1e-4);
+
+        // the cells form a single planar partition: V - E + F = 1
+        let partition = voronoi.to_polyhedron().unwrap();
+        let euler = partition.verts.len() as i64 - partition.edges.len() as i64 / 2
+            + partition.faces.len() as i64;
+        assert_eq!(euler, 1);
+        assert_eq!(partition.faces.len(), sites.len());
+        assert!(partition.faces.iter().all(|face| face.normal.z > 0.99));
+        let dual = partition.dual_graph();
+        assert_eq!(dual.verts.len(), sites.len());
+    }
+
+    #[test]
+    fn test_voronoi_concave() {
+        // a U shape, with a site between the legs. Its cell is cut in two
+        let boundary = Polygon::new(
+            [
+                (0.0, 0.0),
+                (3.0, 0.0),
+                (3.0, 3.0),
+                (2.0, 3.0),
+                (2.0, 1.0),
+                (1.0, 1.0),
+                (1.0, 3.0),
+                (0.0, 3.0),
+            ]
+            .iter()
+            .map(|(x, y)| vec3(*x, *y, 0.0))
+            .collect(),
+        );
+        let sites = [vec2(1.5, 2.5), vec2(0.5, 0.5), vec2(2.5, 0.5)];
+        let voronoi = Voronoi::new_in_polygon(&sites, &boundary).unwrap();
+        assert_eq!(voronoi.cell(0).len(), 2);
+        let total = (0..3).map(|i| voronoi.area(i)).sum::<fxx>();
+        assert!((total - 7.0).abs() < 1e-4);
+
+        let partition = voronoi.to_polyhedron().unwrap();
+        assert_eq!(partition.faces.len(), 4);
+    }
+
+    #[test]
+    fn test_voronoi_relax() {
+        let spread = |voronoi: &Voronoi| {
+            let areas = (0..voronoi.sites().len())
+                .map(|i| voronoi.area(i))
+                .collect::<Vec<_>>();
+            let max = areas.iter().copied().fold(0.0, fxx::max);
+            let min = areas.iter().copied().fold(fxx::MAX, fxx::min);
+            max / min
+        };
+        let voronoi = Voronoi::new(&scatter_square(30), &Range2::UNIT).unwrap();
+        let relaxed = voronoi.clone().relaxed(20);
+        assert!(spread(&relaxed) < spread(&voronoi));
+        assert!(spread(&relaxed) < 2.0);
+        let total = (0..30).map(|i| relaxed.area(i)).sum::<fxx>();
+        assert!((total - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_voronoi_degenerate() {
+        // collinear sites give parallel strips, duplicates give nothing
+        let sites = [
+            vec2(0.25, 0.5),
+            vec2(0.5, 0.5),
+            vec2(0.75, 0.5),
+            vec2(0.5, 0.5),
+        ];
+        let voronoi = Voronoi::new(&sites, &Range2::UNIT).unwrap();
+        assert!((voronoi.area(0) - 0.375).abs() < 1e-6);
+        assert!((voronoi.area(1) - 0.25).abs() < 1e-6);
+        assert!(voronoi.cell(3).is_empty());
+
+        let single = Voronoi::new(&[vec2(0.5, 0.5)], &Range2::UNIT).unwrap();
+        assert!((single.area(0) - 1.0).abs() < 1e-6);
+        let nan = Voronoi::new(&[vec2(fxx::NAN, 0.5)], &Range2::UNIT);
+        assert_eq!(nan.unwrap_err(), CdtError::NotFinite);
+    }
+}
@@ -28,6 +28,26 @@ impl From<HedronMesh> for BevyMesh {
 
         mesh.insert_attribute(BevyMesh::ATTRIBUTE_POSITION, verts);
 
+        // known vertex attribute channels. Triangle attributes have no bevy counterpart
+        if let Some(colors) = hmesh.vert_attributes.colors() {
+            if colors.len() == hmesh.verts.len() {
+                let colors = colors
+                    .iter()
+                    .map(|c| c.to_array())
+                    .collect::<Vec<[f32; 4]>>();
+                mesh.insert_attribute(BevyMesh::ATTRIBUTE_COLOR, colors);
+            }
+        }
+        if let Some(tangents) = hmesh.vert_attributes.tangents() {
+            if tangents.len() == hmesh.verts.len() {
+                let tangents = tangents
+                    .iter()
+                    .map(|t| t.to_array())
+                    .collect::<Vec<[f32; 4]>>();
+                mesh.insert_attribute(BevyMesh::ATTRIBUTE_TANGENT, tangents);
+            }
+        }
+
         match hmesh.normals {
             crate::prelude::Normals::Face(_f_normals) => {
                 warn!("ignoring face normals...");
@@ -3,9 +3,11 @@ mod grid2;
 mod grid_pos;
 mod grid_tri;
 mod pool;
+mod spatial_hash;
 
 pub use grid::*;
 pub use grid2::*;
 pub use grid_pos::*;
 pub use grid_tri::*;
 pub use pool::*;
+pub use spatial_hash::*;
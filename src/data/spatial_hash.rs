@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use crate::kernel::{fxx, Vec3, EPSILON};
+
+/// A uniform grid of buckets, used to find points close to each other
+/// without checking every pair.
+///
+/// Points are stored as ids. The hash does not own the points themselves,
+/// so the caller is responsible for doing the exact distance checks.
+#[derive(Debug, Clone)]
+pub struct SpatialHash {
+    cell_size: fxx,
+    cells: HashMap<(i64, i64, i64), Vec<usize>>,
+}
+
+impl SpatialHash {
+    /// `cell_size` should be at least as large as the search radius you plan on using
+    pub fn new(cell_size: fxx) -> Self {
+        Self {
+            cell_size: cell_size.max(EPSILON),
+            cells: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn cell_size(&self) -> fxx {
+        self.cell_size
+    }
+
+    #[inline]
+    pub fn key(&self, p: Vec3) -> (i64, i64, i64) {
+        let k = (p / self.cell_size).floor();
+        (k.x as i64, k.y as i64, k.z as i64)
+    }
+
+    pub fn insert(&mut self, p: Vec3, id: usize) {
+        self.cells.entry(self.key(p)).or_default().push(id);
+    }
+
+    /// all ids stored in the cell of `p`, and the 26 cells surrounding it.
+    /// Every point within `cell_size` of `p` is guaranteed to be in here.
+    pub fn iter_near(&self, p: Vec3) -> impl Iterator<Item = usize> + '_ {
+        let (x, y, z) = self.key(p);
+        (-1..=1)
+            .flat_map(move |dz| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| (dx, dy, dz))))
+            .filter_map(move |(dx, dy, dz)| self.cells.get(&(x + dx, y + dy, z + dz)))
+            .flatten()
+            .copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpatialHash;
+    use crate::kernel::vec3;
+
+    #[test]
+    fn test_near() {
+        let mut hash = SpatialHash::new(0.1);
+        hash.insert(vec3(0.0, 0.0, 0.0), 0);
+        hash.insert(vec3(0.09, 0.0, -0.05), 1);
+        hash.insert(vec3(5.0, 0.0, 0.0), 2);
+
+        let mut near = hash.iter_near(vec3(0.01, 0.01, 0.01)).collect::<Vec<_>>();
+        near.sort();
+        assert_eq!(near, vec![0, 1]);
+        assert_eq!(
+            hash.iter_near(vec3(5.05, 0.0, 0.0)).collect::<Vec<_>>(),
+            vec![2]
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use std::error::Error;
+use std::fmt;
+
+/// Something went wrong while reading a mesh file
+#[derive(Debug)]
+pub enum ParseError {
+    /// the file could not be read in the first place
+    Io(std::io::Error),
+    /// a text based file is malformed at a certain line (starting at 1)
+    Line(usize, String),
+    /// the file is malformed, without a line to point to (binary formats)
+    Format(String),
+}
+
+impl ParseError {
+    pub fn line(line: usize, message: impl Into<String>) -> Self {
+        Self::Line(line, message.into())
+    }
+
+    pub fn format(message: impl Into<String>) -> Self {
+        Self::Format(message.into())
+    }
+}
+
+impl Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Io(err) => write!(f, "Parse error: {}", err),
+            ParseError::Line(line, message) => {
+                write!(f, "Parse error at line {}: {}", line, message)
+            }
+            ParseError::Format(message) => write!(f, "Parse error: {}", message),
+        }
+    }
+}
+
+impl From<std::io::Error> for ParseError {
+    fn from(err: std::io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
@@ -0,0 +1,721 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::core::Pose;
+use crate::kernel::{Vec2, Vec3};
+use crate::solid::{Mesh, Normals, TriMesh};
+
+const GLB_MAGIC: u32 = 0x4654_6C67; // "glTF"
+const GLB_VERSION: u32 = 2;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A; // "JSON"
+const GLB_CHUNK_BIN: u32 = 0x004E_4942; // "BIN\0"
+
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const MODE_TRIANGLES: u32 = 4;
+
+/// A mesh placed in a gltf scene
+#[derive(Debug, Clone)]
+pub struct GltfNode {
+    pub name: String,
+    pub mesh: Mesh,
+    pub pose: Option<Pose>,
+}
+
+/// A flat list of nodes, each holding one mesh, written as a single gltf 2.0 scene.
+///
+/// Positions, normals and uvs are written as f32, indices as u32.
+/// The `.gltf` flavor embeds the buffer as a base64 data uri, the `.glb` flavor stores it in a binary chunk.
+#[derive(Debug, Clone, Default)]
+pub struct GltfScene {
+    pub nodes: Vec<GltfNode>,
+}
+
+/// The vertex data of one mesh, as gltf wants it: every attribute indexed by the same index
+struct GltfPrimitive {
+    positions: Vec<Vec3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<Vec2>>,
+    indices: Vec<u32>,
+}
+
+impl GltfScene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_mesh(mut self, name: &str, mesh: Mesh, pose: Option<Pose>) -> Self {
+        self.add_mesh(name, mesh, pose);
+        self
+    }
+
+    pub fn add_mesh(&mut self, name: &str, mesh: Mesh, pose: Option<Pose>) {
+        self.nodes.push(GltfNode {
+            name: name.to_owned(),
+            mesh,
+            pose,
+        });
+    }
+
+    pub fn add_tri_mesh(&mut self, name: &str, tri_mesh: TriMesh, pose: Option<Pose>) {
+        self.add_mesh(name, Mesh::from_tri_mesh(tri_mesh), pose);
+    }
+
+    pub fn write_gltf(&self, path: &str) -> Result<(), std::io::Error> {
+        let gltf = self.gen_gltf_string();
+        let mut gltf_file = std::fs::File::create(path)?;
+        gltf_file.write_all(gltf.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_glb(&self, path: &str) -> Result<(), std::io::Error> {
+        let glb = self.gen_glb_buffer();
+        let mut glb_file = std::fs::File::create(path)?;
+        glb_file.write_all(&glb)?;
+        Ok(())
+    }
+
+    /// A self-contained `.gltf` json document
+    pub fn gen_gltf_string(&self) -> String {
+        let bin = self.gen_bin();
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            encode_base64(&bin)
+        );
+        self.gen_json(bin.len(), Some(&uri))
+    }
+
+    /// A `.glb` file: the json chunk, followed by the binary chunk
+    pub fn gen_glb_buffer(&self) -> Vec<u8> {
+        let mut bin = self.gen_bin();
+        let mut json = self.gen_json(bin.len(), None).into_bytes();
+
+        // both chunks need to be aligned to 4 bytes
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        while bin.len() % 4 != 0 {
+            bin.push(0);
+        }
+
+        let total = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::with_capacity(total);
+        for word in [GLB_MAGIC, GLB_VERSION, total as u32] {
+            glb.extend_from_slice(&word.to_le_bytes());
+        }
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&GLB_CHUNK_JSON.to_le_bytes());
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&GLB_CHUNK_BIN.to_le_bytes());
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    fn primitives(&self) -> Vec<Option<GltfPrimitive>> {
+        self.nodes
+            .iter()
+            .map(|node| GltfPrimitive::from_mesh(&node.mesh))
+            .collect()
+    }
+
+    /// all attributes of all meshes, one after the other. Everything is 4 bytes wide, so no padding is needed
+    fn gen_bin(&self) -> Vec<u8> {
+        let mut bin = Vec::new();
+        for prim in self.primitives().iter().flatten() {
+            push_vec3s(&mut bin, &prim.positions);
+            if let Some(normals) = &prim.normals {
+                push_vec3s(&mut bin, normals);
+            }
+            if let Some(uvs) = &prim.uvs {
+                for uv in uvs {
+                    bin.extend_from_slice(&(uv.x as f32).to_le_bytes());
+                    bin.extend_from_slice(&(uv.y as f32).to_le_bytes());
+                }
+            }
+            for i in &prim.indices {
+                bin.extend_from_slice(&i.to_le_bytes());
+            }
+        }
+        bin
+    }
+
+    /// The json part of the gltf. This must lay out the buffer views in the same order as `gen_bin`
+    fn gen_json(&self, byte_length: usize, uri: Option<&str>) -> String {
+        let mut views = Vec::new();
+        let mut accessors = Vec::new();
+        let mut meshes = Vec::new();
+        let mut nodes = Vec::new();
+        let mut offset = 0;
+
+        let mut add_view = |byte_size: usize, count: usize, target: u32, accessor: String| {
+            views.push(format!(
+                r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}"#,
+                offset,
+                byte_size * count,
+                target
+            ));
+            offset += byte_size * count;
+            accessors.push(format!(
+                r#"{{"bufferView":{},"componentType":{},"count":{}{}}}"#,
+                views.len() - 1,
+                if target == TARGET_ELEMENT_ARRAY_BUFFER {
+                    COMPONENT_UNSIGNED_INT
+                } else {
+                    COMPONENT_FLOAT
+                },
+                count,
+                accessor
+            ));
+            accessors.len() - 1
+        };
+
+        for (node, prim) in self.nodes.iter().zip(self.primitives()) {
+            let mut json = format!(r#"{{"name":{}"#, json_string(&node.name));
+            if let Some(prim) = prim {
+                let (min, max) = bounds(&prim.positions);
+                let mut attributes = format!(
+                    r#""POSITION":{}"#,
+                    add_view(
+                        12,
+                        prim.positions.len(),
+                        TARGET_ARRAY_BUFFER,
+                        format!(
+                            r#","type":"VEC3","min":{},"max":{}"#,
+                            json_floats(&min.to_array()),
+                            json_floats(&max.to_array())
+                        ),
+                    )
+                );
+                if let Some(normals) = &prim.normals {
+                    let id = add_view(
+                        12,
+                        normals.len(),
+                        TARGET_ARRAY_BUFFER,
+                        r#","type":"VEC3""#.to_owned(),
+                    );
+                    let _ = write!(attributes, r#","NORMAL":{}"#, id);
+                }
+                if let Some(uvs) = &prim.uvs {
+                    let id = add_view(
+                        8,
+                        uvs.len(),
+                        TARGET_ARRAY_BUFFER,
+                        r#","type":"VEC2""#.to_owned(),
+                    );
+                    let _ = write!(attributes, r#","TEXCOORD_0":{}"#, id);
+                }
+                let indices = add_view(
+                    4,
+                    prim.indices.len(),
+                    TARGET_ELEMENT_ARRAY_BUFFER,
+                    r#","type":"SCALAR""#.to_owned(),
+                );
+                meshes.push(format!(
+                    r#"{{"name":{},"primitives":[{{"attributes":{{{}}},"indices":{},"mode":{}}}]}}"#,
+                    json_string(&node.name),
+                    attributes,
+                    indices,
+                    MODE_TRIANGLES
+                ));
+                let _ = write!(json, r#","mesh":{}"#, meshes.len() - 1);
+            }
+            if let Some(pose) = &node.pose {
+                let _ = write!(
+                    json,
+                    r#","translation":{},"rotation":{}"#,
+                    json_floats(&pose.pos.to_array()),
+                    json_floats(&pose.rot.to_array())
+                );
+            }
+            json.push('}');
+            nodes.push(json);
+        }
+
+        let uri = uri
+            .map(|uri| format!(r#","uri":{}"#, json_string(uri)))
+            .unwrap_or_default();
+        let scene_nodes = (0..nodes.len())
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut json = String::new();
+        json.push_str(r#"{"asset":{"version":"2.0","generator":"Hedron"},"scene":0,"#);
+        let _ = write!(json, r#""scenes":[{{"nodes":[{}]}}],"#, scene_nodes);
+        let _ = write!(json, r#""nodes":[{}],"#, nodes.join(","));
+        if !meshes.is_empty() {
+            let _ = write!(json, r#""meshes":[{}],"#, meshes.join(","));
+            let _ = write!(json, r#""accessors":[{}],"#, accessors.join(","));
+            let _ = write!(json, r#""bufferViews":[{}],"#, views.join(","));
+        }
+        let _ = write!(
+            json,
+            r#""buffers":[{{"byteLength":{}{}}}]}}"#,
+            byte_length, uri
+        );
+        json
+    }
+}
+
+impl GltfPrimitive {
+    /// Face normals are not a thing in gltf, so meshes with face normals are unshared: every corner gets its own vertex.
+    /// Returns `None` for a mesh without triangles, since gltf doesn't allow empty accessors.
+    fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        if mesh.count_triangles() == 0 {
+            return None;
+        }
+        let uvs = (mesh.uvs.len() == mesh.verts.len()).then_some(&mesh.uvs);
+        match &mesh.normals {
+            Normals::Face(normals) if normals.len() == mesh.count_triangles() => {
+                let corners = mesh.tri.iter().take(mesh.count_triangles() * 3);
+                Some(Self {
+                    positions: corners.clone().map(|i| mesh.verts[*i]).collect(),
+                    normals: Some(normals.iter().flat_map(|n| [*n; 3]).collect()),
+                    uvs: uvs.map(|uvs| corners.map(|i| uvs[*i]).collect()),
+                    indices: (0..mesh.count_triangles() as u32 * 3).collect(),
+                })
+            }
+            normals => Some(Self {
+                positions: mesh.verts.clone(),
+                normals: match normals {
+                    Normals::Vertex(normals) if normals.len() == mesh.verts.len() => {
+                        Some(normals.clone())
+                    }
+                    _ => None,
+                },
+                uvs: uvs.cloned(),
+                indices: mesh
+                    .tri
+                    .iter()
+                    .take(mesh.count_triangles() * 3)
+                    .map(|i| *i as u32)
+                    .collect(),
+            }),
+        }
+    }
+}
+
+impl Mesh {
+    pub fn write_gltf(&self, path: &str) -> Result<(), std::io::Error> {
+        GltfScene::new()
+            .with_mesh("mesh", self.clone(), None)
+            .write_gltf(path)
+    }
+
+    pub fn write_glb(&self, path: &str) -> Result<(), std::io::Error> {
+        GltfScene::new()
+            .with_mesh("mesh", self.clone(), None)
+            .write_glb(path)
+    }
+}
+
+impl TriMesh {
+    pub fn write_gltf(&self, path: &str) -> Result<(), std::io::Error> {
+        Mesh::from_tri_mesh(self.clone()).write_gltf(path)
+    }
+
+    pub fn write_glb(&self, path: &str) -> Result<(), std::io::Error> {
+        Mesh::from_tri_mesh(self.clone()).write_glb(path)
+    }
+}
+
+fn push_vec3s(bin: &mut Vec<u8>, vecs: &[Vec3]) {
+    for v in vecs {
+        for f in [v.x, v.y, v.z] {
+            bin.extend_from_slice(&(f as f32).to_le_bytes());
+        }
+    }
+}
+
+fn bounds(verts: &[Vec3]) -> (Vec3, Vec3) {
+    verts.iter().fold(
+        (Vec3::splat(f32::MAX as _), Vec3::splat(f32::MIN as _)),
+        |(min, max), v| (min.min(*v), max.max(*v)),
+    )
+}
+
+/// json has no notion of NaN or infinity
+fn json_floats<T: Into<f64> + Copy>(floats: &[T]) -> String {
+    let floats = floats
+        .iter()
+        .map(|f| {
+            let f = (*f).into() as f32;
+            if f.is_finite() {
+                f.to_string()
+            } else {
+                "0".to_owned()
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", floats.join(","))
+}
+
+fn json_string(string: &str) -> String {
+    let mut json = String::with_capacity(string.len() + 2);
+    json.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut string = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                string.push(TABLE[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                string.push('=');
+            }
+        }
+    }
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{encode_base64, GltfScene};
+    use crate::core::Pose;
+    use crate::kernel::{vec3, Quat};
+    use crate::solid::{Mesh, Normals};
+
+    /// Just enough json to read back what we wrote
+    #[derive(Debug, Clone, PartialEq)]
+    enum Json {
+        Null,
+        Bool(bool),
+        Num(f64),
+        Str(String),
+        Arr(Vec<Json>),
+        Obj(HashMap<String, Json>),
+    }
+
+    impl Json {
+        fn parse(string: &str) -> Json {
+            let chars = string.chars().collect::<Vec<_>>();
+            let mut at = 0;
+            let json = Self::parse_value(&chars, &mut at);
+            Self::skip_ws(&chars, &mut at);
+            assert_eq!(at, chars.len(), "trailing characters after json");
+            json
+        }
+
+        fn skip_ws(chars: &[char], at: &mut usize) {
+            while *at < chars.len() && chars[*at].is_whitespace() {
+                *at += 1;
+            }
+        }
+
+        fn expect(chars: &[char], at: &mut usize, c: char) {
+            Self::skip_ws(chars, at);
+            assert_eq!(chars[*at], c, "unexpected character at {}", at);
+            *at += 1;
+        }
+
+        fn parse_value(chars: &[char], at: &mut usize) -> Json {
+            Self::skip_ws(chars, at);
+            match chars[*at] {
+                '{' => {
+                    *at += 1;
+                    let mut obj = HashMap::new();
+                    Self::skip_ws(chars, at);
+                    if chars[*at] == '}' {
+                        *at += 1;
+                        return Json::Obj(obj);
+                    }
+                    loop {
+                        let Json::Str(key) = Self::parse_value(chars, at) else {
+                            panic!("keys should be strings");
+                        };
+                        Self::expect(chars, at, ':');
+                        obj.insert(key, Self::parse_value(chars, at));
+                        Self::skip_ws(chars, at);
+                        *at += 1;
+                        match chars[*at - 1] {
+                            ',' => continue,
+                            '}' => return Json::Obj(obj),
+                            c => panic!("unexpected {} in object", c),
+                        }
+                    }
+                }
+                '[' => {
+                    *at += 1;
+                    let mut arr = Vec::new();
+                    Self::skip_ws(chars, at);
+                    if chars[*at] == ']' {
+                        *at += 1;
+                        return Json::Arr(arr);
+                    }
+                    loop {
+                        arr.push(Self::parse_value(chars, at));
+                        Self::skip_ws(chars, at);
+                        *at += 1;
+                        match chars[*at - 1] {
+                            ',' => continue,
+                            ']' => return Json::Arr(arr),
+                            c => panic!("unexpected {} in array", c),
+                        }
+                    }
+                }
+                '"' => {
+                    *at += 1;
+                    let mut string = String::new();
+                    while chars[*at] != '"' {
+                        if chars[*at] == '\\' {
+                            *at += 1;
+                            string.push(match chars[*at] {
+                                'n' => '\n',
+                                't' => '\t',
+                                'r' => '\r',
+                                c => c,
+                            });
+                        } else {
+                            string.push(chars[*at]);
+                        }
+                        *at += 1;
+                    }
+                    *at += 1;
+                    Json::Str(string)
+                }
+                't' | 'f' | 'n' => {
+                    let word = chars[*at..]
+                        .iter()
+                        .take_while(|c| c.is_alphabetic())
+                        .collect::<String>();
+                    *at += word.len();
+                    match word.as_str() {
+                        "true" => Json::Bool(true),
+                        "false" => Json::Bool(false),
+                        "null" => Json::Null,
+                        w => panic!("unknown literal {}", w),
+                    }
+                }
+                _ => {
+                    let number = chars[*at..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_digit() || "+-.eE".contains(**c))
+                        .collect::<String>();
+                    *at += number.len();
+                    Json::Num(number.parse().expect("invalid number"))
+                }
+            }
+        }
+
+        fn get(&self, key: &str) -> &Json {
+            match self {
+                Json::Obj(obj) => obj.get(key).unwrap_or(&Json::Null),
+                _ => &Json::Null,
+            }
+        }
+
+        fn at(&self, i: usize) -> &Json {
+            match self {
+                Json::Arr(arr) => &arr[i],
+                _ => panic!("not an array"),
+            }
+        }
+
+        fn len(&self) -> usize {
+            match self {
+                Json::Arr(arr) => arr.len(),
+                _ => 0,
+            }
+        }
+
+        fn num(&self) -> usize {
+            match self {
+                Json::Num(num) => *num as usize,
+                _ => panic!("not a number"),
+            }
+        }
+
+        fn float(&self) -> f64 {
+            match self {
+                Json::Num(num) => *num,
+                _ => panic!("not a number"),
+            }
+        }
+
+        fn str(&self) -> &str {
+            match self {
+                Json::Str(str) => str,
+                _ => panic!("not a string"),
+            }
+        }
+    }
+
+    fn decode_base64(string: &str) -> Vec<u8> {
+        let value = |c: u8| match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => panic!("invalid base64"),
+        };
+        let mut bytes = Vec::new();
+        for chunk in string.as_bytes().chunks(4) {
+            let data = chunk.iter().filter(|c| **c != b'=').collect::<Vec<_>>();
+            let n = data
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, c)| n | (value(**c) as u32) << (18 - 6 * i));
+            for i in 0..data.len() - 1 {
+                bytes.push((n >> (16 - 8 * i)) as u8);
+            }
+        }
+        bytes
+    }
+
+    /// read the floats or ints an accessor points to, as f64
+    fn read_accessor(json: &Json, bin: &[u8], accessor: usize) -> Vec<f64> {
+        let accessor = json.get("accessors").at(accessor);
+        let view = json.get("bufferViews").at(accessor.get("bufferView").num());
+        let width = match accessor.get("type").str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            t => panic!("unexpected type {}", t),
+        };
+        let count = accessor.get("count").num() * width;
+        assert_eq!(view.get("byteLength").num(), count * 4);
+        let start = view.get("byteOffset").num();
+        bin[start..start + count * 4]
+            .chunks(4)
+            .map(|b| {
+                let b = [b[0], b[1], b[2], b[3]];
+                match accessor.get("componentType").num() {
+                    5126 => f32::from_le_bytes(b) as f64,
+                    5125 => u32::from_le_bytes(b) as f64,
+                    c => panic!("unexpected component type {}", c),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_base64() {
+        for (raw, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v")] {
+            assert_eq!(encode_base64(raw.as_bytes()), encoded);
+            assert_eq!(decode_base64(encoded), raw.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_gltf_export() {
+        let cube = Mesh::new_cube(2.0);
+        let pose = Pose::from_xyz(1.0, 2.0, 3.0).with_rot(Quat::from_rotation_z(0.5));
+        let gltf = GltfScene::new()
+            .with_mesh("cube \"one\"", cube.clone(), Some(pose))
+            .with_mesh("flat", cube.clone().with_face_normals(), None)
+            .gen_gltf_string();
+
+        let json = Json::parse(&gltf);
+        assert_eq!(json.get("asset").get("version").str(), "2.0");
+        assert_eq!(json.get("nodes").len(), 2);
+        assert_eq!(json.get("meshes").len(), 2);
+
+        let node = json.get("nodes").at(0);
+        assert_eq!(node.get("name").str(), "cube \"one\"");
+        assert_eq!(node.get("translation").at(2).float(), 3.0);
+        assert_eq!(json.get("nodes").at(1).get("translation"), &Json::Null);
+
+        let buffer = json.get("buffers").at(0);
+        let uri = buffer.get("uri").str();
+        let bin = decode_base64(uri.split_once(',').unwrap().1);
+        assert_eq!(bin.len(), buffer.get("byteLength").num());
+
+        // the smooth cube can be read back as is
+        let attributes = json
+            .get("meshes")
+            .at(0)
+            .get("primitives")
+            .at(0)
+            .get("attributes");
+        let positions = read_accessor(&json, &bin, attributes.get("POSITION").num());
+        for (i, v) in positions.chunks(3).enumerate() {
+            assert!(cube.verts[i].distance(vec3(v[0] as _, v[1] as _, v[2] as _)) < 1e-6);
+        }
+        let uvs = read_accessor(&json, &bin, attributes.get("TEXCOORD_0").num());
+        assert_eq!(uvs.len(), cube.uvs.len() * 2);
+        let indices = read_accessor(
+            &json,
+            &bin,
+            json.get("meshes")
+                .at(0)
+                .get("primitives")
+                .at(0)
+                .get("indices")
+                .num(),
+        );
+        assert_eq!(
+            indices,
+            cube.tri.iter().map(|i| *i as f64).collect::<Vec<_>>()
+        );
+
+        // the flat cube is unshared
+        let attributes = json
+            .get("meshes")
+            .at(1)
+            .get("primitives")
+            .at(0)
+            .get("attributes");
+        let normals = read_accessor(&json, &bin, attributes.get("NORMAL").num());
+        assert_eq!(normals.len(), cube.count_triangles() * 3 * 3);
+    }
+
+    #[test]
+    fn test_glb_export() {
+        let mesh = Mesh::new_icosahedron(1.0);
+        assert!(matches!(mesh.normals, Normals::Vertex(_)));
+        let glb = GltfScene::new()
+            .with_mesh("ico", mesh.clone(), None)
+            .gen_glb_buffer();
+
+        let word = |at: usize| {
+            u32::from_le_bytes([glb[at], glb[at + 1], glb[at + 2], glb[at + 3]]) as usize
+        };
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(word(4), 2);
+        assert_eq!(word(8), glb.len());
+
+        let json_length = word(12);
+        assert_eq!(&glb[16..20], b"JSON");
+        let json = Json::parse(std::str::from_utf8(&glb[20..20 + json_length]).unwrap());
+        let bin_start = 20 + json_length;
+        assert_eq!(&glb[bin_start + 4..bin_start + 8], b"BIN\0");
+        let bin = &glb[bin_start + 8..];
+        assert_eq!(bin.len(), word(bin_start));
+        assert_eq!(json.get("buffers").at(0).get("uri"), &Json::Null);
+
+        let primitive = json.get("meshes").at(0).get("primitives").at(0);
+        let indices = read_accessor(&json, bin, primitive.get("indices").num());
+        assert_eq!(indices.len(), mesh.tri.len());
+        let normals = read_accessor(&json, bin, primitive.get("attributes").get("NORMAL").num());
+        assert_eq!(normals.len(), mesh.verts.len() * 3);
+    }
+}
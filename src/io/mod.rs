@@ -0,0 +1,15 @@
+//! Importers & exporters of the various mesh file formats
+mod error;
+mod gltf;
+mod mtl;
+mod obj;
+mod off;
+mod ply;
+mod stl;
+
+pub use error::*;
+pub use gltf::*;
+pub use mtl::*;
+pub use obj::*;
+pub use ply::*;
+pub use stl::*;
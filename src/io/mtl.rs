@@ -0,0 +1,132 @@
+use std::fmt::Write as _;
+
+use crate::kernel::{fxx, Vec3};
+
+/// A material, as written to a wavefront mtl file.
+///
+/// Next to the classic `Kd`, `Ks` and `Ke` colors, the PBR extension is used for roughness and metallic (`Pr` and `Pm`).
+/// `Ns` is derived from the roughness, for readers which do not understand those.
+/// Texture maps are written as given, so they should be relative to the mtl file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialDesc {
+    pub name: String,
+    /// linear rgb, in the 0..1 range
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub emissive: Vec3,
+    pub roughness: fxx,
+    pub metallic: fxx,
+    /// 1 is fully opaque
+    pub opacity: fxx,
+    pub diffuse_map: Option<String>,
+    pub normal_map: Option<String>,
+    pub roughness_map: Option<String>,
+    pub metallic_map: Option<String>,
+    pub emissive_map: Option<String>,
+}
+
+impl MaterialDesc {
+    /// A white, fully rough, opaque material
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            diffuse: Vec3::ONE,
+            specular: Vec3::ZERO,
+            emissive: Vec3::ZERO,
+            roughness: 1.0,
+            metallic: 0.0,
+            opacity: 1.0,
+            diffuse_map: None,
+            normal_map: None,
+            roughness_map: None,
+            metallic_map: None,
+            emissive_map: None,
+        }
+    }
+
+    pub fn with_diffuse(mut self, color: impl Into<Vec3>) -> Self {
+        self.diffuse = color.into();
+        self
+    }
+
+    pub fn with_specular(mut self, color: impl Into<Vec3>) -> Self {
+        self.specular = color.into();
+        self
+    }
+
+    pub fn with_emissive(mut self, color: impl Into<Vec3>) -> Self {
+        self.emissive = color.into();
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: fxx) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn with_metallic(mut self, metallic: fxx) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_opacity(mut self, opacity: fxx) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn with_diffuse_map(mut self, path: &str) -> Self {
+        self.diffuse_map = Some(path.to_owned());
+        self
+    }
+
+    pub fn with_normal_map(mut self, path: &str) -> Self {
+        self.normal_map = Some(path.to_owned());
+        self
+    }
+
+    pub fn with_roughness_map(mut self, path: &str) -> Self {
+        self.roughness_map = Some(path.to_owned());
+        self
+    }
+
+    pub fn with_metallic_map(mut self, path: &str) -> Self {
+        self.metallic_map = Some(path.to_owned());
+        self
+    }
+
+    pub fn with_emissive_map(mut self, path: &str) -> Self {
+        self.emissive_map = Some(path.to_owned());
+        self
+    }
+
+    /// The `newmtl` block of this material
+    pub fn gen_mtl_string(&self) -> String {
+        let color = |c: Vec3| format!("{:.6} {:.6} {:.6}", c.x, c.y, c.z);
+        let shininess = (1.0 - self.roughness.clamp(0.0, 1.0)) * 30.0;
+        let mut m = String::new();
+        let _ = writeln!(m, "newmtl {}", self.name);
+        let _ = writeln!(m, "Ns {:.6}", shininess * shininess);
+        let _ = writeln!(m, "Ka 1.000000 1.000000 1.000000");
+        let _ = writeln!(m, "Kd {}", color(self.diffuse));
+        let _ = writeln!(m, "Ks {}", color(self.specular));
+        let _ = writeln!(m, "Ke {}", color(self.emissive));
+        let _ = writeln!(m, "Ni 1.450000");
+        let _ = writeln!(m, "d {:.6}", self.opacity);
+        let _ = writeln!(m, "illum 2");
+        let _ = writeln!(m, "Pr {:.6}", self.roughness);
+        let _ = writeln!(m, "Pm {:.6}", self.metallic);
+        let maps = [
+            ("map_Kd", &self.diffuse_map),
+            ("norm", &self.normal_map),
+            ("map_Pr", &self.roughness_map),
+            ("map_Pm", &self.metallic_map),
+            ("map_Ke", &self.emissive_map),
+        ];
+        for (keyword, map) in maps {
+            if let Some(path) = map {
+                let _ = writeln!(m, "{} {}", keyword, path);
+            }
+        }
+        m
+    }
+}
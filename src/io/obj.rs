@@ -0,0 +1,577 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::ops::Range;
+
+use super::{MaterialDesc, ParseError};
+use crate::algos::earcut_3d;
+use crate::core::Plane;
+use crate::kernel::{fxx, Vec2, Vec3};
+use crate::planar::{Polygon, Triangle};
+use crate::solid::{Indexing, Mesh, Normals, TriCorner, TriMesh};
+use crate::util::OneOrMany;
+
+/// A named, consecutive run of triangles, as started by an `o`, `g` or `usemtl` statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjGroup {
+    pub name: String,
+    pub tris: Range<usize>,
+}
+
+/// Everything we understand of a wavefront obj file.
+///
+/// OBJ corners index positions, uvs and normals separately,
+/// so the mesh always uses `Indexing::Hetero`.
+/// Faces with more than 3 corners are triangulated, so the groups refer to triangle indices.
+#[derive(Debug, Clone)]
+pub struct ObjFile {
+    pub mesh: TriMesh,
+    pub objects: Vec<ObjGroup>,
+    pub groups: Vec<ObjGroup>,
+    pub materials: Vec<ObjGroup>,
+    pub mtllibs: Vec<String>,
+}
+
+/// a corner as written in the file. uv & normal are optional
+type ObjCorner = (usize, Option<usize>, Option<usize>);
+
+impl ObjFile {
+    pub fn read(path: &str) -> Result<Self, ParseError> {
+        let string = std::fs::read_to_string(path)?;
+        Self::parse(&string)
+    }
+
+    pub fn parse(string: &str) -> Result<Self, ParseError> {
+        let mut verts = Vec::new();
+        let mut uvs = Vec::new();
+        let mut normals = Vec::new();
+        let mut corners: Vec<ObjCorner> = Vec::new();
+
+        let mut objects = Vec::new();
+        let mut groups = Vec::new();
+        let mut materials = Vec::new();
+        let mut mtllibs = Vec::new();
+
+        for (i, line) in string.lines().enumerate() {
+            let nr = i + 1;
+            let parts: Vec<_> = line.split_whitespace().collect();
+            let Some(keyword) = parts.first() else {
+                continue;
+            };
+            let tri_count = corners.len() / 3;
+            match *keyword {
+                "v" => {
+                    let num = parse_floats(&parts, 3, nr)?;
+                    verts.push(Vec3::new(num[0], num[1], num[2]));
+                }
+                "vt" => {
+                    let num = parse_floats(&parts, 1, nr)?;
+                    uvs.push(Vec2::new(num[0], num.get(1).copied().unwrap_or(0.0)));
+                }
+                "vn" => {
+                    let num = parse_floats(&parts, 3, nr)?;
+                    normals.push(Vec3::new(num[0], num[1], num[2]));
+                }
+                "f" => {
+                    let face = parts
+                        .iter()
+                        .skip(1)
+                        .map(|c| parse_corner(c, verts.len(), uvs.len(), normals.len(), nr))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if face.len() < 3 {
+                        return Err(ParseError::line(nr, "a face needs at least 3 corners"));
+                    }
+                    let polygon = face.iter().map(|(v, _, _)| verts[*v]).collect::<Vec<_>>();
+                    for id in triangulate_polygon(&polygon) {
+                        corners.push(face[id]);
+                    }
+                }
+                "o" => switch_group(&mut objects, parts[1..].join(" "), tri_count),
+                "g" => switch_group(&mut groups, parts[1..].join(" "), tri_count),
+                "usemtl" => switch_group(&mut materials, parts[1..].join(" "), tri_count),
+                "mtllib" => mtllibs.push(parts[1..].join(" ")),
+                _ => continue, // comments, smoothing groups, lines, and everything else we don't support
+            };
+        }
+
+        let tri_count = corners.len() / 3;
+        for list in [&mut objects, &mut groups, &mut materials] {
+            close_groups(list, tri_count);
+        }
+
+        Ok(Self {
+            mesh: build_tri_mesh(verts, uvs, normals, &corners),
+            objects,
+            groups,
+            materials,
+            mtllibs,
+        })
+    }
+}
+
+impl TriMesh {
+    pub fn from_obj_str(string: &str) -> Result<TriMesh, ParseError> {
+        Ok(ObjFile::parse(string)?.mesh)
+    }
+}
+
+impl Mesh {
+    pub fn read_obj(path: &str) -> Result<Mesh, ParseError> {
+        Ok(Mesh::from_tri_mesh(ObjFile::read(path)?.mesh))
+    }
+
+    /// Vertices are split wherever a position is used with different uvs or normals
+    pub fn from_obj_str(string: &str) -> Result<Mesh, ParseError> {
+        Ok(Mesh::from_tri_mesh(TriMesh::from_obj_str(string)?))
+    }
+}
+
+/// A mesh written to an obj file, as its own `o` object
+#[derive(Debug, Clone)]
+pub struct ObjObject {
+    pub name: String,
+    pub mesh: Mesh,
+}
+
+/// Several meshes, written as separate `o` objects to one obj file, sharing one mtl file.
+///
+/// The triangles of every mesh are grouped per material, one `usemtl` group per material,
+/// using the ids stored as [`crate::solid::Attributes::MATERIAL`], which point into `materials`.
+/// Triangles without a valid material id, like the [`crate::solid::Attribute::NO_INDEX`] padding of joined meshes,
+/// use the first material.
+/// Uvs and normals are written when they are in sync with the mesh, both vertex and face normals.
+#[derive(Debug, Clone, Default)]
+pub struct ObjScene {
+    pub objects: Vec<ObjObject>,
+    pub materials: Vec<MaterialDesc>,
+}
+
+impl ObjScene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_mesh(mut self, name: &str, mesh: Mesh) -> Self {
+        self.add_mesh(name, mesh);
+        self
+    }
+
+    pub fn add_mesh(&mut self, name: &str, mesh: Mesh) {
+        self.objects.push(ObjObject {
+            name: name.to_owned(),
+            mesh,
+        });
+    }
+
+    pub fn with_material(mut self, material: MaterialDesc) -> Self {
+        self.add_material(material);
+        self
+    }
+
+    /// Returns the id of the material, to be used as a material id of triangles
+    pub fn add_material(&mut self, material: MaterialDesc) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    /// Write `name_obj` and `name_mtl` to the folder at `path`. Texture maps should be relative to that folder
+    pub fn write_obj_mtl(
+        &self,
+        path: &str,
+        name_obj: &str,
+        name_mtl: &str,
+    ) -> Result<(), std::io::Error> {
+        let obj = self.gen_obj_string("obj generated by Hedron", Some(name_mtl));
+        let mtl = self.gen_mtl_string("mtl generated by Hedron");
+        let mut obj_file = std::fs::File::create(path.to_owned() + name_obj)?;
+        obj_file.write_all(obj.as_bytes())?;
+        let mut mtl_file = std::fs::File::create(path.to_owned() + name_mtl)?;
+        mtl_file.write_all(mtl.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn gen_obj_string(&self, header: &str, mtl_path: Option<&str>) -> String {
+        let mut obj = format!("# {}\n", header);
+        if let Some(path) = mtl_path {
+            let _ = writeln!(obj, "mtllib {}", path);
+        }
+        let names = self
+            .materials
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect::<Vec<_>>();
+        let mut offsets = [0; 3];
+        for object in self.objects.iter() {
+            let _ = writeln!(obj, "o {}", object.name);
+            offsets = write_obj_mesh(&mut obj, &object.mesh, &names, offsets);
+        }
+        obj
+    }
+
+    pub fn gen_mtl_string(&self, header: &str) -> String {
+        let mut mtl = format!("# {}\n", header);
+        for material in self.materials.iter() {
+            mtl.push('\n');
+            mtl.push_str(&material.gen_mtl_string());
+        }
+        mtl
+    }
+}
+
+/// Write the vertices and triangles of one mesh, grouped by material.
+/// `offsets` counts the positions, uvs and normals written before, the counts after this mesh are returned
+pub(crate) fn write_obj_mesh(
+    obj: &mut String,
+    mesh: &Mesh,
+    materials: &[&str],
+    offsets: [usize; 3],
+) -> [usize; 3] {
+    let count = mesh.verts.len();
+    let tri_count = mesh.count_triangles();
+    let has_uvs = !mesh.uvs.is_empty() && mesh.uvs.len() == count;
+    let normals = match &mesh.normals {
+        Normals::Vertex(normals) if normals.len() == count => Some((normals, true)),
+        Normals::Face(normals) if normals.len() == tri_count => Some((normals, false)),
+        _ => None,
+    };
+
+    for v in mesh.verts.iter() {
+        let _ = writeln!(obj, "v {} {} {}", v.x, v.y, v.z);
+    }
+    if has_uvs {
+        for uv in mesh.uvs.iter() {
+            let _ = writeln!(obj, "vt {} {}", uv.x, uv.y);
+        }
+    }
+    if let Some((normals, _)) = normals {
+        for n in normals.iter() {
+            let _ = writeln!(obj, "vn {} {} {}", n.x, n.y, n.z);
+        }
+    }
+
+    let [v_offset, vt_offset, vn_offset] = offsets;
+    let corner = |v: usize, t: usize| {
+        let p = v + v_offset + 1;
+        let uv = has_uvs.then_some(v + vt_offset + 1);
+        let n = normals.map(|(_, per_vertex)| if per_vertex { v } else { t } + vn_offset + 1);
+        match (uv, n) {
+            (Some(uv), Some(n)) => format!("{p}/{uv}/{n}"),
+            (Some(uv), None) => format!("{p}/{uv}"),
+            (None, Some(n)) => format!("{p}//{n}"),
+            (None, None) => format!("{p}"),
+        }
+    };
+
+    // one usemtl group per material, in order of the materials
+    let ids = mesh
+        .tri_attributes
+        .materials()
+        .filter(|ids| ids.len() == tri_count);
+    let material_of = |t: usize| {
+        ids.map(|ids| ids[t])
+            .filter(|id| *id < materials.len())
+            .unwrap_or(0)
+    };
+    let mut groups = vec![Vec::new(); materials.len().max(1)];
+    for t in 0..tri_count {
+        groups[material_of(t)].push(t);
+    }
+    for (id, group) in groups.iter().enumerate() {
+        if group.is_empty() {
+            continue;
+        }
+        if let Some(name) = materials.get(id) {
+            let _ = writeln!(obj, "usemtl {}", name);
+        }
+        for t in group {
+            let [a, b, c] = [0, 1, 2].map(|k| corner(mesh.tri[t * 3 + k], *t));
+            let _ = writeln!(obj, "f {a} {b} {c}");
+        }
+    }
+
+    let uv_count = if has_uvs { count } else { 0 };
+    let normal_count = normals.map_or(0, |(normals, _)| normals.len());
+    [
+        v_offset + count,
+        vt_offset + uv_count,
+        vn_offset + normal_count,
+    ]
+}
+
+fn parse_floats(parts: &[&str], min: usize, nr: usize) -> Result<Vec<fxx>, ParseError> {
+    let num = parts
+        .iter()
+        .skip(1)
+        .map(|s| s.parse::<fxx>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ParseError::line(nr, format!("'{}' expects numbers", parts[0])))?;
+    if num.len() < min {
+        return Err(ParseError::line(
+            nr,
+            format!("'{}' expects at least {} numbers", parts[0], min),
+        ));
+    }
+    Ok(num)
+}
+
+/// OBJ indices start at 1. Negative indices count back from the last element read so far.
+fn parse_index(token: &str, count: usize, what: &str, nr: usize) -> Result<usize, ParseError> {
+    let i = token
+        .parse::<i64>()
+        .map_err(|_| ParseError::line(nr, format!("invalid {} index '{}'", what, token)))?;
+    let id = match i {
+        0 => return Err(ParseError::line(nr, format!("{} index 0 is invalid", what))),
+        i if i > 0 => i - 1,
+        i => count as i64 + i,
+    };
+    if id < 0 || id >= count as i64 {
+        return Err(ParseError::line(
+            nr,
+            format!("{} index {} is out of range", what, token),
+        ));
+    }
+    Ok(id as usize)
+}
+
+/// parse `v`, `v/vt`, `v//vn` or `v/vt/vn`
+fn parse_corner(
+    corner: &str,
+    vert_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+    nr: usize,
+) -> Result<ObjCorner, ParseError> {
+    let mut parts = corner.split('/');
+    let v = parse_index(parts.next().unwrap_or(""), vert_count, "vertex", nr)?;
+    let uv = match parts.next() {
+        None | Some("") => None,
+        Some(token) => Some(parse_index(token, uv_count, "uv", nr)?),
+    };
+    let n = match parts.next() {
+        None | Some("") => None,
+        Some(token) => Some(parse_index(token, normal_count, "normal", nr)?),
+    };
+    Ok((v, uv, n))
+}
+
+/// Triangulate a polygonal face. Returns ids into `polygon`, three per triangle
+pub(super) fn triangulate_polygon(polygon: &[Vec3]) -> Vec<usize> {
+    if polygon.len() == 3 {
+        return vec![0, 1, 2];
+    }
+    let fan = || {
+        (1..polygon.len() - 1)
+            .flat_map(|i| [0, i, i + 1])
+            .collect::<Vec<_>>()
+    };
+
+    let polygon = Polygon::new(polygon.to_vec());
+    let normal = polygon.average_normal();
+    if !normal.is_finite() {
+        return fan();
+    }
+    let plane = Plane::from_pos_normal(polygon.center(), normal);
+    let Some(mut ids) = earcut_3d(&polygon.verts, &vec![], &plane) else {
+        return fan();
+    };
+    if ids.len() != (polygon.verts.len() - 2) * 3 {
+        return fan();
+    }
+
+    // earcut picks its own winding, make sure it agrees with the face
+    let (a, b, c) = (ids[0], ids[1], ids[2]);
+    let tri_normal =
+        (polygon.verts[b] - polygon.verts[a]).cross(polygon.verts[c] - polygon.verts[a]);
+    if tri_normal.dot(normal) < 0.0 {
+        for tri in ids.chunks_mut(3) {
+            tri.swap(0, 1);
+        }
+    }
+    ids
+}
+
+fn switch_group(groups: &mut Vec<ObjGroup>, name: String, at: usize) {
+    close_groups(groups, at);
+    groups.push(ObjGroup { name, tris: at..at });
+}
+
+/// close the last group at triangle `at`, drop it if it turns out to be empty
+fn close_groups(groups: &mut Vec<ObjGroup>, at: usize) {
+    if let Some(last) = groups.last_mut() {
+        last.tris.end = at;
+        if last.tris.is_empty() {
+            groups.pop();
+        }
+    }
+}
+
+/// Corners missing a uv point to an extra zero uv.
+/// Corners missing a normal point to a flat face normal.
+fn build_tri_mesh(
+    verts: Vec<Vec3>,
+    mut uvs: Vec<Vec2>,
+    mut normals: Vec<Vec3>,
+    corners: &[ObjCorner],
+) -> TriMesh {
+    let has_uvs = !uvs.is_empty();
+    let has_normals = !normals.is_empty();
+    let missing_uv = uvs.len();
+    if has_uvs && corners.iter().any(|(_, uv, _)| uv.is_none()) {
+        uvs.push(Vec2::ZERO);
+    }
+
+    let mut hetero = Vec::with_capacity(corners.len());
+    for tri in corners.chunks(3) {
+        let mut face_normal = None;
+        for (v, uv, n) in tri {
+            let uv = if has_uvs { uv.unwrap_or(missing_uv) } else { 0 };
+            let n = match (has_normals, n) {
+                (false, _) => 0,
+                (true, Some(n)) => *n,
+                (true, None) => *face_normal.get_or_insert_with(|| {
+                    let (a, b, c) = (verts[tri[0].0], verts[tri[1].0], verts[tri[2].0]);
+                    normals.push(Triangle::new(a, b, c).normal());
+                    normals.len() - 1
+                }),
+            };
+            hetero.push(TriCorner::new(*v, uv, n));
+        }
+    }
+
+    let mut mesh = TriMesh::default().with_verts(verts);
+    mesh.tri = Indexing::Hetero(hetero);
+    if has_uvs {
+        mesh.uvs = OneOrMany::Many(uvs);
+    }
+    if has_normals {
+        mesh.normals = OneOrMany::Many(normals);
+    }
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ObjFile, ObjScene};
+    use crate::io::{MaterialDesc, ParseError};
+    use crate::kernel::{vec3, Vec3};
+    use crate::solid::{Indexing, Mesh};
+
+    const QUADS: &str = "
+        # two quads and an L shaped hexagon
+        mtllib some.mtl
+        o plate
+        v 0 0 0
+        v 1 0 0
+        v 1 1 0
+        v 0 1 0
+        vt 0 0
+        vt 1 0
+        vt 1 1
+        vt 0 1
+        vn 0 0 1
+        usemtl red
+        f 1/1/1 2/2/1 3/3/1 4/4/1
+        f -4//-1 -2//-1 -1//-1
+        o ell
+        v 2 0 0
+        v 4 0 0
+        v 4 1 0
+        v 3 1 0
+        v 3 2 0
+        v 2 2 0
+        usemtl blue
+        f 5 6 7 8 9 10
+    ";
+
+    #[test]
+    fn test_obj_import() {
+        let obj = ObjFile::parse(QUADS).unwrap();
+        let Indexing::Hetero(corners) = &obj.mesh.tri else {
+            panic!("obj should import as hetero");
+        };
+        assert_eq!(corners.len(), (2 + 1 + 4) * 3);
+        assert_eq!(obj.mtllibs, vec!["some.mtl".to_owned()]);
+        assert_eq!(
+            obj.objects
+                .iter()
+                .map(|o| o.tris.clone())
+                .collect::<Vec<_>>(),
+            vec![0..3, 3..7]
+        );
+        assert_eq!(obj.materials[1].name, "blue");
+
+        // the triangulated hexagon should keep facing up
+        let mesh = Mesh::from_tri_mesh(obj.mesh);
+        for n in mesh.calc_flat_face_normals() {
+            assert!(n.z > 0.99);
+        }
+    }
+
+    #[test]
+    fn test_obj_errors() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 4\n";
+        let Err(ParseError::Line(line, _)) = ObjFile::parse(obj) else {
+            panic!("expected an error at a line");
+        };
+        assert_eq!(line, 5);
+    }
+
+    #[test]
+    fn test_obj_scene() {
+        // two triangles of the grid use the second material, the rest the first
+        let grid = Mesh::new_hexagrid(1.0, 1).with_vertex_normals();
+        let tri_count = grid.count_triangles();
+        let ids = (0..tri_count).map(|t| (t < 2) as usize).collect();
+        let grid = grid.with_material_ids(ids);
+        let diamond = Mesh::new_diamond(vec3(0.0, 0.0, 3.0), 1.0);
+
+        let scene = ObjScene::new()
+            .with_material(MaterialDesc::new("stone").with_roughness(0.8))
+            .with_material(
+                MaterialDesc::new("gold")
+                    .with_diffuse(vec3(1.0, 0.8, 0.3))
+                    .with_metallic(1.0)
+                    .with_diffuse_map("gold.png"),
+            )
+            .with_mesh("grid", grid.clone())
+            .with_mesh("diamond", diamond.clone());
+        let obj = ObjFile::parse(&scene.gen_obj_string("test", Some("test.mtl"))).unwrap();
+
+        assert_eq!(obj.mtllibs, vec!["test.mtl".to_owned()]);
+        assert_eq!(obj.mesh.verts.len(), grid.verts.len() + diamond.verts.len());
+        let names = obj
+            .objects
+            .iter()
+            .map(|o| o.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["grid", "diamond"]);
+        let materials = obj
+            .materials
+            .iter()
+            .map(|m| (m.name.as_str(), m.tris.len()))
+            .collect::<Vec<_>>();
+        let diamond_tris = diamond.count_triangles();
+        assert_eq!(
+            materials,
+            vec![
+                ("stone", tri_count - 2),
+                ("gold", 2),
+                ("stone", diamond_tris)
+            ]
+        );
+
+        // the grid has vertex normals, the diamond none
+        let Indexing::Hetero(corners) = &obj.mesh.tri else {
+            panic!("obj should import as hetero");
+        };
+        for corner in corners.iter().filter(|c| c.v < grid.verts.len()) {
+            let normal = obj.mesh.normals.get(corner.n).unwrap();
+            assert!((*normal - Vec3::Z).length() < 1e-6);
+        }
+
+        let mtl = scene.gen_mtl_string("test");
+        assert!(mtl.contains("newmtl stone"));
+        assert!(mtl.contains("Kd 1.000000 0.800000 0.300000"));
+        assert!(mtl.contains("Pm 1.000000"));
+        assert!(mtl.contains("map_Kd gold.png"));
+    }
+}
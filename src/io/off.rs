@@ -0,0 +1,295 @@
+use std::io::Write;
+
+use super::obj::triangulate_polygon;
+use super::ParseError;
+use crate::kernel::{fxx, Vec3, Vec4};
+use crate::planar::Polygon;
+use crate::solid::{Mesh, Normals, Polyhedron};
+
+/// The content of an OFF file: vertices, optional vertex colors, and polygon faces
+struct OffData {
+    verts: Vec<Vec3>,
+    colors: Option<Vec<Vec4>>,
+    faces: Vec<Vec<usize>>,
+}
+
+impl Mesh {
+    pub fn read_off(path: &str) -> Result<Mesh, ParseError> {
+        let string = std::fs::read_to_string(path)?;
+        Mesh::from_off_str(&string)
+    }
+
+    pub fn write_off(&self, path: &str) -> Result<(), std::io::Error> {
+        let off = self.gen_off_buffer()?;
+        let mut off_file = std::fs::File::create(path)?;
+        off_file.write_all(&off)?;
+        Ok(())
+    }
+
+    /// Polygon faces are triangulated. The vertex colors of a `COFF` file are stored as [`crate::solid::Attributes::COLOR`]
+    pub fn from_off_str(string: &str) -> Result<Mesh, ParseError> {
+        let off = parse_off(string)?;
+        let mut tri = Vec::new();
+        for face in off.faces.iter() {
+            let polygon = face.iter().map(|i| off.verts[*i]).collect::<Vec<_>>();
+            tri.extend(triangulate_polygon(&polygon).into_iter().map(|i| face[i]));
+        }
+        let mesh = Mesh::new(off.verts, tri, Vec::new(), Normals::None);
+        Ok(match off.colors {
+            Some(colors) => mesh.with_vert_colors(colors),
+            None => mesh,
+        })
+    }
+
+    /// Writes a `COFF` file if the mesh has vertex colors, and a plain `OFF` file otherwise
+    pub fn gen_off_buffer(&self) -> Result<Vec<u8>, std::io::Error> {
+        let colors = self
+            .vert_attributes
+            .colors()
+            .filter(|c| c.len() == self.verts.len());
+
+        let mut off = Vec::new();
+        let o = &mut off;
+        writeln!(o, "{}", if colors.is_some() { "COFF" } else { "OFF" })?;
+        writeln!(o, "# off generated by Hedron")?;
+        writeln!(o, "{} {} 0", self.verts.len(), self.tri.len() / 3)?;
+        for (i, v) in self.verts.iter().enumerate() {
+            match colors {
+                Some(colors) => {
+                    let c = colors[i];
+                    writeln!(o, "{} {} {} {} {} {} {}", v.x, v.y, v.z, c.x, c.y, c.z, c.w)?
+                }
+                None => writeln!(o, "{} {} {}", v.x, v.y, v.z)?,
+            }
+        }
+        for (a, b, c) in self.iter_triangles() {
+            writeln!(o, "3 {} {} {}", a, b, c)?;
+        }
+        Ok(off)
+    }
+}
+
+impl Polyhedron {
+    pub fn read_off(path: &str) -> Result<Polyhedron, ParseError> {
+        let string = std::fs::read_to_string(path)?;
+        Polyhedron::from_off_str(&string)
+    }
+
+    /// Every polygon becomes a loop of edges, inserted using the normal of that polygon.
+    /// Just like [`Polyhedron::from_mesh`], the face orientation of the file must be consistent.
+    pub fn from_off_str(string: &str) -> Result<Polyhedron, ParseError> {
+        let off = parse_off(string)?;
+        let mut hedron = Polyhedron::new();
+        for vert in off.verts.iter() {
+            hedron.add_vert(*vert);
+        }
+        for face in off.faces.iter() {
+            let polygon = Polygon::new(face.iter().map(|i| off.verts[*i]).collect());
+            let normal = polygon.average_normal();
+            for i in 0..face.len() {
+                hedron.add_edge(face[i], face[(i + 1) % face.len()], normal, normal);
+            }
+        }
+        Ok(hedron)
+    }
+}
+
+/// Reads `OFF` and `COFF` files. Comments start with `#`.
+/// Colors are either all in the 0..1 range, or all in the 0..255 range.
+fn parse_off(string: &str) -> Result<OffData, ParseError> {
+    let mut lines = string
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.split('#').next().unwrap_or("").trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let Some((nr, header)) = lines.next() else {
+        return Err(ParseError::format("the file is empty"));
+    };
+    let mut words = header.split_whitespace();
+    let has_colors = match words.next() {
+        Some("OFF") => false,
+        Some("COFF") => true,
+        _ => return Err(ParseError::line(nr, "expected an 'OFF' or 'COFF' header")),
+    };
+
+    // the counts can be on the header line as well
+    let mut counts = words.collect::<Vec<_>>();
+    let mut nr = nr;
+    if counts.is_empty() {
+        let Some((count_nr, line)) = lines.next() else {
+            return Err(ParseError::format("the file ends before the counts"));
+        };
+        nr = count_nr;
+        counts = line.split_whitespace().collect();
+    }
+    let counts = counts
+        .iter()
+        .map(|c| c.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ParseError::line(nr, "expected the vertex, face and edge counts"))?;
+    let (Some(vert_count), Some(face_count)) = (counts.first(), counts.get(1)) else {
+        return Err(ParseError::line(
+            nr,
+            "expected the vertex, face and edge counts",
+        ));
+    };
+
+    let mut verts = Vec::new();
+    let mut colors = Vec::new();
+    for _ in 0..*vert_count {
+        let Some((nr, line)) = lines.next() else {
+            return Err(ParseError::format(
+                "the file ends before all vertices are read",
+            ));
+        };
+        let num = line
+            .split_whitespace()
+            .map(|s| s.parse::<fxx>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ParseError::line(nr, "vertex coordinates are not numbers"))?;
+        if num.len() < 3 {
+            return Err(ParseError::line(nr, "vertex should have 3 coordinates"));
+        }
+        verts.push(Vec3::new(num[0], num[1], num[2]));
+        if has_colors {
+            if num.len() < 6 {
+                return Err(ParseError::line(nr, "vertex should have a color"));
+            }
+            colors.push((Vec3::new(num[3], num[4], num[5]), num.get(6).copied()));
+        }
+    }
+
+    // colors are either bytes or in the 0..1 range. A missing alpha is opaque in both
+    let bytes = colors
+        .iter()
+        .any(|(rgb, alpha)| rgb.max_element() > 1.0 || alpha.map_or(false, |a| a > 1.0));
+    let range: fxx = match bytes {
+        true => 255.0,
+        false => 1.0,
+    };
+    let colors = colors
+        .into_iter()
+        .map(|(rgb, alpha)| (rgb / range).extend(alpha.map_or(1.0, |a| a / range)))
+        .collect::<Vec<Vec4>>();
+
+    let mut faces = Vec::new();
+    for _ in 0..*face_count {
+        let Some((nr, line)) = lines.next() else {
+            return Err(ParseError::format(
+                "the file ends before all faces are read",
+            ));
+        };
+        let num = line
+            .split_whitespace()
+            .map(|s| s.parse::<usize>())
+            .collect::<Vec<_>>();
+        let Some(Ok(len)) = num.first() else {
+            return Err(ParseError::line(
+                nr,
+                "a face should start with its vertex count",
+            ));
+        };
+        if *len < 3 {
+            return Err(ParseError::line(nr, "a face needs at least 3 vertices"));
+        }
+        // anything after the indices is a face color, which we ignore
+        let face = num
+            .iter()
+            .skip(1)
+            .take(*len)
+            .map(|i| i.as_ref().ok().copied().filter(|i| *i < verts.len()))
+            .collect::<Option<Vec<_>>>();
+        match face {
+            Some(face) if face.len() == *len => faces.push(face),
+            _ => return Err(ParseError::line(nr, "invalid vertex index")),
+        }
+    }
+
+    Ok(OffData {
+        verts,
+        colors: has_colors.then_some(colors),
+        faces,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::ParseError;
+    use crate::kernel::Vec4;
+    use crate::solid::{Mesh, Polyhedron};
+
+    const PYRAMID: &str = "OFF
+# a square based pyramid
+5 5 8
+0 0 0
+1 0 0
+1 1 0
+0 1 0
+0.5 0.5 1
+4 3 2 1 0
+3 0 1 4
+3 1 2 4
+3 2 3 4
+3 3 0 4 255 0 0
+";
+
+    #[test]
+    fn test_off_import() {
+        let mesh = Mesh::from_off_str(PYRAMID).unwrap();
+        assert_eq!(mesh.verts.len(), 5);
+        assert_eq!(mesh.tri.len(), 6 * 3);
+        // the bottom should still face down
+        for (n, (a, b, c)) in mesh
+            .calc_flat_face_normals()
+            .iter()
+            .zip(mesh.iter_triangles())
+        {
+            if a < 4 && b < 4 && c < 4 {
+                assert!(n.z < -0.99);
+            }
+        }
+
+        let hedron = Polyhedron::from_off_str(PYRAMID).unwrap();
+        assert_eq!(hedron.verts.iter().count(), 5);
+        assert_eq!(hedron.edges.iter().count(), 8 * 2);
+
+        let Err(ParseError::Line(line, _)) =
+            Mesh::from_off_str(&PYRAMID.replace("3 2 3 4", "3 2 3 5"))
+        else {
+            panic!("expected an error at a line");
+        };
+        assert_eq!(line, 12);
+    }
+
+    #[test]
+    fn test_off_round_trip() {
+        let cube = Mesh::new_cube(1.0);
+        let colors = vec![Vec4::new(1.0, 0.5, 0.0, 1.0); cube.verts.len()];
+        let cube = cube.with_vert_colors(colors.clone());
+
+        let off = String::from_utf8(cube.gen_off_buffer().unwrap()).unwrap();
+        assert!(off.starts_with("COFF"));
+        let mesh = Mesh::from_off_str(&off).unwrap();
+        assert_eq!(mesh.verts, cube.verts);
+        assert_eq!(mesh.tri, cube.tri);
+        assert_eq!(mesh.vert_attributes.colors(), Some(&colors));
+    }
+
+    #[test]
+    fn test_off_byte_colors() {
+        let off = "COFF\n3 1 0\n0 0 0 255 0 0\n1 0 0 0 255 0\n0 1 0 0 0 255 127.5\n3 0 1 2\n";
+        let mesh = Mesh::from_off_str(off).unwrap();
+        let colors = mesh.vert_attributes.colors().unwrap();
+        assert_eq!(colors[0], Vec4::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(colors[1], Vec4::new(0.0, 1.0, 0.0, 1.0));
+        assert_eq!(colors[2], Vec4::new(0.0, 0.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn test_off_oversized_count() {
+        // counts are not trusted to allocate up front
+        let off = "OFF\n999999999999 1 0\n0 0 0\n";
+        assert!(Mesh::from_off_str(off).is_err());
+    }
+}
@@ -0,0 +1,691 @@
+use std::io::Write;
+
+use super::obj::triangulate_polygon;
+use super::ParseError;
+use crate::kernel::{fxx, Vec2, Vec3, Vec4};
+use crate::solid::{Attribute, Attributes, Mesh, Normals};
+
+/// PLY flavors we can read and write. Big endian files are rare enough to not bother with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+/// property names which are not stored as a generic attribute
+const KNOWN_PROPERTIES: [&str; 17] = [
+    "x",
+    "y",
+    "z",
+    "nx",
+    "ny",
+    "nz",
+    "s",
+    "t",
+    "u",
+    "v",
+    "texture_u",
+    "texture_v",
+    "texture_s",
+    "texture_t",
+    "red",
+    "green",
+    "blue",
+];
+
+const UV_PROPERTIES: [(&str, &str); 4] = [
+    ("s", "t"),
+    ("u", "v"),
+    ("texture_u", "texture_v"),
+    ("texture_s", "texture_t"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+#[derive(Debug, Clone)]
+struct PlyProperty {
+    name: String,
+    kind: PlyType,
+    /// the type of the length prefix, if this is a list property
+    list: Option<PlyType>,
+}
+
+#[derive(Debug, Clone)]
+struct PlyElement {
+    name: String,
+    count: usize,
+    props: Vec<PlyProperty>,
+}
+
+#[derive(Debug, Clone)]
+enum PlyValue {
+    Scalar(f64),
+    List(Vec<f64>),
+}
+
+#[derive(Debug, Clone)]
+struct PlyHeader {
+    format: PlyFormat,
+    elements: Vec<PlyElement>,
+    /// the byte at which the body starts
+    body_start: usize,
+    /// the line at which the body starts, for error messages
+    body_line: usize,
+}
+
+/// per element, per row, per property
+type PlyData = Vec<Vec<Vec<PlyValue>>>;
+
+impl PlyType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+
+    /// Integer colors are stored in the full range of their type
+    fn color_scale(self) -> f64 {
+        match self {
+            PlyType::U8 => 255.0,
+            PlyType::U16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+
+    fn read_le(self, buffer: &[u8], at: &mut usize) -> Option<f64> {
+        let bytes = buffer.get(*at..*at + self.size())?;
+        *at += self.size();
+        let mut b = [0u8; 8];
+        b[..bytes.len()].copy_from_slice(bytes);
+        Some(match self {
+            PlyType::I8 => b[0] as i8 as f64,
+            PlyType::U8 => b[0] as f64,
+            PlyType::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            PlyType::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            PlyType::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            PlyType::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            PlyType::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            PlyType::F64 => f64::from_le_bytes(b),
+        })
+    }
+}
+
+impl Mesh {
+    pub fn read_ply(path: &str) -> Result<Mesh, ParseError> {
+        let buffer = std::fs::read(path)?;
+        Mesh::from_ply_buffer(&buffer)
+    }
+
+    pub fn write_ply(&self, path: &str, format: PlyFormat) -> Result<(), std::io::Error> {
+        let ply = self.gen_ply_buffer(format)?;
+        let mut ply_file = std::fs::File::create(path)?;
+        ply_file.write_all(&ply)?;
+        Ok(())
+    }
+
+    /// Read an ascii or binary little endian ply.
+    /// Next to positions, faces, normals and uvs, the vertex colors are stored as [`Attributes::COLOR`],
+    /// and all other scalar vertex properties as scalar attributes of the same name.
+    /// Polygon faces are triangulated.
+    pub fn from_ply_buffer(buffer: &[u8]) -> Result<Mesh, ParseError> {
+        let header = parse_ply_header(buffer)?;
+        let body = &buffer[header.body_start..];
+        let data = match header.format {
+            PlyFormat::Ascii => parse_ply_ascii(&header.elements, body, header.body_line)?,
+            PlyFormat::BinaryLittleEndian => parse_ply_binary(&header.elements, body)?,
+        };
+
+        let mut mesh = Mesh::default();
+        for (element, rows) in header.elements.iter().zip(data) {
+            match element.name.as_str() {
+                "vertex" => read_ply_vertices(&mut mesh, element, &rows)?,
+                "face" => read_ply_faces(&mut mesh, element, &rows)?,
+                _ => continue,
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// Writes positions, vertex normals, uvs, vertex colors and all scalar vertex attributes.
+    /// Attributes of other types are skipped, ply has no notion of them.
+    /// Scalar attributes named like a position, normal, uv or color property, like `x` or `red`,
+    /// are written with an `attribute_` prefix, so they don't collide with those properties.
+    pub fn gen_ply_buffer(&self, format: PlyFormat) -> Result<Vec<u8>, std::io::Error> {
+        let count = self.verts.len();
+        let normals = match &self.normals {
+            Normals::Vertex(normals) if normals.len() == count => Some(normals),
+            _ => None,
+        };
+        let uvs = (!self.uvs.is_empty() && self.uvs.len() == count).then_some(&self.uvs);
+        let colors = self.vert_attributes.colors().filter(|c| c.len() == count);
+        let scalars = self
+            .vert_attributes
+            .iter()
+            .filter_map(|(name, attribute)| match attribute {
+                Attribute::Scalar(data) if data.len() == count => {
+                    let mut name = name.replace(char::is_whitespace, "_");
+                    while name == "alpha" || KNOWN_PROPERTIES.contains(&name.as_str()) {
+                        name.insert_str(0, "attribute_");
+                    }
+                    Some((name, data))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut ply = Vec::new();
+        let o = &mut ply;
+        writeln!(o, "ply")?;
+        match format {
+            PlyFormat::Ascii => writeln!(o, "format ascii 1.0")?,
+            PlyFormat::BinaryLittleEndian => writeln!(o, "format binary_little_endian 1.0")?,
+        }
+        writeln!(o, "comment ply generated by Hedron")?;
+        writeln!(o, "element vertex {}", count)?;
+        let mut props = vec!["x", "y", "z"];
+        if normals.is_some() {
+            props.extend(["nx", "ny", "nz"]);
+        }
+        if uvs.is_some() {
+            props.extend(["s", "t"]);
+        }
+        for prop in props {
+            writeln!(o, "property float {}", prop)?;
+        }
+        if colors.is_some() {
+            for prop in ["red", "green", "blue", "alpha"] {
+                writeln!(o, "property uchar {}", prop)?;
+            }
+        }
+        for (name, _) in scalars.iter() {
+            writeln!(o, "property float {}", name)?;
+        }
+        writeln!(o, "element face {}", self.tri.len() / 3)?;
+        writeln!(o, "property list uchar uint vertex_indices")?;
+        writeln!(o, "end_header")?;
+
+        for i in 0..count {
+            let mut floats = self.verts[i].to_array().to_vec();
+            if let Some(normals) = normals {
+                floats.extend(normals[i].to_array());
+            }
+            if let Some(uvs) = uvs {
+                floats.extend(uvs[i].to_array());
+            }
+            let bytes = colors
+                .map(|colors| {
+                    colors[i]
+                        .to_array()
+                        .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+                })
+                .unwrap_or_default();
+            let color_bytes = &bytes[..if colors.is_some() { 4 } else { 0 }];
+            let extra = scalars.iter().map(|(_, data)| data[i]).collect::<Vec<_>>();
+
+            match format {
+                PlyFormat::Ascii => {
+                    let mut words = floats.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+                    words.extend(color_bytes.iter().map(|c| c.to_string()));
+                    words.extend(extra.iter().map(|f| f.to_string()));
+                    writeln!(o, "{}", words.join(" "))?;
+                }
+                PlyFormat::BinaryLittleEndian => {
+                    for f in floats {
+                        o.write_all(&(f as f32).to_le_bytes())?;
+                    }
+                    o.write_all(color_bytes)?;
+                    for f in extra {
+                        o.write_all(&(f as f32).to_le_bytes())?;
+                    }
+                }
+            }
+        }
+
+        for (a, b, c) in self.iter_triangles() {
+            match format {
+                PlyFormat::Ascii => writeln!(o, "3 {} {} {}", a, b, c)?,
+                PlyFormat::BinaryLittleEndian => {
+                    o.write_all(&[3])?;
+                    for i in [a, b, c] {
+                        o.write_all(&(i as u32).to_le_bytes())?;
+                    }
+                }
+            }
+        }
+        Ok(ply)
+    }
+}
+
+fn parse_ply_header(buffer: &[u8]) -> Result<PlyHeader, ParseError> {
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    let mut at = 0;
+    let mut nr = 0;
+
+    loop {
+        let Some(len) = buffer[at..].iter().position(|b| *b == b'\n') else {
+            return Err(ParseError::format(
+                "the ply header is not terminated by 'end_header'",
+            ));
+        };
+        let line = std::str::from_utf8(&buffer[at..at + len])
+            .map_err(|_| ParseError::line(nr + 1, "the ply header should be ascii"))?;
+        at += len + 1;
+        nr += 1;
+
+        let parts: Vec<_> = line.split_whitespace().collect();
+        let Some(keyword) = parts.first() else {
+            continue;
+        };
+        if nr == 1 && *keyword != "ply" {
+            return Err(ParseError::line(nr, "not a ply file"));
+        }
+        match *keyword {
+            "ply" | "comment" | "obj_info" => continue,
+            "format" => {
+                format = Some(match parts.get(1) {
+                    Some(&"ascii") => PlyFormat::Ascii,
+                    Some(&"binary_little_endian") => PlyFormat::BinaryLittleEndian,
+                    Some(other) => {
+                        return Err(ParseError::line(
+                            nr,
+                            format!("unsupported ply format '{}'", other),
+                        ))
+                    }
+                    None => return Err(ParseError::line(nr, "missing ply format")),
+                });
+            }
+            "element" => {
+                let (Some(name), Some(Ok(count))) =
+                    (parts.get(1), parts.get(2).map(|c| c.parse::<usize>()))
+                else {
+                    return Err(ParseError::line(nr, "expected 'element <name> <count>'"));
+                };
+                elements.push(PlyElement {
+                    name: name.to_string(),
+                    count,
+                    props: Vec::new(),
+                });
+            }
+            "property" => {
+                let Some(element) = elements.last_mut() else {
+                    return Err(ParseError::line(nr, "property defined before any element"));
+                };
+                let parse_type = |name: Option<&&str>| {
+                    name.and_then(|name| PlyType::parse(name))
+                        .ok_or_else(|| ParseError::line(nr, "unknown property type"))
+                };
+                let prop = if parts.get(1) == Some(&"list") {
+                    PlyProperty {
+                        list: Some(parse_type(parts.get(2))?),
+                        kind: parse_type(parts.get(3))?,
+                        name: parts.get(4).unwrap_or(&"").to_string(),
+                    }
+                } else {
+                    PlyProperty {
+                        list: None,
+                        kind: parse_type(parts.get(1))?,
+                        name: parts.get(2).unwrap_or(&"").to_string(),
+                    }
+                };
+                element.props.push(prop);
+            }
+            "end_header" => break,
+            other => {
+                return Err(ParseError::line(
+                    nr,
+                    format!("unexpected keyword '{}'", other),
+                ));
+            }
+        }
+    }
+
+    let Some(format) = format else {
+        return Err(ParseError::format(
+            "the ply header does not specify a format",
+        ));
+    };
+    Ok(PlyHeader {
+        format,
+        elements,
+        body_start: at,
+        body_line: nr + 1,
+    })
+}
+
+fn parse_ply_ascii(
+    elements: &[PlyElement],
+    body: &[u8],
+    body_line: usize,
+) -> Result<PlyData, ParseError> {
+    let body = std::str::from_utf8(body).map_err(|_| ParseError::format("invalid ascii ply"))?;
+    let mut lines = body
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + body_line, line))
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let mut data = Vec::with_capacity(elements.len());
+    for element in elements {
+        let mut rows = Vec::new();
+        for _ in 0..element.count {
+            let Some((nr, line)) = lines.next() else {
+                return Err(ParseError::format(format!(
+                    "the file ends before all '{}' elements are read",
+                    element.name
+                )));
+            };
+            let mut words = line.split_whitespace().map(|w| w.parse::<f64>());
+            let mut next = || match words.next() {
+                Some(Ok(num)) => Ok(num),
+                Some(Err(_)) => Err(ParseError::line(nr, "expected a number")),
+                None => Err(ParseError::line(nr, "not enough values")),
+            };
+            let mut row = Vec::with_capacity(element.props.len());
+            for prop in element.props.iter() {
+                row.push(match prop.list {
+                    None => PlyValue::Scalar(next()?),
+                    Some(_) => {
+                        let len = next()? as usize;
+                        PlyValue::List((0..len).map(|_| next()).collect::<Result<_, _>>()?)
+                    }
+                });
+            }
+            rows.push(row);
+        }
+        data.push(rows);
+    }
+    Ok(data)
+}
+
+fn parse_ply_binary(elements: &[PlyElement], body: &[u8]) -> Result<PlyData, ParseError> {
+    let mut at = 0;
+    let mut data = Vec::with_capacity(elements.len());
+    for element in elements {
+        let too_short = || {
+            ParseError::format(format!(
+                "the file ends before all '{}' elements are read",
+                element.name
+            ))
+        };
+        let mut rows = Vec::new();
+        for _ in 0..element.count {
+            let mut row = Vec::with_capacity(element.props.len());
+            for prop in element.props.iter() {
+                row.push(match prop.list {
+                    None => {
+                        PlyValue::Scalar(prop.kind.read_le(body, &mut at).ok_or_else(too_short)?)
+                    }
+                    Some(count_kind) => {
+                        let len = count_kind.read_le(body, &mut at).ok_or_else(too_short)? as usize;
+                        PlyValue::List(
+                            (0..len)
+                                .map(|_| prop.kind.read_le(body, &mut at).ok_or_else(too_short))
+                                .collect::<Result<_, _>>()?,
+                        )
+                    }
+                });
+            }
+            rows.push(row);
+        }
+        data.push(rows);
+    }
+    Ok(data)
+}
+
+fn read_ply_vertices(
+    mesh: &mut Mesh,
+    element: &PlyElement,
+    rows: &[Vec<PlyValue>],
+) -> Result<(), ParseError> {
+    let find = |name: &str| {
+        element
+            .props
+            .iter()
+            .position(|p| p.name == name && p.list.is_none())
+    };
+    let scalar = |row: &[PlyValue], id: usize| match row[id] {
+        PlyValue::Scalar(s) => s as fxx,
+        PlyValue::List(_) => 0.0,
+    };
+
+    let (Some(x), Some(y), Some(z)) = (find("x"), find("y"), find("z")) else {
+        return Err(ParseError::format(
+            "ply vertices should have x, y and z properties",
+        ));
+    };
+    mesh.verts = rows
+        .iter()
+        .map(|row| Vec3::new(scalar(row, x), scalar(row, y), scalar(row, z)))
+        .collect();
+
+    if let (Some(nx), Some(ny), Some(nz)) = (find("nx"), find("ny"), find("nz")) {
+        mesh.normals = Normals::Vertex(
+            rows.iter()
+                .map(|row| Vec3::new(scalar(row, nx), scalar(row, ny), scalar(row, nz)))
+                .collect(),
+        );
+    }
+
+    let uv = UV_PROPERTIES
+        .iter()
+        .find_map(|(u, v)| Some((find(u)?, find(v)?)));
+    if let Some((u, v)) = uv {
+        mesh.uvs = rows
+            .iter()
+            .map(|row| Vec2::new(scalar(row, u), scalar(row, v)))
+            .collect();
+    }
+
+    if let (Some(r), Some(g), Some(b)) = (find("red"), find("green"), find("blue")) {
+        let a = find("alpha");
+        let channel = |row: &[PlyValue], id: usize| {
+            scalar(row, id) / element.props[id].kind.color_scale() as fxx
+        };
+        let colors = rows
+            .iter()
+            .map(|row| {
+                let alpha = a.map(|a| channel(row, a)).unwrap_or(1.0);
+                Vec4::new(channel(row, r), channel(row, g), channel(row, b), alpha)
+            })
+            .collect();
+        mesh.vert_attributes
+            .insert(Attributes::COLOR, Attribute::Vec4(colors));
+    }
+
+    for (id, prop) in element.props.iter().enumerate() {
+        if prop.list.is_some()
+            || prop.name == "alpha"
+            || KNOWN_PROPERTIES.contains(&prop.name.as_str())
+        {
+            continue;
+        }
+        let data = rows.iter().map(|row| scalar(row, id)).collect();
+        mesh.vert_attributes
+            .insert(prop.name.clone(), Attribute::Scalar(data));
+    }
+    Ok(())
+}
+
+fn read_ply_faces(
+    mesh: &mut Mesh,
+    element: &PlyElement,
+    rows: &[Vec<PlyValue>],
+) -> Result<(), ParseError> {
+    let Some(id) = element
+        .props
+        .iter()
+        .position(|p| p.list.is_some() && (p.name == "vertex_indices" || p.name == "vertex_index"))
+    else {
+        return Err(ParseError::format(
+            "ply faces should have a vertex_indices list",
+        ));
+    };
+
+    for (f, row) in rows.iter().enumerate() {
+        let PlyValue::List(face) = &row[id] else {
+            continue;
+        };
+        if face.len() < 3 {
+            return Err(ParseError::format(format!(
+                "face {} has less than 3 vertices",
+                f
+            )));
+        }
+        let face = face.iter().map(|i| *i as usize).collect::<Vec<_>>();
+        let Some(polygon) = face
+            .iter()
+            .map(|i| mesh.verts.get(*i).copied())
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Err(ParseError::format(format!(
+                "face {} refers to a vertex which does not exist",
+                f
+            )));
+        };
+        mesh.tri
+            .extend(triangulate_polygon(&polygon).into_iter().map(|i| face[i]));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlyFormat;
+    use crate::io::ParseError;
+    use crate::kernel::{fxx, vec3, Vec4};
+    use crate::solid::{Attribute, Attributes, Mesh};
+
+    #[test]
+    fn test_ply_round_trip() {
+        let cube = Mesh::new_cube(1.0);
+        let count = cube.verts.len();
+        let colors = (0..count)
+            .map(|i| Vec4::new(i as fxx, 51.0, 0.0, 255.0) / 255.0)
+            .collect::<Vec<_>>();
+        let quality = (0..count).map(|i| i as fxx).collect::<Vec<_>>();
+        let cube = cube
+            .with_vert_colors(colors.clone())
+            .with_vert_attribute("quality", Attribute::Scalar(quality.clone()));
+
+        for format in [PlyFormat::Ascii, PlyFormat::BinaryLittleEndian] {
+            let ply = cube.gen_ply_buffer(format).unwrap();
+            let mesh = Mesh::from_ply_buffer(&ply).unwrap();
+            assert_eq!(mesh.tri, cube.tri);
+            assert_eq!(mesh.uvs.len(), count);
+            assert_eq!(mesh.get_normals().map(|n| n.len()), Some(count));
+            for (a, b) in mesh.verts.iter().zip(cube.verts.iter()) {
+                assert!(a.distance(*b) < 1e-6);
+            }
+            for (a, b) in mesh
+                .vert_attributes
+                .colors()
+                .unwrap()
+                .iter()
+                .zip(colors.iter())
+            {
+                assert!(a.distance(*b) < 1e-6);
+            }
+            assert_eq!(
+                mesh.vert_attributes.get("quality"),
+                Some(&Attribute::Scalar(quality.clone()))
+            );
+        }
+
+        // channels named like the properties of positions or colors get a prefix
+        let cube = cube
+            .with_vert_attribute("x", Attribute::Scalar(quality.clone()))
+            .with_vert_attribute("alpha", Attribute::Scalar(quality.clone()));
+        let mesh = Mesh::from_ply_buffer(&cube.gen_ply_buffer(PlyFormat::Ascii).unwrap()).unwrap();
+        assert_eq!(mesh.verts, cube.verts);
+        for name in ["attribute_x", "attribute_alpha", "quality"] {
+            assert_eq!(
+                mesh.vert_attributes.get(name),
+                Some(&Attribute::Scalar(quality.clone()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_ply_polygons() {
+        let ply = "ply
+format ascii 1.0
+comment a quad and a triangle
+element vertex 5
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+property float confidence
+element face 2
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0 0.5
+1 0 0 0 255 0 0.5
+1 1 0 0 0 255 0.5
+0 1 0 255 255 255 0.5
+0.5 2 0 0 0 0 1.0
+4 0 1 2 3
+3 3 2 4
+";
+        let mesh = Mesh::from_ply_buffer(ply.as_bytes()).unwrap();
+        assert_eq!(mesh.tri.len(), 3 * 3);
+        for n in mesh.calc_flat_face_normals() {
+            assert!(n.distance(vec3(0.0, 0.0, 1.0)) < 1e-6);
+        }
+        let colors = mesh.vert_attributes.colors().unwrap();
+        assert_eq!(colors[1], Vec4::new(0.0, 1.0, 0.0, 1.0));
+        let Some(Attribute::Scalar(confidence)) = mesh.vert_attributes.get("confidence") else {
+            panic!("confidence should be a scalar attribute");
+        };
+        assert_eq!(confidence[4], 1.0);
+        assert_eq!(mesh.vert_attributes.len(), 2);
+        assert!(mesh.vert_attributes.get(Attributes::COLOR).is_some());
+
+        let broken = ply.replace("0.5 2 0 0 0 0 1.0", "0.5 2 0 0 0 zero 1.0");
+        let Err(ParseError::Line(line, _)) = Mesh::from_ply_buffer(broken.as_bytes()) else {
+            panic!("expected an error at a line");
+        };
+        assert_eq!(line, 19);
+    }
+
+    #[test]
+    fn test_ply_oversized_count() {
+        // counts are not trusted to allocate up front
+        for format in ["ascii", "binary_little_endian"] {
+            let ply = format!(
+                "ply\nformat {format} 1.0\nelement vertex 99999999999\nproperty float x\nend_header\n0\n"
+            );
+            assert!(Mesh::from_ply_buffer(ply.as_bytes()).is_err());
+        }
+    }
+}
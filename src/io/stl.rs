@@ -0,0 +1,230 @@
+use std::io::Write;
+
+use super::ParseError;
+use crate::kernel::{fxx, Vec3};
+use crate::solid::{Mesh, Normals, WELD_TOLERANCE};
+
+const BINARY_HEADER_SIZE: usize = 80;
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+/// STL comes in two flavors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StlFormat {
+    Ascii,
+    Binary,
+}
+
+impl Mesh {
+    pub fn read_stl(path: &str) -> Result<Mesh, ParseError> {
+        let buffer = std::fs::read(path)?;
+        Mesh::from_stl_buffer(&buffer)
+    }
+
+    pub fn write_stl(&self, path: &str, format: StlFormat) -> Result<(), std::io::Error> {
+        let stl = self.gen_stl_buffer("stl generated by Hedron", format)?;
+        let mut stl_file = std::fs::File::create(path)?;
+        stl_file.write_all(&stl)?;
+        Ok(())
+    }
+
+    /// Read an ascii or binary stl, the format is detected automatically.
+    /// The normals stored in the file are ignored, since plenty of writers leave them at zero.
+    pub fn from_stl_buffer(buffer: &[u8]) -> Result<Mesh, ParseError> {
+        let soup = if is_binary_stl(buffer) {
+            parse_binary_stl(buffer)?
+        } else {
+            parse_ascii_stl(buffer)?
+        };
+
+        let tri = (0..soup.len()).collect();
+        let mesh = Mesh::new(soup, tri, Vec::new(), Normals::None);
+        Ok(mesh.to_uniform_tol(WELD_TOLERANCE))
+    }
+
+    /// Triangles are written using flat face normals.
+    /// `header` is used as the solid name (ascii), or the 80 byte header (binary).
+    pub fn gen_stl_buffer(
+        &self,
+        header: &str,
+        format: StlFormat,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        let normals = self
+            .calc_flat_face_normals()
+            .into_iter()
+            .map(|n| if n.is_finite() { n } else { Vec3::ZERO })
+            .collect::<Vec<_>>();
+
+        match format {
+            StlFormat::Ascii => self.gen_stl_ascii(header, &normals),
+            StlFormat::Binary => self.gen_stl_binary(header, &normals),
+        }
+    }
+
+    fn gen_stl_ascii(&self, header: &str, normals: &[Vec3]) -> Result<Vec<u8>, std::io::Error> {
+        let mut stl = Vec::new();
+        let o = &mut stl;
+        let name = header.replace(['\n', '\r'], " ");
+
+        writeln!(o, "solid {}", name)?;
+        for ((a, b, c), n) in self.iter_triangle_verts().zip(normals) {
+            writeln!(o, "  facet normal {} {} {}", n.x, n.y, n.z)?;
+            writeln!(o, "    outer loop")?;
+            for v in [a, b, c] {
+                writeln!(o, "      vertex {} {} {}", v.x, v.y, v.z)?;
+            }
+            writeln!(o, "    endloop")?;
+            writeln!(o, "  endfacet")?;
+        }
+        writeln!(o, "endsolid {}", name)?;
+        Ok(stl)
+    }
+
+    fn gen_stl_binary(&self, header: &str, normals: &[Vec3]) -> Result<Vec<u8>, std::io::Error> {
+        let count = self.count_triangles();
+        let mut stl = Vec::with_capacity(BINARY_HEADER_SIZE + 4 + count * BINARY_TRIANGLE_SIZE);
+
+        let mut head = [0u8; BINARY_HEADER_SIZE];
+        for (h, byte) in head.iter_mut().zip(header.bytes()) {
+            *h = byte;
+        }
+        stl.write_all(&head)?;
+        stl.write_all(&(count as u32).to_le_bytes())?;
+
+        for ((a, b, c), n) in self.iter_triangle_verts().zip(normals) {
+            for v in [*n, a, b, c] {
+                for f in [v.x, v.y, v.z] {
+                    stl.write_all(&(f as f32).to_le_bytes())?;
+                }
+            }
+            stl.write_all(&0u16.to_le_bytes())?; // attribute byte count
+        }
+        Ok(stl)
+    }
+}
+
+/// A binary stl can start with "solid" as well, so the size is the only reliable check
+fn is_binary_stl(buffer: &[u8]) -> bool {
+    if buffer.len() < BINARY_HEADER_SIZE + 4 {
+        return false;
+    }
+    let count = read_u32(buffer, BINARY_HEADER_SIZE) as usize;
+    buffer.len() == BINARY_HEADER_SIZE + 4 + count * BINARY_TRIANGLE_SIZE
+}
+
+fn read_u32(buffer: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([buffer[at], buffer[at + 1], buffer[at + 2], buffer[at + 3]])
+}
+
+fn read_f32(buffer: &[u8], at: usize) -> f32 {
+    f32::from_le_bytes([buffer[at], buffer[at + 1], buffer[at + 2], buffer[at + 3]])
+}
+
+/// returns the triangle soup, 3 vertices per triangle
+fn parse_binary_stl(buffer: &[u8]) -> Result<Vec<Vec3>, ParseError> {
+    let count = read_u32(buffer, BINARY_HEADER_SIZE) as usize;
+    let mut soup = Vec::with_capacity(count * 3);
+    for i in 0..count {
+        // skip the normal, the first 12 bytes
+        let start = BINARY_HEADER_SIZE + 4 + i * BINARY_TRIANGLE_SIZE + 12;
+        for corner in 0..3 {
+            let at = start + corner * 12;
+            let v = Vec3::new(
+                read_f32(buffer, at) as fxx,
+                read_f32(buffer, at + 4) as fxx,
+                read_f32(buffer, at + 8) as fxx,
+            );
+            if !v.is_finite() {
+                return Err(ParseError::format(format!(
+                    "triangle {} contains a non-finite vertex",
+                    i
+                )));
+            }
+            soup.push(v);
+        }
+    }
+    Ok(soup)
+}
+
+/// returns the triangle soup, 3 vertices per triangle
+fn parse_ascii_stl(buffer: &[u8]) -> Result<Vec<Vec3>, ParseError> {
+    let string = std::str::from_utf8(buffer)
+        .map_err(|_| ParseError::format("not a binary stl, and not valid ascii either"))?;
+
+    let mut soup = Vec::new();
+    let mut loop_count = 0;
+    for (i, line) in string.lines().enumerate() {
+        let nr = i + 1;
+        let parts: Vec<_> = line.split_whitespace().collect();
+        let Some(keyword) = parts.first() else {
+            continue;
+        };
+        match *keyword {
+            "solid" | "endsolid" | "facet" | "endfacet" => continue,
+            "outer" => loop_count = 0,
+            "vertex" => {
+                let num = parts
+                    .iter()
+                    .skip(1)
+                    .map(|s| s.parse::<fxx>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| ParseError::line(nr, "vertex coordinates are not numbers"))?;
+                if num.len() != 3 {
+                    return Err(ParseError::line(nr, "vertex should have 3 coordinates"));
+                }
+                soup.push(Vec3::new(num[0], num[1], num[2]));
+                loop_count += 1;
+            }
+            "endloop" => {
+                if loop_count != 3 {
+                    return Err(ParseError::line(
+                        nr,
+                        format!("facet should have 3 vertices, found {}", loop_count),
+                    ));
+                }
+            }
+            other => {
+                return Err(ParseError::line(
+                    nr,
+                    format!("unexpected keyword '{}'", other),
+                ));
+            }
+        }
+    }
+
+    if soup.len() % 3 != 0 {
+        return Err(ParseError::format("the last facet is incomplete"));
+    }
+    Ok(soup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StlFormat;
+    use crate::io::ParseError;
+    use crate::solid::Mesh;
+
+    #[test]
+    fn test_stl_round_trip() {
+        let cube = Mesh::new_cube(1.0);
+        for format in [StlFormat::Ascii, StlFormat::Binary] {
+            let buffer = cube.gen_stl_buffer("cube", format).unwrap();
+            let mesh = Mesh::from_stl_buffer(&buffer).unwrap();
+
+            // the 24 vertices of the cube, split per face, are welded back to 8
+            assert_eq!(mesh.verts.len(), 8);
+            assert_eq!(mesh.count_triangles(), 12);
+            for (a, b) in mesh.iter_triangle_verts().zip(cube.iter_triangle_verts()) {
+                assert_eq!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stl_ascii_error() {
+        let stl = "solid bad\n facet normal 0 0 1\n  outer loop\n   vertex 0 0 0\n   vertex 1 0\n";
+        let Err(ParseError::Line(line, _)) = Mesh::from_stl_buffer(stl.as_bytes()) else {
+            panic!("expected an error at a line");
+        };
+        assert_eq!(line, 5);
+    }
+}
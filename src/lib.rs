@@ -1,6 +1,7 @@
 pub mod algos;
 pub mod core;
 pub mod data;
+pub mod io;
 pub mod kernel;
 pub mod lines;
 pub mod math;
@@ -29,6 +30,7 @@ pub mod prelude {
     pub use crate::algos::*;
     pub use crate::core::*;
     pub use crate::data::*;
+    pub use crate::io::*;
     pub use crate::lines::*;
     pub use crate::math::*;
     pub use crate::planar::*;
@@ -9,6 +9,7 @@ use std::ops::Range;
 use super::{Range1, Shaper};
 
 /// A 3D range, or axis-aligned box
+#[derive(Debug, Clone, PartialEq)]
 pub struct Range3 {
     pub x: Range<fxx>,
     pub y: Range<fxx>,
@@ -40,6 +41,50 @@ impl Range3 {
         Self::from_ranges(-r..r, -r..r, -r..r)
     }
 
+    /// The smallest range containing all points.
+    /// Without any points, the range is inverted, and includes nothing
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut range = Self::new(Vec3::splat(fxx::INFINITY), Vec3::splat(fxx::NEG_INFINITY));
+        for point in points {
+            range.expand_to(point);
+        }
+        range
+    }
+
+    #[inline]
+    pub fn min(&self) -> Vec3 {
+        vec3(self.x.start, self.y.start, self.z.start)
+    }
+
+    #[inline]
+    pub fn max(&self) -> Vec3 {
+        vec3(self.x.end, self.y.end, self.z.end)
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max() - self.min()
+    }
+
+    /// The smallest range containing both ranges
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.min().min(other.min()), self.max().max(other.max()))
+    }
+
+    /// Do the two boxes touch or overlap
+    pub fn overlaps(&self, other: &Self) -> bool {
+        !(other.x.start > self.x.end
+            || other.x.end < self.x.start
+            || other.y.start > self.y.end
+            || other.y.end < self.y.start
+            || other.z.start > self.z.end
+            || other.z.end < self.z.start)
+    }
+
+    /// The point within the box closest to `t`
+    pub fn closest_point(&self, t: Vec3) -> Vec3 {
+        t.max(self.min()).min(self.max())
+    }
+
     pub fn includes(&self, t: Vec3) -> bool {
         !(t.x < self.x.start
             || t.x > self.x.end
@@ -169,6 +169,8 @@ impl Polygon {
         sum / 2.0
     }
 
+    /// Estimate a pose at the center of the polygon, with its local z axis along the average normal
+    /// and its local x axis towards the first vertex. Seen from this pose, the polygon runs counter clockwise.
     pub fn estimate_pose(&self) -> Pose {
         assert!(
             self.verts.len() >= 3,
@@ -178,7 +180,8 @@ impl Polygon {
         let normal = self.average_normal();
         let first = self.verts.first().unwrap();
         let axis = *first - center;
-        Pose::from_pos(center).looking_at(normal, axis)
+        // `looking_at` points the local negative z axis at the target, so look away from the normal
+        Pose::from_pos(center).looking_at(center - normal, axis)
     }
 
     /// calculate the normalized vertices using the estimated pose
@@ -258,4 +261,27 @@ mod tests {
         polygon = polygon.offset(&plane, 0.25);
         println!("{polygon:?}");
     }
+
+    #[test]
+    pub fn test_estimate_pose() {
+        let polygon = Polygon::new(vec![
+            Vec3::new(0., 0., 2.),
+            Vec3::new(1., 0., 2.),
+            Vec3::new(1., 1., 2.),
+            Vec3::new(0., 1., 2.),
+        ]);
+
+        // the local z axis is the average normal, so the planar area matches the 2d area
+        let pose = polygon.estimate_pose();
+        assert!(pose.pos.distance(Vec3::new(0.5, 0.5, 2.0)) < 1e-9);
+        assert!(pose.normal().distance(Vec3::Z) < 1e-9);
+        assert!((polygon.posed_signed_area(&pose) - polygon.signed_area_2d()).abs() < 1e-9);
+        assert!((polygon.signed_area_planar() + 1.0).abs() < 1e-9);
+
+        // a flipped polygon is seen from the other side
+        let flipped = polygon.clone().flip();
+        assert!(flipped.estimate_pose().normal().distance(-Vec3::Z) < 1e-9);
+        assert!((flipped.signed_area_planar() + 1.0).abs() < 1e-9);
+        assert!((flipped.posed_signed_area(&pose) - 1.0).abs() < 1e-9);
+    }
 }
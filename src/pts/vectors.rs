@@ -1,8 +1,10 @@
 // we need a different struct, since a bunch of vectors will need to be rendered as a bunch of arrows
 
 use crate::{
+    algos::{convex_hull, convex_hull_faces},
     core::PointBased,
     kernel::{fxx, Quat, Vec2, Vec3, TAU},
+    solid::{Mesh, Normals, Polyhedron},
 };
 
 // abstraction around a list of vectors.
@@ -115,6 +117,47 @@ impl Vectors {
         // println!("{rot}");
         center + rot
     }
+
+    /// The convex hull of these points, as a closed mesh with outward facing triangles.
+    /// Only the corners of the hull become vertices, in the order they appear in.
+    /// Returns `None` if the points are coplanar. See [`convex_hull`].
+    pub fn convex_hull(&self) -> Option<Mesh> {
+        let triangles = convex_hull(&self.data)?;
+        let (verts, faces) = Self::compact_hull(&self.data, triangles.iter().map(|t| t.to_vec()));
+        Some(Mesh::new(verts, faces.concat(), Vec::new(), Normals::None))
+    }
+
+    /// The convex hull of these points, with every flat side as a single face.
+    /// Returns `None` if the points are coplanar. See [`convex_hull_faces`].
+    pub fn convex_hull_polyhedron(&self) -> Option<Polyhedron> {
+        let faces = convex_hull_faces(&self.data)?;
+        let (verts, faces) = Self::compact_hull(&self.data, faces.into_iter());
+        Polyhedron::from_faces(&verts, &faces)
+    }
+
+    /// Keep only the points used by `faces`, and point to those instead
+    fn compact_hull(
+        points: &[Vec3],
+        faces: impl Iterator<Item = Vec<usize>>,
+    ) -> (Vec<Vec3>, Vec<Vec<usize>>) {
+        let faces = faces.collect::<Vec<_>>();
+        let mut ids = vec![usize::MAX; points.len()];
+        for v in faces.iter().flatten() {
+            ids[*v] = 0;
+        }
+        let mut verts = Vec::new();
+        for (id, point) in ids.iter_mut().zip(points) {
+            if *id == 0 {
+                *id = verts.len();
+                verts.push(*point);
+            }
+        }
+        let faces = faces
+            .into_iter()
+            .map(|face| face.into_iter().map(|v| ids[v]).collect())
+            .collect();
+        (verts, faces)
+    }
 }
 
 impl PointBased for Vectors {
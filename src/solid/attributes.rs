@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+use crate::kernel::{fxx, Vec2, Vec3, Vec4};
+
+/// A channel of data, with one item per vertex, or one item per triangle
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attribute {
+    Scalar(Vec<fxx>),
+    Vec2(Vec<Vec2>),
+    Vec3(Vec<Vec3>),
+    Vec4(Vec<Vec4>),
+    /// Whole numbers, like material ids. These are never interpolated
+    Index(Vec<usize>),
+}
+
+impl Attribute {
+    /// Pads `Attribute::Index` channels, where items are added without a value to inherit.
+    /// It is no valid index, so padded triangles have no material, instead of material 0
+    pub const NO_INDEX: usize = usize::MAX;
+
+    pub fn len(&self) -> usize {
+        match self {
+            Attribute::Scalar(data) => data.len(),
+            Attribute::Vec2(data) => data.len(),
+            Attribute::Vec3(data) => data.len(),
+            Attribute::Vec4(data) => data.len(),
+            Attribute::Index(data) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A new channel, containing the items at `ids`, in that order
+    pub fn select(&self, ids: &[usize]) -> Self {
+        match self {
+            Attribute::Scalar(data) => Attribute::Scalar(ids.iter().map(|i| data[*i]).collect()),
+            Attribute::Vec2(data) => Attribute::Vec2(ids.iter().map(|i| data[*i]).collect()),
+            Attribute::Vec3(data) => Attribute::Vec3(ids.iter().map(|i| data[*i]).collect()),
+            Attribute::Vec4(data) => Attribute::Vec4(ids.iter().map(|i| data[*i]).collect()),
+            Attribute::Index(data) => Attribute::Index(ids.iter().map(|i| data[*i]).collect()),
+        }
+    }
+
+    /// Pad the channel with zeroes, or [`Attribute::NO_INDEX`] for indices, or truncate it, so it contains `len` items
+    pub fn resize(&mut self, len: usize) {
+        match self {
+            Attribute::Scalar(data) => data.resize(len, 0.0),
+            Attribute::Vec2(data) => data.resize(len, Vec2::ZERO),
+            Attribute::Vec3(data) => data.resize(len, Vec3::ZERO),
+            Attribute::Vec4(data) => data.resize(len, Vec4::ZERO),
+            Attribute::Index(data) => data.resize(len, Self::NO_INDEX),
+        }
+    }
+
+    /// Add the items of `other` to the end. Returns false, and leaves the channel as is, if `other` holds another kind of item
+    pub fn append(&mut self, other: &Attribute) -> bool {
+        match (self, other) {
+            (Attribute::Scalar(data), Attribute::Scalar(more)) => data.extend(more),
+            (Attribute::Vec2(data), Attribute::Vec2(more)) => data.extend(more),
+            (Attribute::Vec3(data), Attribute::Vec3(more)) => data.extend(more),
+            (Attribute::Vec4(data), Attribute::Vec4(more)) => data.extend(more),
+            (Attribute::Index(data), Attribute::Index(more)) => data.extend(more),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Push a new item, interpolated between the items at `a` and `b`.
+    /// Indices are not interpolated, but copied from the closest of the two
+    pub fn push_lerp(&mut self, a: usize, b: usize, t: fxx) {
+        match self {
+            Attribute::Scalar(data) => data.push(data[a] + (data[b] - data[a]) * t),
+            Attribute::Vec2(data) => data.push(data[a].lerp(data[b], t)),
+            Attribute::Vec3(data) => data.push(data[a].lerp(data[b], t)),
+            Attribute::Vec4(data) => data.push(data[a].lerp(data[b], t)),
+            Attribute::Index(data) => data.push(if t < 0.5 { data[a] } else { data[b] }),
+        }
+    }
+}
+
+/// Named attribute channels, stored next to the vertices or the triangles of a [`super::Mesh`].
+/// Used for things like vertex colors, the arbitrary scalar properties of a scanned point cloud, or material ids.
+///
+/// A `BTreeMap` is used so channels are always iterated (and written to files) in the same order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attributes {
+    channels: BTreeMap<String, Attribute>,
+}
+
+impl Attributes {
+    /// The conventional name of the rgba vertex color channel, stored as `Attribute::Vec4` in the 0..1 range
+    pub const COLOR: &'static str = "color";
+
+    /// The conventional name of the tangent channel, stored as `Attribute::Vec4` with the bitangent sign in w.
+    /// See [`super::Mesh::with_tangents`]
+    pub const TANGENT: &'static str = "tangent";
+
+    /// The conventional name of the per-triangle material channel, stored as `Attribute::Index`.
+    /// See [`crate::io::ObjScene`]
+    pub const MATERIAL: &'static str = "material";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Attribute> {
+        self.channels.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Attribute> {
+        self.channels.get_mut(name)
+    }
+
+    /// Returns the channel previously stored under `name`, if any
+    pub fn insert(&mut self, name: impl Into<String>, attribute: Attribute) -> Option<Attribute> {
+        self.channels.insert(name.into(), attribute)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Attribute> {
+        self.channels.remove(name)
+    }
+
+    pub fn clear(&mut self) {
+        self.channels.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Attribute)> {
+        self.channels.iter()
+    }
+
+    /// Select the items at `ids` from every channel.
+    /// Channels which don't contain exactly `len` items are out of sync with their vertices, and are dropped.
+    pub fn select(&self, len: usize, ids: &[usize]) -> Self {
+        let channels = self
+            .channels
+            .iter()
+            .filter(|(_, attribute)| attribute.len() == len)
+            .map(|(name, attribute)| (name.clone(), attribute.select(ids)))
+            .collect();
+        Self { channels }
+    }
+
+    /// Append the channels of `other`, holding `other_len` items, after the `len` items of these channels.
+    /// Channels missing on one side, or holding another kind of item, are padded, see [`Attribute::resize`].
+    /// Used when joining meshes
+    pub fn append(&mut self, len: usize, other: &Attributes, other_len: usize) {
+        for (name, attribute) in other.iter() {
+            if !self.channels.contains_key(name) {
+                self.channels.insert(name.clone(), attribute.select(&[]));
+            }
+        }
+        for (name, channel) in self.channels.iter_mut() {
+            channel.resize(len);
+            if let Some(attribute) = other.get(name).filter(|a| a.len() == other_len) {
+                channel.append(attribute);
+            }
+            channel.resize(len + other_len);
+        }
+    }
+
+    /// Pad every channel, or truncate it, so it contains `len` items. See [`Attribute::resize`].
+    /// Used for new vertices which have no sensible attribute value to inherit.
+    pub fn resize(&mut self, len: usize) {
+        for attribute in self.channels.values_mut() {
+            attribute.resize(len);
+        }
+    }
+
+    /// Push an interpolated item to every channel, for a new vertex between vertices `a` and `b`
+    pub fn push_lerp(&mut self, a: usize, b: usize, t: fxx) {
+        for attribute in self.channels.values_mut() {
+            attribute.push_lerp(a, b, t);
+        }
+    }
+
+    pub fn colors(&self) -> Option<&Vec<Vec4>> {
+        match self.get(Self::COLOR) {
+            Some(Attribute::Vec4(colors)) => Some(colors),
+            _ => None,
+        }
+    }
+
+    pub fn materials(&self) -> Option<&Vec<usize>> {
+        match self.get(Self::MATERIAL) {
+            Some(Attribute::Index(materials)) => Some(materials),
+            _ => None,
+        }
+    }
+
+    pub fn tangents(&self) -> Option<&Vec<Vec4>> {
+        match self.get(Self::TANGENT) {
+            Some(Attribute::Vec4(tangents)) => Some(tangents),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,504 @@
+use super::Mesh;
+use crate::kernel::{fxx, Vec3, EPSILON};
+use crate::lines::Ray;
+use crate::math::Range3;
+
+/// Triangles per leaf. Leaves are only split beyond this count
+const LEAF_SIZE: usize = 4;
+
+/// A ray hitting a triangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// index of the triangle, as in [`Mesh::iter_triangles`]
+    pub triangle: usize,
+    /// parameter along the ray. Equal to the distance if the ray normal is normalized
+    pub t: fxx,
+    /// weights of the three triangle corners at the hit point
+    pub barycentric: Vec3,
+}
+
+/// The point on a mesh closest to some query point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClosestPoint {
+    pub triangle: usize,
+    pub point: Vec3,
+    pub distance: fxx,
+    /// weights of the three triangle corners at `point`
+    pub barycentric: Vec3,
+}
+
+#[derive(Debug, Clone)]
+struct BvhNode {
+    bounds: Range3,
+    /// index of the first child, or of the first triangle in `order` if this is a leaf
+    first: usize,
+    /// number of triangles, or zero if this node has two children at `first` and `first + 1`
+    count: usize,
+}
+
+/// A bounding volume hierarchy over the triangles of a mesh.
+///
+/// The triangles are copied, so the hierarchy stays valid when the mesh changes.
+/// Rebuild it after editing the mesh to get results matching the new geometry.
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    triangles: Vec<[Vec3; 3]>,
+    nodes: Vec<BvhNode>,
+    order: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(mesh: &Mesh) -> Self {
+        let triangles = mesh
+            .iter_triangle_verts()
+            .map(|(a, b, c)| [a, b, c])
+            .collect::<Vec<_>>();
+        let bounds = triangles
+            .iter()
+            .map(|t| Range3::from_points(*t))
+            .collect::<Vec<_>>();
+        let centers = triangles
+            .iter()
+            .map(|[a, b, c]| (*a + *b + *c) / 3.0)
+            .collect::<Vec<_>>();
+
+        let mut bvh = Self {
+            triangles,
+            nodes: Vec::new(),
+            order: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            bvh.nodes.push(BvhNode {
+                bounds: Range3::from_points([]),
+                first: 0,
+                count: bounds.len(),
+            });
+            bvh.subdivide(0, &bounds, &centers);
+        }
+        bvh
+    }
+
+    /// Fit node `n` around its triangles, and split it at the median of the longest axis of their centers
+    fn subdivide(&mut self, n: usize, bounds: &[Range3], centers: &[Vec3]) {
+        let (first, count) = (self.nodes[n].first, self.nodes[n].count);
+        let ids = &mut self.order[first..first + count];
+        self.nodes[n].bounds = ids
+            .iter()
+            .fold(Range3::from_points([]), |range, i| range.union(&bounds[*i]));
+        if count <= LEAF_SIZE {
+            return;
+        }
+
+        let spread = Range3::from_points(ids.iter().map(|i| centers[*i])).size();
+        let axis = match spread.max_element() {
+            m if m == spread.x => 0,
+            m if m == spread.y => 1,
+            _ => 2,
+        };
+        let half = count / 2;
+        ids.select_nth_unstable_by(half, |a, b| centers[*a][axis].total_cmp(&centers[*b][axis]));
+
+        let left = self.nodes.len();
+        for (first, count) in [(first, half), (first + half, count - half)] {
+            self.nodes.push(BvhNode {
+                bounds: Range3::from_points([]),
+                first,
+                count,
+            });
+        }
+        self.nodes[n].first = left;
+        self.nodes[n].count = 0;
+        self.subdivide(left, bounds, centers);
+        self.subdivide(left + 1, bounds, centers);
+    }
+
+    /// The box around all triangles, or `None` for an empty mesh
+    pub fn bounds(&self) -> Option<&Range3> {
+        self.nodes.first().map(|node| &node.bounds)
+    }
+
+    /// Node ids to start a traversal with
+    fn root(&self) -> Vec<usize> {
+        (0..self.nodes.len().min(1)).collect()
+    }
+
+    pub fn triangle(&self, i: usize) -> [Vec3; 3] {
+        self.triangles[i]
+    }
+
+    /// The first triangle hit by the ray, in front of its origin. Both sides of a triangle can be hit
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        let inverse = ray.normal.recip();
+        let mut best: Option<RayHit> = None;
+        let mut stack = self.root();
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            let limit = best.map_or(fxx::INFINITY, |hit| hit.t);
+            if !ray_hits_box(ray, inverse, &node.bounds, limit) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+                continue;
+            }
+            for i in self.order[node.first..node.first + node.count].iter() {
+                match ray_triangle(ray, &self.triangles[*i], *i) {
+                    Some(hit) if hit.t < limit && best.map_or(true, |best| hit.t < best.t) => {
+                        best = Some(hit)
+                    }
+                    _ => (),
+                }
+            }
+        }
+        best
+    }
+
+    /// All triangles hit by the ray in front of its origin, sorted from near to far
+    pub fn raycast_all(&self, ray: &Ray) -> Vec<RayHit> {
+        let inverse = ray.normal.recip();
+        let mut hits = Vec::new();
+        let mut stack = self.root();
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if !ray_hits_box(ray, inverse, &node.bounds, fxx::INFINITY) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+                continue;
+            }
+            hits.extend(
+                self.order[node.first..node.first + node.count]
+                    .iter()
+                    .filter_map(|i| ray_triangle(ray, &self.triangles[*i], *i)),
+            );
+        }
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t).then(a.triangle.cmp(&b.triangle)));
+        hits
+    }
+
+    /// The closest point on any triangle. Returns `None` for an empty mesh
+    pub fn closest_point(&self, point: Vec3) -> Option<ClosestPoint> {
+        let mut best: Option<ClosestPoint> = None;
+        let mut best_squared = fxx::INFINITY;
+        let mut stack = self.root();
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if node.bounds.closest_point(point).distance_squared(point) > best_squared {
+                continue;
+            }
+            if node.count == 0 {
+                // visit the nearest child first, so the other one is more likely to be pruned
+                let (a, b) = (node.first, node.first + 1);
+                let distance = |c: usize| {
+                    let bounds = &self.nodes[c].bounds;
+                    bounds.closest_point(point).distance_squared(point)
+                };
+                match distance(a) < distance(b) {
+                    true => stack.extend([b, a]),
+                    false => stack.extend([a, b]),
+                }
+                continue;
+            }
+            for i in self.order[node.first..node.first + node.count].iter() {
+                let [a, b, c] = self.triangles[*i];
+                let barycentric = closest_barycentric(point, a, b, c);
+                let on_triangle = a * barycentric.x + b * barycentric.y + c * barycentric.z;
+                let squared = on_triangle.distance_squared(point);
+                if squared < best_squared {
+                    best_squared = squared;
+                    best = Some(ClosestPoint {
+                        triangle: *i,
+                        point: on_triangle,
+                        distance: squared.sqrt(),
+                        barycentric,
+                    });
+                }
+            }
+        }
+        best
+    }
+
+    /// Indices of all triangles touching or within the box, in ascending order
+    pub fn overlap_box(&self, range: &Range3) -> Vec<usize> {
+        let mut found = self.collect(
+            |bounds| bounds.overlaps(range),
+            |t| triangle_overlaps_box(t, range),
+        );
+        found.sort();
+        found
+    }
+
+    /// Indices of all triangles touching or within the sphere, in ascending order
+    pub fn overlap_sphere(&self, center: Vec3, radius: fxx) -> Vec<usize> {
+        let squared = radius * radius;
+        let mut found = self.collect(
+            |bounds| bounds.closest_point(center).distance_squared(center) <= squared,
+            |[a, b, c]| {
+                let barycentric = closest_barycentric(center, *a, *b, *c);
+                let closest = *a * barycentric.x + *b * barycentric.y + *c * barycentric.z;
+                closest.distance_squared(center) <= squared
+            },
+        );
+        found.sort();
+        found
+    }
+
+    /// Triangles accepted by `test`, within nodes accepted by `visit`
+    fn collect(
+        &self,
+        visit: impl Fn(&Range3) -> bool,
+        test: impl Fn(&[Vec3; 3]) -> bool,
+    ) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = self.root();
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if !visit(&node.bounds) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+                continue;
+            }
+            found.extend(
+                self.order[node.first..node.first + node.count]
+                    .iter()
+                    .filter(|i| test(&self.triangles[**i])),
+            );
+        }
+        found
+    }
+}
+
+/// slab test, for boxes in front of the ray, and not beyond `limit`
+fn ray_hits_box(ray: &Ray, inverse: Vec3, bounds: &Range3, limit: fxx) -> bool {
+    let t0 = (bounds.min() - ray.origin) * inverse;
+    let t1 = (bounds.max() - ray.origin) * inverse;
+    // NaN occurs when the ray lies exactly on a slab plane. `max` and `min` then ignore that axis
+    let near = t0.min(t1).max_element().max(0.0);
+    let far = t0.max(t1).min_element().min(limit);
+    near <= far
+}
+
+/// Möller-Trumbore, hitting both sides
+fn ray_triangle(ray: &Ray, [a, b, c]: &[Vec3; 3], triangle: usize) -> Option<RayHit> {
+    let (ab, ac) = (*b - *a, *c - *a);
+    let p = ray.normal.cross(ac);
+    let det = ab.dot(p);
+    if det.abs() < EPSILON {
+        return None;
+    }
+    let inverse = 1.0 / det;
+    let s = ray.origin - *a;
+    let u = s.dot(p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(ab);
+    let v = ray.normal.dot(q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(q) * inverse;
+    (t >= 0.0).then_some(RayHit {
+        triangle,
+        t,
+        barycentric: Vec3::new(1.0 - u - v, u, v),
+    })
+}
+
+/// Barycentric weights of the point on triangle `abc` closest to `p`, by checking its voronoi regions.
+/// From Real-Time Collision Detection, Christer Ericson
+fn closest_barycentric(p: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return Vec3::X;
+    }
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return Vec3::Y;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return Vec3::new(1.0 - v, v, 0.0);
+    }
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return Vec3::Z;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return Vec3::new(1.0 - w, 0.0, w);
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return Vec3::new(0.0, 1.0 - w, w);
+    }
+    let denom = va + vb + vc;
+    if denom.abs() < EPSILON {
+        // degenerate triangle, fall back to the nearest corner
+        let corners = [a, b, c].map(|corner| corner.distance_squared(p));
+        return match corners {
+            [a, b, c] if a <= b && a <= c => Vec3::X,
+            [_, b, c] if b <= c => Vec3::Y,
+            _ => Vec3::Z,
+        };
+    }
+    let (v, w) = (vb / denom, vc / denom);
+    Vec3::new(1.0 - v - w, v, w)
+}
+
+/// Separating axis test between a triangle and a box.
+/// From Fast 3D Triangle-Box Overlap Testing, Tomas Akenine-Möller
+fn triangle_overlaps_box(triangle: &[Vec3; 3], range: &Range3) -> bool {
+    let center = range.center();
+    let half = range.size() * 0.5;
+    let v = triangle.map(|v| v - center);
+    let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+
+    let separates = |axis: Vec3| {
+        let radius = half.dot(axis.abs());
+        let projected = v.map(|v| v.dot(axis));
+        let (min, max) = (
+            projected[0].min(projected[1]).min(projected[2]),
+            projected[0].max(projected[1]).max(projected[2]),
+        );
+        min > radius || max < -radius
+    };
+
+    // the box normals, the triangle normal, and all cross products of edges
+    let mut axes = vec![Vec3::X, Vec3::Y, Vec3::Z, edges[0].cross(edges[1])];
+    for edge in edges.iter() {
+        axes.extend([Vec3::X, Vec3::Y, Vec3::Z].map(|a| a.cross(*edge)));
+    }
+    !axes.into_iter().any(separates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bvh;
+    use crate::core::Geometry;
+    use crate::kernel::{fxx, vec3, Vec3};
+    use crate::lines::Ray;
+    use crate::math::Range3;
+    use crate::solid::test_fixtures::assert_close;
+    use crate::solid::Mesh;
+
+    /// a row of unit cubes along x, one every 2 units
+    fn cubes(count: usize) -> Mesh {
+        Mesh::from_join(
+            (0..count)
+                .map(|i| Mesh::new_cube(0.5).mv(vec3(i as fxx * 2.0, 0.0, 0.0)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_raycast() {
+        let mesh = cubes(20);
+        let bvh = Bvh::new(&mesh);
+
+        let ray = Ray::new(vec3(-5.0, 0.1, 0.2), Vec3::X);
+        let hit = bvh.raycast(&ray).unwrap();
+        assert_close(hit.t, 4.5);
+
+        // the barycentrics point at the hit
+        let [a, b, c] = bvh.triangle(hit.triangle);
+        let point = a * hit.barycentric.x + b * hit.barycentric.y + c * hit.barycentric.z;
+        assert!(point.distance(ray.at(hit.t)) < 1e-4);
+        assert_close(
+            hit.barycentric.x + hit.barycentric.y + hit.barycentric.z,
+            1.0,
+        );
+
+        // every cube is entered and left
+        let hits = bvh.raycast_all(&ray);
+        assert_eq!(hits.len(), 40);
+        assert!(hits.windows(2).all(|w| w[0].t <= w[1].t));
+        assert_eq!(hits[0], hit);
+
+        // from within a cube, only what is in front counts
+        let hits = bvh.raycast_all(&Ray::new(vec3(2.0, 0.1, 0.2), Vec3::X));
+        assert_eq!(hits.len(), 2 * 18 + 1);
+        assert_close(hits[0].t, 0.5);
+
+        // misses
+        assert!(bvh
+            .raycast(&Ray::new(vec3(-5.0, 0.1, 0.2), -Vec3::X))
+            .is_none());
+        assert!(bvh
+            .raycast(&Ray::new(vec3(1.0, 0.0, -5.0), Vec3::Z))
+            .is_none());
+
+        // matches a brute force check, for rays in all sorts of directions
+        for i in 0..50 {
+            let f = i as fxx;
+            let normal = vec3(f.sin(), (f * 0.7).cos(), (f * 1.3).sin() * 0.1).normalize();
+            let ray = Ray::new(vec3(f * 0.8 - 2.0, -3.0 * normal.y, 0.1), normal);
+            let brute = (0..mesh.tri.len() / 3)
+                .filter_map(|t| super::ray_triangle(&ray, &bvh.triangle(t), t))
+                .count();
+            assert_eq!(bvh.raycast_all(&ray).len(), brute);
+        }
+    }
+
+    #[test]
+    fn test_closest_point() {
+        let bvh = Bvh::new(&cubes(10));
+
+        let closest = bvh.closest_point(vec3(6.2, 3.0, 0.1)).unwrap();
+        assert!(closest.point.distance(vec3(6.2, 0.5, 0.1)) < 1e-4);
+        assert_close(closest.distance, 2.5);
+
+        // corners and insides
+        let closest = bvh.closest_point(vec3(-2.0, -2.0, -2.0)).unwrap();
+        assert!(closest.point.distance(Vec3::splat(-0.5)) < 1e-4);
+        let closest = bvh.closest_point(vec3(4.1, 0.0, 0.0)).unwrap();
+        assert_close(closest.distance, 0.4);
+
+        let empty = Bvh::new(&Mesh::default());
+        assert!(empty.bounds().is_none());
+        assert!(empty.closest_point(Vec3::ZERO).is_none());
+        assert!(empty.raycast(&Ray::new(Vec3::ZERO, Vec3::X)).is_none());
+    }
+
+    #[test]
+    fn test_overlap() {
+        let mesh = cubes(10);
+        let bvh = Bvh::new(&mesh);
+        let per_cube = mesh.tri.len() / 3 / 10;
+
+        // a box between two cubes touches nothing
+        assert!(bvh
+            .overlap_box(&Range3::new(vec3(0.6, -1.0, -1.0), vec3(1.4, 1.0, 1.0)))
+            .is_empty());
+        // a box within a cube touches nothing either, it only looks at the surface
+        assert!(bvh
+            .overlap_box(&Range3::new(Vec3::splat(-0.1), Vec3::splat(0.1)))
+            .is_empty());
+
+        // a box overlapping the second and third cube
+        let found = bvh.overlap_box(&Range3::new(vec3(2.0, -1.0, -1.0), vec3(4.0, 1.0, 1.0)));
+        assert!(found.contains(&per_cube) && found.contains(&(per_cube * 2)));
+        assert!(found.iter().all(|t| (per_cube..per_cube * 3).contains(t)));
+
+        // a sphere touching the +x side of the first cube
+        let found = bvh.overlap_sphere(vec3(0.9, 0.0, 0.0), 0.5);
+        assert!(!found.is_empty());
+        assert!(found.iter().all(|t| {
+            let [a, b, c] = bvh.triangle(*t);
+            [a, b, c].iter().all(|v| v.x > 0.49 && v.x < 0.51)
+        }));
+        assert!(bvh.overlap_sphere(vec3(1.0, 0.0, 0.0), 0.4).is_empty());
+    }
+}
@@ -1,7 +1,8 @@
 #![allow(unused_variables, dead_code)]
 
-use super::{quad_to_tri, Octoid, Polyhedron, CUBE_FACES};
-use crate::kernel::{fxx, kernel, vec2, vec3, Vec2, Vec3};
+use super::{quad_to_tri, Attribute, Attributes, Octoid, Polyhedron, CUBE_FACES};
+use crate::io::{write_obj_mesh, MaterialDesc, ObjScene};
+use crate::kernel::{fxx, kernel, vec2, vec3, Vec2, Vec3, Vec4};
 use crate::{prelude::*, util};
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
@@ -17,6 +18,14 @@ pub enum Normals {
     Face(Vec<Vec3>),
 }
 
+/// A point where a plane crosses a mesh: an existing vertex on the plane,
+/// or a point on the edge between vertex `a` and `b` (with `a < b`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SectionPoint {
+    Vert(usize),
+    Edge(usize, usize),
+}
+
 /// A dead simple, internal data structure to store meshes.
 /// Can get confusing in conjunction with bevy's mesh
 #[derive(Debug, Clone)]
@@ -25,6 +34,10 @@ pub struct Mesh {
     pub tri: Vec<usize>, // TODO Index Enum
     pub uvs: Vec<Vec2>,
     pub normals: Normals,
+    /// named per-vertex data, like colors
+    pub vert_attributes: Attributes,
+    /// named per-triangle data, like material ids
+    pub tri_attributes: Attributes,
 }
 
 impl Default for Mesh {
@@ -34,6 +47,8 @@ impl Default for Mesh {
             tri: Default::default(),
             uvs: Default::default(),
             normals: Normals::None,
+            vert_attributes: Attributes::new(),
+            tri_attributes: Attributes::new(),
         }
     }
 }
@@ -45,6 +60,8 @@ impl Mesh {
             tri,
             uvs,
             normals,
+            vert_attributes: Attributes::new(),
+            tri_attributes: Attributes::new(),
         }
     }
 
@@ -67,6 +84,27 @@ impl Mesh {
         self
     }
 
+    pub fn with_vert_attribute(mut self, name: &str, attribute: Attribute) -> Self {
+        self.vert_attributes.insert(name, attribute);
+        self
+    }
+
+    pub fn with_tri_attribute(mut self, name: &str, attribute: Attribute) -> Self {
+        self.tri_attributes.insert(name, attribute);
+        self
+    }
+
+    /// An index into the materials of every triangle, stored as [`Attributes::MATERIAL`].
+    /// See [`crate::io::ObjScene`]
+    pub fn with_material_ids(self, ids: Vec<usize>) -> Self {
+        self.with_tri_attribute(Attributes::MATERIAL, Attribute::Index(ids))
+    }
+
+    /// rgba, in the 0..1 range
+    pub fn with_vert_colors(self, colors: Vec<Vec4>) -> Self {
+        self.with_vert_attribute(Attributes::COLOR, Attribute::Vec4(colors))
+    }
+
     /// bevy workaround
     pub fn with_dummy_normals(mut self) -> Self {
         self.normals = Normals::Vertex(self.verts.clone());
@@ -166,10 +204,28 @@ impl Mesh {
     //     edges
     // }
 
-    /// an edge is naked if the same sequence cannot be found
+    // an edge is naked if the same sequence cannot be found
 
+    /// Give every triangle corner its own vertex, so no vertex is shared between triangles.
+    /// Uvs, vertex normals and vertex attributes are copied to every corner
     pub fn linearize(self) -> Self {
-        todo!();
+        let count = self.verts.len();
+        let verts = self.tri.iter().map(|i| self.verts[*i]).collect();
+        let uvs = match self.uvs.len() == count {
+            true => self.tri.iter().map(|i| self.uvs[*i]).collect(),
+            false => Vec::new(),
+        };
+        let normals = match self.normals {
+            Normals::Vertex(normals) if normals.len() == count => {
+                Normals::Vertex(self.tri.iter().map(|i| normals[*i]).collect())
+            }
+            Normals::Vertex(_) => Normals::None,
+            normals => normals,
+        };
+        let mut mesh = Self::new(verts, (0..self.tri.len()).collect(), uvs, normals);
+        mesh.vert_attributes = self.vert_attributes.select(count, &self.tri);
+        mesh.tri_attributes = self.tri_attributes;
+        mesh
     }
 
     pub fn get_normals(&self) -> Option<&Vec<Vec3>> {
@@ -187,7 +243,13 @@ impl Mesh {
     }
 
     pub fn to_uniform(&self) -> Self {
-        let desouped = TriMesh::desoupify(&self.verts, 0.001);
+        self.to_uniform_tol(0.001)
+    }
+
+    /// weld all vertices closer than `tolerance` to each other into one.
+    /// Vertex attributes are taken from the same vertex as the welded position, triangle attributes are kept as they are
+    pub fn to_uniform_tol(&self, tolerance: fxx) -> Self {
+        let desouped = TriMesh::desoupify_hashed(&self.verts, tolerance);
 
         // let mut uvs = Vec::new();
         let mut verts = Vec::new();
@@ -199,20 +261,27 @@ impl Mesh {
         // for all others, id must be replaced with this i
         let mut map = HashMap::new();
         let mut new_vert_index = 0;
-        for (i, id) in desouped.iter().enumerate() {
+        let mut firsts = Vec::new();
+        for id in desouped.iter() {
             if let Some(index) = map.get(id) {
                 ids.push(*index);
             } else {
                 map.insert(id, new_vert_index);
                 ids.push(new_vert_index);
                 verts.push(self.verts[*id]);
+                firsts.push(*id);
                 // uvs.push(self.uvs.get(i).unwrap().clone());
                 // normals.push(self.normals.get(i).unwrap().clone());
                 new_vert_index += 1;
             }
         }
 
-        Self::new(verts, ids, Vec::new(), Normals::None)
+        // `ids` maps every old vertex to a new one
+        let tri = self.tri.iter().map(|i| ids[*i]).collect();
+        let mut mesh = Self::new(verts, tri, Vec::new(), Normals::None);
+        mesh.vert_attributes = self.vert_attributes.select(self.verts.len(), &firsts);
+        mesh.tri_attributes = self.tri_attributes.clone();
+        mesh
     }
 }
 
@@ -361,6 +430,46 @@ impl Mesh {
         mesh
     }
 
+    /// A `TriMesh` can point to different vertices, uvs and normals per corner.
+    /// Every unique combination of the three becomes a vertex of the new mesh.
+    pub fn from_tri_mesh(tri_mesh: TriMesh) -> Mesh {
+        let has_uvs = matches!(tri_mesh.uvs, OneOrMany::Many(_));
+        let has_normals = matches!(tri_mesh.normals, OneOrMany::Many(_));
+
+        let mut mesh = Mesh::default();
+        let mut normals = Vec::new();
+        let mut map = HashMap::new();
+        for (a, b, c) in tri_mesh.iter_triangles_hetero() {
+            for corner in [a, b, c] {
+                let key = (
+                    corner.v,
+                    if has_uvs { corner.uv } else { 0 },
+                    if has_normals { corner.n } else { 0 },
+                );
+                if let Some(id) = map.get(&key) {
+                    mesh.tri.push(*id);
+                    continue;
+                }
+                let id = mesh.verts.len();
+                map.insert(key, id);
+                mesh.tri.push(id);
+                mesh.verts.push(tri_mesh.verts[corner.v]);
+                if has_uvs {
+                    mesh.uvs
+                        .push(*tri_mesh.uvs.get(corner.uv).unwrap_or(&Vec2::ZERO));
+                }
+                if has_normals {
+                    normals.push(*tri_mesh.normals.get(corner.n).unwrap_or(&Vec3::ZERO));
+                }
+            }
+        }
+
+        if has_normals {
+            mesh.normals = Normals::Vertex(normals);
+        }
+        mesh
+    }
+
     pub fn new_triangle(verts: [Vec3; 3]) -> Self {
         Triangle::new(verts[0], verts[1], verts[2]).into()
     }
@@ -820,7 +929,8 @@ impl Mesh {
         mesh
     }
 
-    // simple join, not taking common verts into account
+    // simple join, not taking common verts into account.
+    // Attribute channels missing in some of the meshes are padded, see `Attribute::resize`
     pub fn from_join(meshes: Vec<Mesh>) -> Mesh {
         let mut mesh = Mesh::default();
 
@@ -841,6 +951,13 @@ impl Mesh {
         let mut vertcount = 0;
         for mut other in meshes {
             let length = other.verts.len();
+            mesh.vert_attributes
+                .append(vertcount, &other.vert_attributes, length);
+            mesh.tri_attributes.append(
+                mesh.count_triangles(),
+                &other.tri_attributes,
+                other.count_triangles(),
+            );
             mesh.verts.append(&mut other.verts);
             mesh.uvs.append(&mut other.uvs);
             mesh.append_normals(&mut other.normals);
@@ -931,13 +1048,6 @@ impl Mesh {
         LineList::new(lines)
     }
 
-    pub fn to_clean(&self) -> Mesh {
-        // TODO: identify all vertices which are not references by any triangle,
-        // exclude them
-        // then update the triangle vertex pointers.
-        todo!();
-    }
-
     pub fn write_obj(&self, path: &str) -> Result<(), std::io::Error> {
         let obj = self.gen_obj_buffer("obj generated by Hedron", None, None)?;
         let mut obj_file = std::fs::File::create(path)?;
@@ -945,6 +1055,8 @@ impl Mesh {
         Ok(())
     }
 
+    /// Write this mesh with a single textured material.
+    /// Use [`crate::io::ObjScene`] for several meshes or materials
     pub fn write_obj_mtl(
         &self,
         path: &str,
@@ -952,22 +1064,12 @@ impl Mesh {
         name_mtl: &str,
         name_texture: &str,
     ) -> Result<(), std::io::Error> {
-        let mat_name = "Material";
-
         // both the texture and mtl should be in the same folder
-        let mtl = Mesh::gen_mtl_buffer("mtl generated by Hedron", mat_name, Some(name_texture))?;
-        let obj = self.gen_obj_buffer("obj generated by Hedron", Some(mat_name), Some(name_mtl))?;
-
-        let texture_path = path.to_owned() + name_texture;
-        let mtl_path = path.to_owned() + name_mtl;
-        let obj_path = path.to_owned() + name_obj;
-
-        let mut obj_file = std::fs::File::create(obj_path)?;
-        obj_file.write_all(&obj)?;
-        let mut mtl_path = std::fs::File::create(mtl_path)?;
-        mtl_path.write_all(&mtl)?;
-
-        Ok(())
+        let material = MaterialDesc::new("Material").with_diffuse_map(name_texture);
+        ObjScene::new()
+            .with_mesh("Mesh", self.clone())
+            .with_material(material)
+            .write_obj_mtl(path, name_obj, name_mtl)
     }
 
     pub fn gen_mtl_buffer(
@@ -975,23 +1077,14 @@ impl Mesh {
         mat_name: &str,
         texture_path: Option<&str>,
     ) -> Result<Vec<u8>, std::io::Error> {
-        let mut mtl = Vec::new();
-        writeln!(&mut mtl, "# {}", header)?;
-        writeln!(&mut mtl, "newmtl {}", mat_name)?;
-        writeln!(&mut mtl, "Ns 250.000000")?;
-        writeln!(&mut mtl, "Ka 1.000000 1.000000 1.000000")?;
-        writeln!(&mut mtl, "Kd 0.000000 0.000000 0.000000")?;
-        writeln!(&mut mtl, "Ks 0.000000 0.000000 0.000000")?;
-        writeln!(&mut mtl, "Ke 0.000000 0.000000 0.000000")?;
-        writeln!(&mut mtl, "Ni 1.450000")?;
-        writeln!(&mut mtl, "d 1.000000")?;
-        writeln!(&mut mtl, "illum 2")?;
+        let mut material = MaterialDesc::new(mat_name);
         if let Some(path) = texture_path {
-            writeln!(&mut mtl, "map_Ka {}", texture_path.unwrap())?;
-            writeln!(&mut mtl, "map_Kd {}", texture_path.unwrap())?;
-            writeln!(&mut mtl, "map_Ks {}", texture_path.unwrap())?;
+            material = material.with_diffuse_map(path);
         }
-        Ok(mtl)
+        let mtl = ObjScene::new()
+            .with_material(material)
+            .gen_mtl_string(header);
+        Ok(mtl.into_bytes())
     }
 
     pub fn gen_obj_buffer(
@@ -1001,32 +1094,18 @@ impl Mesh {
         mtl_path: Option<&str>,
     ) -> Result<Vec<u8>, std::io::Error> {
         let mut obj = Vec::new();
-        let o = &mut obj;
-
-        writeln!(o, "# {}", header)?;
-
-        if mtl_path.is_some() && mat_name.is_some() {
-            writeln!(o, "mtllib {}", mtl_path.unwrap())?;
-            writeln!(o, "usemtl {}", mat_name.unwrap())?;
-        }
-        for vert in self.verts.iter() {
-            writeln!(o, "v {} {} {}", vert.x, vert.y, vert.z)?;
-        }
-        for uv in self.uvs.iter() {
-            writeln!(o, "vt {} {}", uv.x, uv.y)?;
-        }
+        writeln!(obj, "# {}", header)?;
 
-        if self.uvs.len() == self.verts.len() {
-            for (a, b, c) in self.iter_triangles() {
-                let (a, b, c) = (a + 1, b + 1, c + 1);
-                writeln!(o, "f {a}/{a} {b}/{b} {c}/{c}")?;
-            }
-        } else {
-            for (a, b, c) in self.iter_triangles() {
-                let (a, b, c) = (a + 1, b + 1, c + 1);
-                writeln!(o, "f {a} {b} {c}")?;
+        let materials = match (mtl_path, mat_name) {
+            (Some(mtl_path), Some(mat_name)) => {
+                writeln!(obj, "mtllib {}", mtl_path)?;
+                vec![mat_name]
             }
-        }
+            _ => Vec::new(),
+        };
+        let mut body = String::new();
+        write_obj_mesh(&mut body, self, &materials, [0; 3]);
+        obj.extend_from_slice(body.as_bytes());
         Ok(obj)
     }
 
@@ -1063,15 +1142,29 @@ impl Mesh {
 
         // NOTE: a naive polygon triangulation puts an additional point in the middle
 
-        // fill the two rings of vertices with triangles
-        for (i, j) in base.iter_naked_edges() {
+        // fill the two rings of vertices with triangles. These take the triangle attributes of the base triangle they start at
+        let mut sources = Vec::new();
+        for ((i, j), (t, other)) in base.get_neighborized_edges() {
+            if other.is_some() {
+                continue;
+            }
             mesh.tri.append(&mut vec![i, j, j + offset]);
             mesh.tri.append(&mut vec![j + offset, i + offset, i]);
+            sources.extend([t, t]);
         }
+        let sides = base.tri_attributes.select(base.count_triangles(), &sources);
+        let count = mesh.count_triangles() - sources.len();
+        mesh.tri_attributes.append(count, &sides, sources.len());
 
         mesh
     }
 
+    /// The convex hull of all vertices, as a closed mesh with outward facing triangles.
+    /// Returns `None` if the mesh is flat. See [`Vectors::convex_hull`].
+    pub fn convex_hull(&self) -> Option<Mesh> {
+        Vectors::new(self.verts.clone()).convex_hull()
+    }
+
     /// assumes all curves are of the same length!!!
     #[rustfmt::skip]
     pub fn loft(mut curves: Vec<Vec<Vec3>>) -> Mesh {
@@ -1109,6 +1202,11 @@ impl Mesh {
     /// return two linear meshes (we can't maintain the triangle index pointers during splitting.
     /// or we can, but it would still require re-formatting the meshes after the procedure.
     /// This way, we do the reverse: After the operation, the meshes can be de-linearized if desired.
+    /// The halves keep their attributes, but drop their uvs and normals. Every naked loop is patched on its own,
+    /// so nested section loops are filled over instead of becoming holes.
+    ///
+    /// For the capping mode, see [`Mesh::split_capped`]: it returns the halves in the same order,
+    /// closes them with caps triangulated around nested loops, and keeps uvs and normals, giving the caps planar uvs.
     #[rustfmt::skip]
     pub fn split(&self, cutting_plane: impl Into<Plane>) -> (Mesh, Mesh) {
 
@@ -1122,9 +1220,13 @@ impl Mesh {
         let mut left = Mesh::default();
         let mut right = Mesh::default();
 
+        // the original triangle of every new triangle
+        let mut left_faces = Vec::new();
+        let mut right_faces = Vec::new();
+
         // To make the cutting plane actually cut individual triangles, 
         // We must do special shit, depending on if any one vertex is on one or the other side of the cutting plane. 
-        for (a,b,c) in self.iter_triangle_verts() {
+        for (face, (a,b,c)) in self.iter_triangle_verts().enumerate() {
 
             let tabc = &[a,b,c].iter().map(|p| plane.half_plane_test_tol(*p, 0.001)).map(|ord| match ord {
                 Ordering::Less => Side::Left,
@@ -1135,6 +1237,7 @@ impl Mesh {
             let [ta, tb, tc] = tabc else {
                 continue;
             };
+            let (left_count, right_count) = (left.verts.len(), right.verts.len());
 
             // take special care to keep the ordering cyclicly alphabetical, if you get what I mean
             // otherwise, newly added triangles will become flipped 
@@ -1159,6 +1262,8 @@ impl Mesh {
                 (Side::Left, Side::Right, Side::OnTop) => perfect_split(&plane, &mut left, &mut right, a, b, c),
                 (Side::Right, Side::Left, Side::OnTop) => perfect_split(&plane, &mut right, &mut left, a, b, c),
             }
+            left_faces.extend(std::iter::repeat(face).take((left.verts.len() - left_count) / 3));
+            right_faces.extend(std::iter::repeat(face).take((right.verts.len() - right_count) / 3));
 
             // in case of an asymetrical split, split like this
             fn asym_split(plane: &Plane, maj_side: &mut Mesh, min_side: &mut Mesh, maj1: Vec3, maj2: Vec3, min: Vec3) {
@@ -1185,12 +1290,14 @@ impl Mesh {
 
         // cleanup
         left.tri = (0..left.verts.len()).collect::<Vec<usize>>();
+        self.carry_split_attributes(&mut left, &left_faces);
         
         let mut left = left.to_uniform();
         left.clean_triangles();
         left.cap_holes_with_normal(normal * -1.0);
         
         right.tri = (0..right.verts.len()).collect::<Vec<usize>>();
+        self.carry_split_attributes(&mut right, &right_faces);
         let mut right = right.to_uniform();
         right.clean_triangles();
         right.cap_holes_with_normal(normal);
@@ -1198,15 +1305,273 @@ impl Mesh {
         (left, right)
     }
 
-    /// Intersect, do not add vertices. Just return the intersection points as polylines
+    /// Give a linear piece of this mesh the attributes of the triangles it was cut from.
+    /// Every vertex lies on an edge of its original triangle, and interpolates the vertex attributes along that edge
+    fn carry_split_attributes(&self, linear: &mut Mesh, faces: &[usize]) {
+        let count = self.verts.len();
+        linear.tri_attributes = self.tri_attributes.select(self.count_triangles(), faces);
+        if self.vert_attributes.is_empty() {
+            return;
+        }
+        let triangles = self.iter_triangles().collect::<Vec<_>>();
+        let mut attributes = self
+            .vert_attributes
+            .select(count, &(0..count).collect::<Vec<_>>());
+        for (i, vert) in linear.verts.iter().enumerate() {
+            let (a, b, c) = triangles[faces[i / 3]];
+            let (from, to, t) = [(a, b), (b, c), (c, a)]
+                .into_iter()
+                .map(|(from, to)| {
+                    let segment = self.verts[to] - self.verts[from];
+                    let t = (*vert - self.verts[from]).dot(segment)
+                        / segment.length_squared().max(fxx::EPSILON);
+                    let t = t.clamp(0.0, 1.0);
+                    (
+                        from,
+                        to,
+                        t,
+                        vert.distance_squared(self.verts[from] + segment * t),
+                    )
+                })
+                .min_by(|x, y| x.3.total_cmp(&y.3))
+                .map(|(from, to, t, _)| (from, to, t))
+                .expect("a triangle has three edges");
+            attributes.push_lerp(from, to, t);
+        }
+        let added = (count..count + linear.verts.len()).collect::<Vec<_>>();
+        linear.vert_attributes = attributes.select(count + linear.verts.len(), &added);
+    }
+
+    /// Intersect, do not add vertices. Just return the intersection points as polylines.
+    ///
+    /// Sections are oriented counter clockwise around the plane normal, when seen from the outside of the mesh.
+    /// Sections through closed parts of the mesh become closed polylines, sections through open parts become open polylines.
     pub fn intersect(&self, plane: impl Into<Plane>) -> Vec<Polyline> {
-        Vec::new()
+        let plane = plane.into();
+        let Some((sides, crossings)) = self.pre_intersect_plane(&plane) else {
+            return Vec::new();
+        };
+        let position = |point: &SectionPoint| match point {
+            SectionPoint::Vert(v) => self.verts[*v],
+            SectionPoint::Edge(a, b) => crossings[&(*a, *b)].0,
+        };
+
+        self.section_chains(&plane, &sides, &crossings)
+            .into_iter()
+            .map(|(chain, closed)| {
+                let verts = chain.iter().map(position).collect();
+                if closed {
+                    Polyline::new_closed(verts)
+                } else {
+                    Polyline::new_open(verts)
+                }
+            })
+            .collect()
+    }
+
+    /// Intersect the mesh with multiple planes, like stacked slices for fabrication
+    pub fn intersect_many<P: Into<Plane>>(
+        &self,
+        planes: impl IntoIterator<Item = P>,
+    ) -> Vec<Vec<Polyline>> {
+        planes
+            .into_iter()
+            .map(|plane| self.intersect(plane))
+            .collect()
     }
 
-    /// intersect & add vertices
-    /// return aggregated loops of inlayed vertices
+    /// intersect & add vertices.
+    /// Every triangle crossing the plane is re-triangulated, so the plane section becomes a path of mesh edges.
+    /// Uvs, vertex normals, face normals and vertex attributes are interpolated for the new vertices.
+    /// See [`Mesh::plane_loop_cut_with_loops`] to also get the inlayed vertices.
     pub fn plane_loop_cut(self, plane: impl Into<Plane>) -> Self {
-        self
+        self.plane_loop_cut_with_loops(plane).0
+    }
+
+    /// Like [`Mesh::plane_loop_cut`], but also returns aggregated loops of inlayed vertices,
+    /// using the same convention as `aggregate_edges`: closed loops repeat their first vertex at the end.
+    pub fn plane_loop_cut_with_loops(self, plane: impl Into<Plane>) -> (Self, Vec<Vec<usize>>) {
+        let plane = plane.into();
+        let Some((sides, crossings)) = self.pre_intersect_plane(&plane) else {
+            return (self, Vec::new());
+        };
+        let chains = self.section_chains(&plane, &sides, &crossings);
+
+        let mut mesh = self.clone();
+        mesh.tri.clear();
+        let has_uvs = mesh.uvs.len() == mesh.verts.len();
+        let has_vert_normals =
+            matches!(&mesh.normals, Normals::Vertex(n) if n.len() == mesh.verts.len());
+        let face_normals = match &self.normals {
+            Normals::Face(normals) if normals.len() == self.count_triangles() => Some(normals),
+            _ => None,
+        };
+        if let (Some(_), Normals::Face(normals)) = (face_normals, &mut mesh.normals) {
+            normals.clear();
+        }
+        // channels out of sync with the vertices are dropped, so every channel can be interpolated
+        let count = self.verts.len();
+        mesh.vert_attributes = self
+            .vert_attributes
+            .select(count, &(0..count).collect::<Vec<_>>());
+
+        // insert the crossings, in triangle order, so the result is deterministic
+        let mut inserted = HashMap::new();
+        for (a, b, c) in self.iter_triangles() {
+            for (a, b) in [(a, b), (b, c), (c, a)] {
+                let key = (a.min(b), a.max(b));
+                let Some((x, _, _)) = crossings.get(&key) else {
+                    continue;
+                };
+                if inserted.contains_key(&key) {
+                    continue;
+                }
+                let (from, to) = key;
+                let t =
+                    (*x - self.verts[from]).length() / (self.verts[to] - self.verts[from]).length();
+                inserted.insert(key, mesh.verts.len());
+                mesh.verts.push(*x);
+                if has_uvs {
+                    mesh.uvs.push(mesh.uvs[from].lerp(mesh.uvs[to], t));
+                }
+                if let (true, Normals::Vertex(normals)) = (has_vert_normals, &mut mesh.normals) {
+                    normals.push(normals[from].lerp(normals[to], t).normalize_or_zero());
+                }
+                mesh.vert_attributes.push_lerp(from, to, t);
+            }
+        }
+
+        let crossing = |a: usize, b: usize| inserted.get(&(a.min(b), a.max(b))).copied();
+        let mut sources = Vec::new();
+        for (i, (a, b, c)) in self.iter_triangles().enumerate() {
+            let rotations = [(a, b, c), (b, c, a), (c, a, b)];
+            let mut tris = vec![[a, b, c]];
+
+            // one crossing: the vertex opposite to it is on the plane
+            if let Some((a, b, c, x)) = rotations.iter().find_map(|(a, b, c)| {
+                let x = crossing(*b, *c)?;
+                (crossing(*a, *b).is_none() && crossing(*c, *a).is_none())
+                    .then_some((*a, *b, *c, x))
+            }) {
+                tris = vec![[a, b, x], [a, x, c]];
+            }
+
+            // two crossings: `a` is on one side, `b` and `c` on the other
+            if let Some((a, b, c, x1, x2)) = rotations
+                .iter()
+                .find_map(|(a, b, c)| Some((*a, *b, *c, crossing(*a, *b)?, crossing(*c, *a)?)))
+            {
+                let v = &mesh.verts;
+                tris = if v[x1].distance(v[c]) < v[b].distance(v[x2]) {
+                    vec![[a, x1, x2], [x1, b, c], [x1, c, x2]]
+                } else {
+                    vec![[a, x1, x2], [x1, b, x2], [b, c, x2]]
+                };
+            }
+
+            for tri in tris {
+                mesh.tri.extend(tri);
+                sources.push(i);
+                if let (Some(face_normals), Normals::Face(normals)) =
+                    (face_normals, &mut mesh.normals)
+                {
+                    normals.push(face_normals[i]);
+                }
+            }
+        }
+        mesh.tri_attributes = self.tri_attributes.select(self.count_triangles(), &sources);
+
+        let loops = chains
+            .into_iter()
+            .map(|(chain, closed)| {
+                let mut ids = chain
+                    .iter()
+                    .map(|point| match point {
+                        SectionPoint::Vert(v) => *v,
+                        SectionPoint::Edge(a, b) => inserted[&(*a, *b)],
+                    })
+                    .collect::<Vec<_>>();
+                if closed {
+                    ids.push(ids[0]);
+                }
+                ids
+            })
+            .collect();
+
+        (mesh, loops)
+    }
+
+    /// The segments of the section between the mesh and the plane, chained together.
+    /// Returns every chain, and if it is closed or not
+    fn section_chains(
+        &self,
+        plane: &Plane,
+        sides: &[Side],
+        crossings: &HashMap<(usize, usize), (Vec3, usize, Option<usize>)>,
+    ) -> Vec<(Vec<SectionPoint>, bool)> {
+        let position = |point: &SectionPoint| match point {
+            SectionPoint::Vert(v) => self.verts[*v],
+            SectionPoint::Edge(a, b) => crossings[&(*a, *b)].0,
+        };
+
+        // every section point gets an id, so we can use `aggregate_edges`
+        let mut points = Vec::new();
+        let mut ids = HashMap::new();
+        let mut segments = Vec::new();
+        let mut existing = HashSet::new();
+        for (a, b, c) in self.iter_triangles() {
+            let mut hits = Vec::new();
+            for (u, v) in [(a, b), (b, c), (c, a)] {
+                if matches!(sides[u], Side::OnTop) {
+                    hits.push(SectionPoint::Vert(u));
+                }
+                if crossings.contains_key(&(u.min(v), u.max(v))) {
+                    hits.push(SectionPoint::Edge(u.min(v), u.max(v)));
+                }
+            }
+            // a triangle lying on the plane is represented by its neighbors
+            let [p, q] = hits[..] else {
+                continue;
+            };
+
+            // orient the segment counter clockwise around the plane normal
+            let normal = (self.verts[b] - self.verts[a]).cross(self.verts[c] - self.verts[a]);
+            let direction = plane.normal().cross(normal);
+            let (p, q) = if (position(&q) - position(&p)).dot(direction) < 0.0 {
+                (q, p)
+            } else {
+                (p, q)
+            };
+
+            let mut id = |point: SectionPoint| {
+                *ids.entry(point).or_insert_with(|| {
+                    points.push(point);
+                    points.len() - 1
+                })
+            };
+            let (p, q) = (id(p), id(q));
+            if existing.insert((p.min(q), p.max(q))) {
+                segments.push((p, q));
+            }
+        }
+
+        let directed = segments.iter().copied().collect::<HashSet<_>>();
+        Self::aggregate_edges(segments.into_iter())
+            .into_iter()
+            .map(|mut chain| {
+                let closed = chain.len() > 2 && chain.first() == chain.last();
+                if closed {
+                    chain.pop();
+                }
+                if chain.len() > 1 && !directed.contains(&(chain[0], chain[1])) {
+                    chain.reverse();
+                    if closed {
+                        chain.rotate_right(1);
+                    }
+                }
+                (chain.into_iter().map(|id| points[id]).collect(), closed)
+            })
+            .collect()
     }
 
     /// returns data about where the intersection "WOULD" take place, but don't intersect it yet.
@@ -1216,7 +1581,7 @@ impl Mesh {
     /// - hashmap[(usizelowest_vertex, usizehighest_vertex)] -> (Vec3, usize_triangle_left, usize_triangle_right)
     fn pre_intersect_plane(
         &self,
-        plane: Plane,
+        plane: &Plane,
     ) -> Option<(
         Vec<Side>,
         HashMap<(usize, usize), (Vec3, usize, Option<usize>)>,
@@ -1286,36 +1651,58 @@ impl Mesh {
     }
 
     /// join consequtive edges.
-    /// This algorithm essentially plays domino's
-    /// Its extremely simple and slow in its current form
-    /// not sure what the algorithm does to duplicates
+    /// This algorithm essentially plays domino's.
+    /// Closed loops are indicated by repeating the first index at the end.
+    /// Open chains are extended in both directions.
+    /// Where more than two edges meet, the first unused edge is followed.
     pub fn aggregate_edges(edges: impl Iterator<Item = (usize, usize)>) -> Vec<Vec<usize>> {
-        let mut edges = edges.into_iter().collect::<Vec<_>>();
+        let edges = edges.into_iter().collect::<Vec<_>>();
+        let mut used = vec![false; edges.len()];
+        let mut touching = HashMap::<usize, Vec<usize>>::new();
+        for (i, (a, b)) in edges.iter().enumerate() {
+            touching.entry(*a).or_default().push(i);
+            touching.entry(*b).or_default().push(i);
+        }
 
-        let mut loops = Vec::new();
-        while let Some(first_edge) = edges.pop() {
-            let mut this_loop = vec![first_edge.0];
-            let mut cursor = first_edge.1;
-            loop {
-                this_loop.push(cursor);
-                let Some(index) = edges.iter().position(|e| e.0 == cursor || e.1 == cursor) else {
-                    break;
-                };
+        // walk over an unused edge touching the cursor, and return the vertex at the other side
+        let mut walk = |cursor: usize, used: &mut Vec<bool>| {
+            let i = *touching.get_mut(&cursor)?.iter().find(|i| !used[**i])?;
+            used[i] = true;
+            let (a, b) = edges[i];
+            Some(if a == cursor { b } else { a })
+        };
 
-                let edge = edges.remove(index);
-                if cursor == edge.0 {
-                    cursor = edge.1;
-                } else {
-                    cursor = edge.0;
-                }
-                if cursor == first_edge.0 {
+        let mut loops = Vec::new();
+        for i in (0..edges.len()).rev() {
+            if used[i] {
+                continue;
+            }
+            used[i] = true;
+            let (first, second) = edges[i];
+            let mut this_loop = vec![first, second];
+            let mut cursor = second;
+            let mut closed = false;
+            while let Some(next) = walk(cursor, &mut used) {
+                this_loop.push(next);
+                cursor = next;
+                if cursor == first {
                     // found a circular loop!
-                    // indicate this by adding the first index again
-                    this_loop.push(first_edge.0);
+                    closed = true;
                     break;
                 }
             }
 
+            if !closed {
+                let mut cursor = first;
+                let mut start = Vec::new();
+                while let Some(next) = walk(cursor, &mut used) {
+                    start.push(next);
+                    cursor = next;
+                }
+                start.reverse();
+                start.append(&mut this_loop);
+                this_loop = start;
+            }
             loops.push(this_loop);
         }
         loops
@@ -1348,7 +1735,8 @@ impl Mesh {
             }
         }
 
-        for (a, b, c) in self.iter_triangles() {
+        let mut kept = Vec::new();
+        for (i, (a, b, c)) in self.iter_triangles().enumerate() {
 
             // check duplicate vertices 
             if a == b || b == c || c == a {
@@ -1363,7 +1751,9 @@ impl Mesh {
             }
             existing_tri.insert(hash);
             cleaned_tri.append(&mut vec![a,b,c]);
+            kept.push(i);
         }
+        self.tri_attributes = self.tri_attributes.select(self.count_triangles(), &kept);
         self.tri = cleaned_tri;
     }
 
@@ -1406,6 +1796,7 @@ impl Mesh {
             let mut real_ids = ids.iter().map(|id| edge_loop[*id]).collect::<Vec<_>>();
             self.tri.append(&mut real_ids);
         }
+        self.tri_attributes.resize(self.count_triangles());
     }
 
     /// cap holes between given edges
@@ -1434,9 +1825,11 @@ impl From<Triangle> for Mesh {
 
 #[cfg(test)]
 mod test {
-    use super::Mesh;
-    use crate::core::Geometry;
-    use crate::kernel::vec3;
+    use super::{Attribute, Mesh, Normals};
+    use crate::core::{Geometry, Plane};
+    use crate::kernel::{fxx, vec3, Vec3};
+    use crate::lines::Polyline;
+    use crate::util::iter_pairs;
 
     // #[test]
     // fn write_some_obj() {
@@ -1487,5 +1880,163 @@ mod test {
             Mesh::aggregate_edges(edges.into_iter()),
             vec![vec![3, 1, 4, 2, 3], vec![5, 6]]
         );
+
+        // open chains are followed in both directions
+        let edges = vec![(1, 2), (3, 4), (2, 3)];
+        assert_eq!(
+            Mesh::aggregate_edges(edges.into_iter()),
+            vec![vec![1, 2, 3, 4]]
+        );
+    }
+
+    #[test]
+    fn test_intersect() {
+        let plane = Plane::from_pos_normal(vec3(0.0, 0.0, 0.1), Vec3::Z);
+        let ico = Mesh::new_icosahedron(1.0);
+        let sections = ico.intersect(plane.clone());
+        assert_eq!(sections.len(), 1);
+        let Polyline::Closed(verts) = &sections[0] else {
+            panic!("the section of a closed mesh should be closed");
+        };
+        assert!(verts.iter().all(|v| (v.z - 0.1).abs() < 1e-6));
+        // counter clockwise around the plane normal
+        let area: fxx = iter_pairs(verts).map(|(a, b)| a.x * b.y - b.x * a.y).sum();
+        assert!(area > 0.0);
+
+        // without one of its sides, the section of a cube is open
+        let (cube, _) = Mesh::new(
+            Mesh::new_cube(1.0).verts,
+            Mesh::new_cube(1.0).tri,
+            vec![],
+            Normals::None,
+        )
+        .to_clean();
+        let tri = cube
+            .iter_triangles()
+            .filter(|(a, b, c)| [a, b, c].iter().any(|i| cube.verts[**i].x < 0.4))
+            .flat_map(|(a, b, c)| [a, b, c])
+            .collect();
+        let open = Mesh::new(cube.verts, tri, vec![], Normals::None);
+        let sections = open.intersect(plane);
+        assert_eq!(sections.len(), 1);
+        let Polyline::Open(verts) = &sections[0] else {
+            panic!("the section of an open mesh should be open");
+        };
+        assert_eq!(verts.len(), 4 + 3);
+        assert!(verts.first().unwrap().x > 0.4 && verts.last().unwrap().x > 0.4);
+        assert!(verts[1..6].iter().all(|v| v.x < 0.4));
+
+        let stacked = ico.intersect_many(
+            [-0.5, 0.0, 0.5].map(|z| Plane::from_pos_normal(vec3(0.0, 0.0, z), Vec3::Z)),
+        );
+        assert_eq!(stacked.len(), 3);
+        assert!(stacked.iter().all(|s| s.len() == 1 && s[0].is_closed()));
+    }
+
+    #[test]
+    fn test_plane_loop_cut() {
+        let (cube, _) = Mesh::new(
+            Mesh::new_cube(1.0).verts,
+            Mesh::new_cube(1.0).tri,
+            vec![],
+            Normals::None,
+        )
+        .to_clean();
+        assert_eq!(cube.verts.len(), 8);
+        assert_eq!(cube.iter_naked_edges().count(), 0);
+
+        let plane = Plane::from_pos_normal(vec3(0.0, 0.0, 0.3), Vec3::Z);
+        let (cut, loops) = cube.clone().plane_loop_cut_with_loops(plane.clone());
+        assert_eq!(cut.verts.len(), 8 + 8);
+        assert_eq!(cut.count_triangles(), 12 + 8 * 2);
+        assert_eq!(cut.iter_naked_edges().count(), 0);
+
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].len(), 8 + 1);
+        assert_eq!(loops[0].first(), loops[0].last());
+        assert!(loops[0]
+            .iter()
+            .all(|i| (cut.verts[*i].z - 0.3).abs() < 1e-6));
+
+        // normals and attributes out of sync with the mesh are left alone
+        let mut broken = cube.with_normals(Normals::Face(vec![Vec3::Z; 3]));
+        broken
+            .vert_attributes
+            .insert("short", Attribute::Scalar(vec![1.0; 3]));
+        let cut = broken.plane_loop_cut(plane);
+        assert_eq!(cut.verts.len(), 8 + 8);
+        assert!(cut.vert_attributes.get("short").is_none());
+        assert!(matches!(&cut.normals, Normals::Face(n) if n.len() == 3));
+    }
+
+    #[test]
+    fn test_attributes_carried() {
+        let grid = |material: usize| {
+            let grid = Mesh::new_hexagrid(1.0, 2);
+            let heights = grid.verts.iter().map(|v| v.x).collect();
+            let materials = vec![material; grid.count_triangles()];
+            grid.with_vert_attribute("height", Attribute::Scalar(heights))
+                .with_tri_attribute("material", Attribute::Index(materials))
+        };
+        let materials = |mesh: &Mesh| match mesh.tri_attributes.get("material") {
+            Some(Attribute::Index(materials)) => materials.clone(),
+            _ => panic!("the triangle attribute should be carried along"),
+        };
+        let heights = |mesh: &Mesh| match mesh.vert_attributes.get("height") {
+            Some(Attribute::Scalar(heights)) => heights.clone(),
+            _ => panic!("the vertex attribute should be carried along"),
+        };
+        let in_sync = |mesh: &Mesh| {
+            let heights = heights(mesh);
+            assert_eq!(heights.len(), mesh.verts.len());
+            assert_eq!(materials(mesh).len(), mesh.count_triangles());
+            for (vert, height) in mesh.verts.iter().zip(heights) {
+                assert!((vert.x - height).abs() < 1e-6);
+            }
+        };
+
+        // channels missing in one of the joined meshes are padded
+        let bare = Mesh::new_hexagrid(1.0, 1);
+        let joined = Mesh::from_join(vec![grid(1), bare.clone(), grid(2).flip()]);
+        assert_eq!(heights(&joined).len(), joined.verts.len());
+        let joined_materials = materials(&joined);
+        let count = grid(1).count_triangles();
+        assert_eq!(joined_materials[0], 1);
+        assert_eq!(joined_materials[count], Attribute::NO_INDEX);
+        assert_eq!(joined_materials[count + bare.count_triangles()], 2);
+
+        let linear = grid(3).linearize();
+        assert_eq!(linear.verts.len(), linear.tri.len());
+        in_sync(&linear);
+        let uniform = linear.to_uniform();
+        assert_eq!(uniform.verts.len(), grid(3).verts.len());
+        in_sync(&uniform);
+
+        // a chain of close vertices keeps the attributes of the vertex whose position it keeps
+        let chain = vec![
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0008, 0.0, 0.0),
+            vec3(0.0016, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ];
+        let heights_chain = chain.iter().map(|v| v.x).collect();
+        let chain = Mesh::new(chain, vec![0, 1, 3, 1, 2, 3], Vec::new(), Normals::None)
+            .with_vert_attribute("height", Attribute::Scalar(heights_chain))
+            .with_tri_attribute("material", Attribute::Index(vec![7; 2]));
+        let uniform = chain.to_uniform();
+        assert_eq!(uniform.verts.len(), 3);
+        in_sync(&uniform);
+
+        let extruded = Mesh::extrude(&grid(4), vec3(0.0, 0.0, 1.0));
+        assert_eq!(materials(&extruded).len(), extruded.count_triangles());
+        assert!(materials(&extruded).iter().all(|m| *m == 4));
+
+        let (left, right) = grid(5).split(Plane::from_pos_normal(vec3(0.1, 0.0, 0.0), Vec3::X));
+        for half in [left, right] {
+            in_sync(&half);
+            assert!(materials(&half).contains(&5));
+        }
+        let (clean, _) = grid(6).to_clean();
+        in_sync(&clean);
     }
 }
@@ -0,0 +1,848 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+use super::mesh_contains::solid_angle;
+use super::{Bvh, Mesh, Normals, TriMesh};
+use crate::algos::{earcut_2d, polygon_contains, signed_area};
+use crate::kernel::{fxx, vec2, Vec2, Vec3, EPSILON, PI};
+use crate::math::Range3;
+use crate::util::{find, join};
+
+/// Which of the two meshes of a boolean operation is meant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanOperand {
+    /// the mesh the operation is called on
+    Lhs,
+    /// the mesh passed as argument
+    Rhs,
+}
+
+/// Why a boolean operation could not be performed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanError {
+    /// the mesh has edges used by only one triangle, so it does not enclose a volume
+    NotClosed(BooleanOperand),
+    /// an edge is used twice in the same direction: the mesh is not manifold, or not consistently oriented
+    NotManifold(BooleanOperand),
+}
+
+impl Error for BooleanError {}
+
+impl fmt::Display for BooleanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = |operand: &BooleanOperand| match operand {
+            BooleanOperand::Lhs => "first",
+            BooleanOperand::Rhs => "second",
+        };
+        match self {
+            BooleanError::NotClosed(operand) => {
+                write!(f, "Boolean error: the {} mesh is not closed", name(operand))
+            }
+            BooleanError::NotManifold(operand) => write!(
+                f,
+                "Boolean error: the {} mesh is not manifold or not consistently oriented",
+                name(operand)
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BooleanOp {
+    Union,
+    Difference,
+    Intersection,
+}
+
+/// Where a piece of one mesh lies, relative to the other mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Inside,
+    Outside,
+    /// on a face of the other mesh, facing the same way
+    Same,
+    /// on a face of the other mesh, facing the other way
+    Opposite,
+}
+
+impl Mesh {
+    /// The volume contained by either mesh.
+    ///
+    /// Both meshes must be closed and consistently oriented, with their normals pointing outwards.
+    /// Faces shared by both meshes are handled, and end up in the result once.
+    /// The result is welded and cleaned. Uvs, normals and vertex attributes are not kept.
+    pub fn union(&self, other: &Mesh) -> Result<Mesh, BooleanError> {
+        self.boolean(other, BooleanOp::Union)
+    }
+
+    /// The volume of this mesh, minus the volume of `other`. See [`Mesh::union`] for the requirements.
+    pub fn difference(&self, other: &Mesh) -> Result<Mesh, BooleanError> {
+        self.boolean(other, BooleanOp::Difference)
+    }
+
+    /// The volume contained by both meshes. See [`Mesh::union`] for the requirements.
+    pub fn intersection(&self, other: &Mesh) -> Result<Mesh, BooleanError> {
+        self.boolean(other, BooleanOp::Intersection)
+    }
+
+    fn boolean(&self, other: &Mesh, op: BooleanOp) -> Result<Mesh, BooleanError> {
+        // scale the tolerance to the size of the input
+        let size = self
+            .verts
+            .iter()
+            .chain(other.verts.iter())
+            .fold(1.0, |size: fxx, v| size.max(v.abs().max_element()));
+        let tol = EPSILON.sqrt() * size;
+
+        let a = Solid::new(self, BooleanOperand::Lhs, tol)?;
+        let b = Solid::new(other, BooleanOperand::Rhs, tol)?;
+
+        // find all cuts between the triangles of both meshes
+        let mut cuts_a = vec![Vec::new(); a.tri.len()];
+        let mut cuts_b = vec![Vec::new(); b.tri.len()];
+        let mut coplanar_a = vec![Vec::new(); a.tri.len()];
+        let mut coplanar_b = vec![Vec::new(); b.tri.len()];
+        let bvh = b.bvh();
+        for i in 0..a.tri.len() {
+            let ta = a.corners(i);
+            let margin = Vec3::splat(tol);
+            let range = Range3::from_points(
+                ta.map(|v| v - margin)
+                    .into_iter()
+                    .chain(ta.map(|v| v + margin)),
+            );
+            for j in bvh.overlap_box(&range) {
+                let tb = b.corners(j);
+                match intersect_triangles(&ta, a.normals[i], &tb, b.normals[j], tol) {
+                    Cut::None => (),
+                    Cut::Point(point) => {
+                        cuts_a[i].push((point, point));
+                        cuts_b[j].push((point, point));
+                    }
+                    Cut::Segment(from, to) => {
+                        cuts_a[i].push((from, to));
+                        cuts_b[j].push((from, to));
+                    }
+                    Cut::Coplanar => {
+                        for k in 0..3 {
+                            cuts_a[i].push((tb[k], tb[(k + 1) % 3]));
+                            cuts_b[j].push((ta[k], ta[(k + 1) % 3]));
+                        }
+                        coplanar_a[i].push(j);
+                        coplanar_b[j].push(i);
+                    }
+                }
+            }
+        }
+
+        // split, classify, and keep the right pieces
+        let mut soup = Vec::new();
+        for (faces, location) in a.classify(&b, &cuts_a, &coplanar_a, tol) {
+            let keep = matches!(
+                (op, location),
+                (BooleanOp::Union, Location::Outside | Location::Same)
+                    | (BooleanOp::Intersection, Location::Inside | Location::Same)
+                    | (
+                        BooleanOp::Difference,
+                        Location::Outside | Location::Opposite
+                    )
+            );
+            if keep {
+                soup.extend(faces);
+            }
+        }
+        for (face, location) in b.classify(&a, &cuts_b, &coplanar_b, tol) {
+            // shared faces are taken from the first mesh
+            match (op, location) {
+                (BooleanOp::Union, Location::Outside) => soup.extend(face),
+                (BooleanOp::Intersection, Location::Inside) => soup.extend(face),
+                (BooleanOp::Difference, Location::Inside) => {
+                    soup.extend(face.iter().map(|piece| [piece[1], piece[0], piece[2]]))
+                }
+                _ => (),
+            }
+        }
+
+        let verts = soup.iter().flatten().copied().collect::<Vec<_>>();
+        let tri = (0..verts.len()).collect();
+        let (mesh, _) = Mesh::new(verts, tri, Vec::new(), Normals::None).to_clean_tol(tol);
+        Ok(mesh)
+    }
+}
+
+/// A closed mesh, welded by position only
+struct Solid {
+    verts: Vec<Vec3>,
+    tri: Vec<[usize; 3]>,
+    normals: Vec<Vec3>,
+}
+
+impl Solid {
+    fn new(mesh: &Mesh, operand: BooleanOperand, tol: fxx) -> Result<Self, BooleanError> {
+        let root = TriMesh::weld_roots(&mesh.verts, tol);
+        let tri = mesh
+            .iter_triangles()
+            .map(|(a, b, c)| [root[a], root[b], root[c]])
+            .filter(|[a, b, c]| a != b && b != c && c != a)
+            .collect::<Vec<_>>();
+
+        let mut edges = HashMap::new();
+        for t in tri.iter() {
+            for k in 0..3 {
+                *edges.entry((t[k], t[(k + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+        if edges.values().any(|count| *count > 1) {
+            return Err(BooleanError::NotManifold(operand));
+        }
+        if edges.keys().any(|(a, b)| !edges.contains_key(&(*b, *a))) {
+            return Err(BooleanError::NotClosed(operand));
+        }
+
+        // slivers have no normal to cut with. They are dropped, like `to_clean` would
+        let verts = mesh.verts.clone();
+        let tri = tri
+            .into_iter()
+            .filter(|[a, b, c]| {
+                (verts[*b] - verts[*a])
+                    .cross(verts[*c] - verts[*a])
+                    .length()
+                    > tol * tol
+            })
+            .collect::<Vec<_>>();
+        let normals = tri
+            .iter()
+            .map(|[a, b, c]| {
+                (verts[*b] - verts[*a])
+                    .cross(verts[*c] - verts[*a])
+                    .normalize()
+            })
+            .collect();
+        Ok(Self {
+            verts,
+            tri,
+            normals,
+        })
+    }
+
+    fn corners(&self, t: usize) -> [Vec3; 3] {
+        self.tri[t].map(|i| self.verts[i])
+    }
+
+    /// A hierarchy over the triangles of this solid, using the same triangle indices
+    fn bvh(&self) -> Bvh {
+        let mesh = Mesh::new(
+            self.verts.clone(),
+            self.tri.concat(),
+            Vec::new(),
+            Normals::None,
+        );
+        Bvh::new(&mesh)
+    }
+
+    /// Split every triangle along its `cuts`, and locate the resulting faces in `other`.
+    /// Triangles without cuts lie on one side as a whole, and so does every region of uncut triangles
+    /// connected through their edges. Only one triangle per region is located, the rest takes its location
+    fn classify(
+        &self,
+        other: &Solid,
+        cuts: &[Vec<(Vec3, Vec3)>],
+        coplanar: &[Vec<usize>],
+        tol: fxx,
+    ) -> Vec<(Vec<[Vec3; 3]>, Location)> {
+        let mut regions = (0..self.tri.len()).collect::<Vec<_>>();
+        let mut edges = HashMap::new();
+        for t in (0..self.tri.len()).filter(|t| cuts[*t].is_empty()) {
+            let tri = self.tri[t];
+            for k in 0..3 {
+                let (a, b) = (tri[k], tri[(k + 1) % 3]);
+                if let Some(neighbor) = edges.insert((a.min(b), a.max(b)), t) {
+                    join(&mut regions, t, neighbor);
+                }
+            }
+        }
+
+        let mut region_locations = HashMap::new();
+        let mut faces = Vec::new();
+        for t in 0..self.tri.len() {
+            let normal = self.normals[t];
+            if cuts[t].is_empty() {
+                let face = vec![self.corners(t)];
+                let location = *region_locations
+                    .entry(find(&mut regions, t))
+                    .or_insert_with(|| other.locate(&face, normal, &[], tol));
+                faces.push((face, location));
+                continue;
+            }
+            for face in split_triangle(&self.corners(t), normal, &cuts[t], tol) {
+                let location = other.locate(&face, normal, &coplanar[t], tol);
+                faces.push((face, location));
+            }
+        }
+        faces
+    }
+
+    /// The generalized winding number: 1 inside the solid, 0 outside
+    fn winding_number(&self, point: Vec3) -> fxx {
+        (0..self.tri.len())
+            .map(|t| {
+                let [a, b, c] = self.corners(t);
+                solid_angle(a, b, c, point)
+            })
+            .sum::<fxx>()
+            / (4.0 * PI)
+    }
+
+    /// Where a face of a split triangle of the other mesh lies. `coplanar` lists the triangles of this solid in its plane.
+    /// The whole face lies on one side, so it is tested at the center of its largest triangle,
+    /// which stays clear of the boundary, unlike the slivers a face may also contain
+    fn locate(&self, face: &[[Vec3; 3]], normal: Vec3, coplanar: &[usize], tol: fxx) -> Location {
+        let area = |t: &&[Vec3; 3]| (t[1] - t[0]).cross(t[2] - t[0]).length_squared();
+        let Some(piece) = face.iter().max_by(|a, b| area(a).total_cmp(&area(b))) else {
+            return Location::Outside;
+        };
+        let center = (piece[0] + piece[1] + piece[2]) / 3.0;
+        for t in coplanar.iter() {
+            if in_triangle(center, &self.corners(*t), self.normals[*t], tol) {
+                return match normal.dot(self.normals[*t]) > 0.0 {
+                    true => Location::Same,
+                    false => Location::Opposite,
+                };
+            }
+        }
+        match self.winding_number(center) > 0.5 {
+            true => Location::Inside,
+            false => Location::Outside,
+        }
+    }
+}
+
+enum Cut {
+    None,
+    /// the triangles touch in a single point, which may still split an edge of either
+    Point(Vec3),
+    Segment(Vec3, Vec3),
+    Coplanar,
+}
+
+/// Is `point`, lying in the plane of triangle `t`, within that triangle
+fn in_triangle(point: Vec3, t: &[Vec3; 3], normal: Vec3, tol: fxx) -> bool {
+    (0..3).all(|k| {
+        let (u, v) = (t[k], t[(k + 1) % 3]);
+        (v - u).cross(point - u).dot(normal) >= -tol * (v - u).length()
+    })
+}
+
+fn intersect_triangles(ta: &[Vec3; 3], na: Vec3, tb: &[Vec3; 3], nb: Vec3, tol: fxx) -> Cut {
+    let distances = |t: &[Vec3; 3], origin: Vec3, normal: Vec3| {
+        t.map(|v| match normal.dot(v - origin) {
+            d if d.abs() < tol => 0.0,
+            d => d,
+        })
+    };
+    let da = distances(ta, tb[0], nb);
+    let db = distances(tb, ta[0], na);
+    if da.iter().all(|d| *d == 0.0) {
+        return Cut::Coplanar;
+    }
+    let apart = |d: &[fxx; 3]| d.iter().all(|d| *d > 0.0) || d.iter().all(|d| *d < 0.0);
+    if apart(&da) || apart(&db) {
+        return Cut::None;
+    }
+
+    // the section of each triangle with the plane of the other, within the other triangle
+    let mut points = Vec::new();
+    for (t, d, other, normal) in [(ta, da, tb, nb), (tb, db, ta, na)] {
+        for k in 0..3 {
+            let l = (k + 1) % 3;
+            if d[k] == 0.0 && in_triangle(t[k], other, normal, tol) {
+                points.push(t[k]);
+            }
+            if d[k] * d[l] < 0.0 {
+                let point = t[k] + (t[l] - t[k]) * d[k] / (d[k] - d[l]);
+                if in_triangle(point, other, normal, tol) {
+                    points.push(point);
+                }
+            }
+        }
+    }
+
+    let direction = na.cross(nb);
+    let by_direction = |a: &&Vec3, b: &&Vec3| direction.dot(**a).total_cmp(&direction.dot(**b));
+    match (
+        points.iter().min_by(by_direction),
+        points.iter().max_by(by_direction),
+    ) {
+        (Some(from), Some(to)) if from.distance(*to) > tol => Cut::Segment(*from, *to),
+        (Some(point), _) => Cut::Point(*point),
+        _ => Cut::None,
+    }
+}
+
+/// Split a triangle along all `cuts`, returning the triangulated faces of the split, with the same orientation.
+/// Cuts of zero length only split the edges they lie on. Cuts are clipped to the triangle. The cut pattern is built as a planar graph, the faces of which are triangulated.
+fn split_triangle(
+    t: &[Vec3; 3],
+    normal: Vec3,
+    cuts: &[(Vec3, Vec3)],
+    tol: fxx,
+) -> Vec<Vec<[Vec3; 3]>> {
+    if cuts.is_empty() {
+        return vec![vec![*t]];
+    }
+    let origin = t[0];
+    let x = (t[1] - t[0]).normalize();
+    let y = normal.cross(x);
+    let flat = |v: Vec3| vec2((v - origin).dot(x), (v - origin).dot(y));
+
+    let mut graph = Graph::new(tol);
+    let corners = t.map(|v| graph.add_node(flat(v), v));
+    let mut edges = vec![
+        (corners[0], corners[1]),
+        (corners[1], corners[2]),
+        (corners[2], corners[0]),
+    ];
+    let flat_corners = t.map(flat);
+    for (from, to) in cuts.iter() {
+        if from == to {
+            graph.add_node(flat(*from), *from);
+            continue;
+        }
+        let Some((t0, t1)) = clip_to_triangle(flat(*from), flat(*to), &flat_corners, tol) else {
+            continue;
+        };
+        let a = graph.add_node(flat(from.lerp(*to, t0)), from.lerp(*to, t0));
+        let b = graph.add_node(flat(from.lerp(*to, t1)), from.lerp(*to, t1));
+        if a != b {
+            edges.push((a, b));
+        }
+    }
+
+    // split edges at the nodes on them, and at their crossings
+    let mut splits = vec![Vec::new(); edges.len()];
+    for i in 0..edges.len() {
+        for j in (i + 1)..edges.len() {
+            let (p, r) = (graph.flat[edges[i].0], graph.flat[edges[i].1]);
+            let (q, w) = (graph.flat[edges[j].0], graph.flat[edges[j].1]);
+            let (r, w) = (r - p, w - q);
+            // overlapping edges are split by each others end points instead
+            let distance = |point: Vec2| r.normalize().perp_dot(point - p).abs();
+            if distance(q) < tol && distance(q + w) < tol {
+                continue;
+            }
+            let denom = r.perp_dot(w);
+            if denom.abs() < EPSILON {
+                continue;
+            }
+            let ti = (q - p).perp_dot(w) / denom;
+            let tj = (q - p).perp_dot(r) / denom;
+            let inside = |t: fxx, length: fxx| t * length > tol && (1.0 - t) * length > tol;
+            if inside(ti, r.length()) && inside(tj, w.length()) {
+                let (from, to) = (graph.verts[edges[i].0], graph.verts[edges[i].1]);
+                let node = graph.add_node(p + r * ti, from.lerp(to, ti));
+                splits[i].push(node);
+                splits[j].push(node);
+            }
+        }
+    }
+    let mut sub_edges = HashSet::new();
+    for (i, (a, b)) in edges.iter().enumerate() {
+        let (from, to) = (graph.flat[*a], graph.flat[*b]);
+        let length = from.distance(to);
+        let direction = (to - from) / length;
+        let mut nodes = (0..graph.flat.len())
+            .filter(|n| {
+                let offset = graph.flat[*n] - from;
+                let t = offset.dot(direction);
+                splits[i].contains(n)
+                    || (t > tol && t < length - tol && offset.perp_dot(direction).abs() < tol)
+            })
+            .map(|n| ((graph.flat[n] - from).dot(direction), n))
+            .collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut chain = vec![*a];
+        chain.extend(nodes.into_iter().map(|(_, n)| n));
+        chain.push(*b);
+        for pair in chain.windows(2) {
+            if pair[0] != pair[1] {
+                sub_edges.insert((pair[0].min(pair[1]), pair[0].max(pair[1])));
+            }
+        }
+    }
+
+    graph
+        .triangulate_faces(sub_edges)
+        .into_iter()
+        .map(|face| {
+            face.into_iter()
+                .map(|[a, b, c]| [graph.verts[a], graph.verts[b], graph.verts[c]])
+                .collect()
+        })
+        .collect()
+}
+
+/// The part of segment `a`-`b` within a counter clockwise triangle, as parameters along the segment
+fn clip_to_triangle(a: Vec2, b: Vec2, corners: &[Vec2; 3], tol: fxx) -> Option<(fxx, fxx)> {
+    let (mut t0, mut t1): (fxx, fxx) = (0.0, 1.0);
+    for k in 0..3 {
+        let (u, v) = (corners[k], corners[(k + 1) % 3]);
+        let edge = (v - u).normalize();
+        // signed distances to the edge, positive inside
+        let (da, db) = (edge.perp_dot(a - u), edge.perp_dot(b - u));
+        if da < -tol && db < -tol {
+            return None;
+        }
+        if da >= -tol && db >= -tol {
+            continue;
+        }
+        let t = da / (da - db);
+        if da < db {
+            t0 = t0.max(t);
+        } else {
+            t1 = t1.min(t);
+        }
+    }
+    ((t1 - t0) * a.distance(b) > tol).then_some((t0, t1))
+}
+
+/// A planar graph within a triangle, in both 2d and 3d
+struct Graph {
+    flat: Vec<Vec2>,
+    verts: Vec<Vec3>,
+    tol: fxx,
+}
+
+impl Graph {
+    fn new(tol: fxx) -> Self {
+        Self {
+            flat: Vec::new(),
+            verts: Vec::new(),
+            tol,
+        }
+    }
+
+    fn add_node(&mut self, flat: Vec2, vert: Vec3) -> usize {
+        match self.flat.iter().position(|f| f.distance(flat) < self.tol) {
+            Some(i) => i,
+            None => {
+                self.flat.push(flat);
+                self.verts.push(vert);
+                self.flat.len() - 1
+            }
+        }
+    }
+
+    /// Triangulate all bounded faces formed by `edges`. Loose ends are ignored
+    fn triangulate_faces(&self, mut edges: HashSet<(usize, usize)>) -> Vec<Vec<[usize; 3]>> {
+        let count = self.flat.len();
+
+        // prune loose ends
+        loop {
+            let mut degree = vec![0; count];
+            for (a, b) in edges.iter() {
+                degree[*a] += 1;
+                degree[*b] += 1;
+            }
+            let before = edges.len();
+            edges.retain(|(a, b)| degree[*a] > 1 && degree[*b] > 1);
+            if edges.len() == before {
+                break;
+            }
+        }
+
+        // neighbors, sorted counter clockwise
+        let mut neighbors = vec![Vec::new(); count];
+        for (a, b) in edges.iter() {
+            neighbors[*a].push(*b);
+            neighbors[*b].push(*a);
+        }
+        for (n, list) in neighbors.iter_mut().enumerate() {
+            let angle = |m: &usize| {
+                let d = self.flat[*m] - self.flat[n];
+                d.y.atan2(d.x)
+            };
+            list.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+        }
+
+        // walk all faces, keeping them on the left
+        let mut visited = HashSet::new();
+        let mut cycles = Vec::new();
+        let mut sorted_edges = edges.iter().copied().collect::<Vec<_>>();
+        sorted_edges.sort();
+        for (a, b) in sorted_edges {
+            for start in [(a, b), (b, a)] {
+                if visited.contains(&start) {
+                    continue;
+                }
+                let mut cycle = Vec::new();
+                let (mut from, mut to) = start;
+                loop {
+                    visited.insert((from, to));
+                    cycle.push(from);
+                    let list = &neighbors[to];
+                    let i = list.iter().position(|n| *n == from).unwrap_or(0);
+                    (from, to) = (to, list[(i + list.len() - 1) % list.len()]);
+                    if (from, to) == start {
+                        break;
+                    }
+                }
+                cycles.push(cycle);
+            }
+        }
+
+        // counter clockwise cycles are faces. Clockwise cycles are the outside of a connected group of edges,
+        // which is a hole if that group floats freely within the triangle
+        let mut group = (0..count).collect::<Vec<_>>();
+        for (a, b) in edges.iter() {
+            join(&mut group, *a, *b);
+        }
+        let polygon = |cycle: &Vec<usize>| cycle.iter().map(|i| self.flat[*i]).collect::<Vec<_>>();
+        let area = |cycle: &Vec<usize>| signed_area(&polygon(cycle));
+        let (faces, outlines): (Vec<_>, Vec<_>) = cycles.into_iter().partition(|c| area(c) > 0.0);
+        let mut holes = vec![Vec::new(); faces.len()];
+        for outline in outlines.iter().filter(|o| !o.contains(&0)) {
+            let outline_group = find(&mut group, outline[0]);
+            let point = self.flat[outline[0]];
+            let parent = (0..faces.len())
+                .filter(|f| find(&mut group, faces[*f][0]) != outline_group)
+                .filter(|f| polygon_contains(&polygon(&faces[*f]), point))
+                .min_by(|a, b| area(&faces[*a]).total_cmp(&area(&faces[*b])));
+            if let Some(parent) = parent {
+                holes[parent].push(outline);
+            }
+        }
+
+        let mut triangles = Vec::new();
+        for (face, holes) in faces.iter().zip(holes) {
+            if face.len() == 3 && holes.is_empty() {
+                triangles.push(vec![[face[0], face[1], face[2]]]);
+                continue;
+            }
+            let mut ids = face.clone();
+            let mut hole_starts = Vec::new();
+            for hole in holes {
+                hole_starts.push(ids.len());
+                ids.extend(hole.iter());
+            }
+            let Some(tri) = earcut_2d(&polygon(&ids), &hole_starts) else {
+                continue;
+            };
+            let mut face_triangles = Vec::new();
+            for t in tri.chunks(3) {
+                let [a, b, c] = [ids[t[0]], ids[t[1]], ids[t[2]]];
+                let (pa, pb, pc) = (self.flat[a], self.flat[b], self.flat[c]);
+                match (pb - pa).perp_dot(pc - pa) > 0.0 {
+                    true => face_triangles.push([a, b, c]),
+                    false => face_triangles.push([b, a, c]),
+                }
+            }
+            self.insert_skipped(&ids, &mut face_triangles);
+            self.remove_slivers(&mut face_triangles);
+            triangles.push(face_triangles);
+        }
+        triangles
+    }
+
+    /// Earcut may skip collinear nodes of a face altogether. Those nodes lie on the edge of a triangle,
+    /// so the triangles on both sides of that edge are split at the node
+    fn insert_skipped(&self, nodes: &[usize], triangles: &mut Vec<[usize; 3]>) {
+        for node in nodes.iter() {
+            if triangles.iter().any(|t| t.contains(node)) {
+                continue;
+            }
+            let point = self.flat[*node];
+            let on_edge = |(x, y): (usize, usize)| {
+                let (from, to) = (self.flat[x], self.flat[y]);
+                let length = from.distance(to);
+                let t = (point - from).dot(to - from) / length;
+                t > self.tol
+                    && t < length - self.tol
+                    && (to - from).perp_dot(point - from).abs() < self.tol * length
+            };
+            let Some((x, y)) = triangles
+                .iter()
+                .flat_map(|t| (0..3).map(|k| (t[k], t[(k + 1) % 3])))
+                .find(|edge| on_edge(*edge))
+            else {
+                continue;
+            };
+            for n in 0..triangles.len() {
+                let other = triangles[n];
+                let Some(k) = (0..3).find(|k| {
+                    let edge = (other[*k], other[(*k + 1) % 3]);
+                    edge == (x, y) || edge == (y, x)
+                }) else {
+                    continue;
+                };
+                let (x, y, z) = (other[k], other[(k + 1) % 3], other[(k + 2) % 3]);
+                triangles[n] = [x, *node, z];
+                triangles.push([*node, y, z]);
+            }
+        }
+    }
+
+    /// Earcut produces zero-area triangles at collinear nodes. Simply dropping those would leave a t-junction,
+    /// so the neighbor across the long edge of a sliver is split at its middle node instead
+    fn remove_slivers(&self, triangles: &mut Vec<[usize; 3]>) {
+        let sliver = |t: &[usize; 3]| {
+            let [a, b, c] = t.map(|i| self.flat[i]);
+            let longest = a.distance(b).max(b.distance(c)).max(c.distance(a));
+            (b - a).perp_dot(c - a).abs() <= self.tol * longest
+        };
+        // every split removes a sliver, unless it splits another sliver. Bound the work for fully degenerate input
+        let mut budget = triangles.len() * 3;
+        while let Some(s) = triangles.iter().position(sliver) {
+            if budget == 0 {
+                break;
+            }
+            budget -= 1;
+            let t = triangles.swap_remove(s);
+            let [a, b, c] = t.map(|i| self.flat[i]);
+            let (p, q, middle) = match (a.distance(b), b.distance(c), c.distance(a)) {
+                (ab, bc, ca) if ab >= bc && ab >= ca => (t[0], t[1], t[2]),
+                (_, bc, ca) if bc >= ca => (t[1], t[2], t[0]),
+                _ => (t[2], t[0], t[1]),
+            };
+            let neighbor = triangles.iter().enumerate().find_map(|(n, other)| {
+                (0..3).find_map(|k| {
+                    let (x, y) = (other[k], other[(k + 1) % 3]);
+                    ((x, y) == (p, q) || (x, y) == (q, p)).then_some((n, k))
+                })
+            });
+            if let Some((n, k)) = neighbor {
+                let other = triangles[n];
+                let (x, y, z) = (other[k], other[(k + 1) % 3], other[(k + 2) % 3]);
+                triangles[n] = [x, middle, z];
+                triangles.push([middle, y, z]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BooleanError, BooleanOperand};
+    use crate::core::Geometry;
+    use crate::kernel::{vec3, Vec3};
+    use crate::math::Range3;
+    use crate::solid::test_fixtures::assert_close;
+    use crate::solid::{Mesh, Normals};
+
+    fn cube(from: Vec3, to: Vec3) -> Mesh {
+        Mesh::from_range(Range3::new(from, to))
+    }
+
+    #[test]
+    fn test_boolean_cubes() {
+        let a = cube(Vec3::ZERO, Vec3::ONE);
+        let b = cube(Vec3::splat(0.5), Vec3::splat(1.5));
+
+        let union = a.union(&b).unwrap();
+        let intersection = a.intersection(&b).unwrap();
+        let difference = a.difference(&b).unwrap();
+        for mesh in [&union, &intersection, &difference] {
+            assert!(mesh.is_watertight());
+        }
+        assert_close(union.volume().unwrap(), 2.0 - 0.125);
+        assert_close(intersection.volume().unwrap(), 0.125);
+        assert_close(difference.volume().unwrap(), 1.0 - 0.125);
+        assert_close(intersection.area(), 6.0 * 0.25);
+        assert_close(union.area(), 2.0 * 6.0 - 6.0 * 0.25);
+    }
+
+    #[test]
+    fn test_boolean_coplanar() {
+        // all cubes share their top and bottom faces
+        let a = cube(Vec3::ZERO, Vec3::ONE);
+        let b = cube(vec3(0.5, 0.25, 0.0), vec3(1.5, 0.75, 1.0));
+
+        let union = a.union(&b).unwrap();
+        assert!(union.is_watertight());
+        assert_close(union.volume().unwrap(), 1.25);
+
+        let difference = a.difference(&b).unwrap();
+        assert!(difference.is_watertight());
+        assert_close(difference.volume().unwrap(), 0.75);
+
+        let intersection = a.intersection(&b).unwrap();
+        assert!(intersection.is_watertight());
+        assert_close(intersection.volume().unwrap(), 0.25);
+
+        // identical cubes
+        let union = a.union(&a).unwrap();
+        assert!(union.is_watertight());
+        assert_close(union.volume().unwrap(), 1.0);
+        assert!(a.difference(&a).unwrap().tri.is_empty());
+
+        // cubes touching along a face glue together
+        let c = cube(vec3(1.0, 0.0, 0.0), vec3(2.0, 1.0, 1.0));
+        let union = a.union(&c).unwrap();
+        assert!(union.is_watertight());
+        assert_close(union.volume().unwrap(), 2.0);
+        assert_close(union.area(), 10.0);
+        assert!(a.intersection(&c).unwrap().tri.is_empty());
+    }
+
+    #[test]
+    fn test_boolean_icosahedra() {
+        let a = Mesh::new_icosahedron(1.0);
+        let b = Mesh::new_icosahedron(1.0).mv(vec3(0.7, 0.3, 0.2));
+        let (va, vb) = (a.volume().unwrap(), b.volume().unwrap());
+        assert!(va > 0.0);
+
+        let union = a.union(&b).unwrap();
+        let intersection = a.intersection(&b).unwrap();
+        let difference = a.difference(&b).unwrap();
+        let reverse = b.difference(&a).unwrap();
+        for mesh in [&union, &intersection, &difference, &reverse] {
+            assert!(mesh.is_watertight());
+        }
+        let (vu, vi) = (union.volume().unwrap(), intersection.volume().unwrap());
+        assert!(vi > 0.0 && vi < va);
+        assert_close(vu + vi, va + vb);
+        assert_close(difference.volume().unwrap(), va - vi);
+        assert_close(reverse.volume().unwrap(), vb - vi);
+
+        // a small cube, fully inside
+        let small = cube(Vec3::splat(-0.1), Vec3::splat(0.1));
+        let hollow = a.difference(&small).unwrap();
+        assert_close(hollow.volume().unwrap(), va - 0.008);
+        assert_close(a.union(&small).unwrap().volume().unwrap(), va);
+    }
+
+    #[test]
+    fn test_boolean_dense() {
+        // most triangles are far from the cut, and are located per region instead of one by one
+        let a = Mesh::new_icosahedron(1.0).loop_subdivide(3, &[]);
+        let b = a.clone().mv(vec3(1.5, 0.2, 0.1));
+        let union = a.union(&b).unwrap();
+        let intersection = a.intersection(&b).unwrap();
+        assert!(union.is_watertight() && intersection.is_watertight());
+        let (va, vb) = (a.volume().unwrap(), b.volume().unwrap());
+        let (vu, vi) = (union.volume().unwrap(), intersection.volume().unwrap());
+        assert!(vi > 0.0);
+        assert_close(vu + vi, va + vb);
+    }
+
+    #[test]
+    fn test_boolean_open() {
+        let a = cube(Vec3::ZERO, Vec3::ONE);
+        let open = Mesh::new(a.verts.clone(), a.tri[6..].to_vec(), vec![], Normals::None);
+        assert_eq!(
+            a.union(&open).err(),
+            Some(BooleanError::NotClosed(BooleanOperand::Rhs))
+        );
+        assert_eq!(
+            open.intersection(&a).err(),
+            Some(BooleanError::NotClosed(BooleanOperand::Lhs))
+        );
+
+        let mut flipped = a.clone();
+        flipped.tri.swap(0, 1);
+        assert_eq!(
+            flipped.difference(&a).err(),
+            Some(BooleanError::NotManifold(BooleanOperand::Lhs))
+        );
+    }
+}
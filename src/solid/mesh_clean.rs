@@ -0,0 +1,254 @@
+use std::collections::HashSet;
+
+use super::{Mesh, Normals};
+use crate::data::SpatialHash;
+use crate::kernel::{fxx, Vec3, VEC_TOLERANCE};
+use crate::util::tolerance_equals;
+
+/// What [`Mesh::to_clean`] changed. A mesh which is already clean produces an empty report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CleanReport {
+    /// vertices merged into an earlier vertex with the same position, uv and normal
+    pub welded_verts: usize,
+    /// vertices not used by any triangle
+    pub unreferenced_verts: usize,
+    /// triangles pointing to a vertex which does not exist
+    pub invalid_triangles: usize,
+    /// triangles using the same vertex twice (after welding)
+    pub degenerate_triangles: usize,
+    /// triangles with three distinct, but collinear or coincident vertices
+    pub zero_area_triangles: usize,
+    /// triangles using the same vertices in the same order as an earlier triangle
+    pub duplicate_triangles: usize,
+}
+
+impl CleanReport {
+    pub fn is_clean(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn removed_verts(&self) -> usize {
+        self.welded_verts + self.unreferenced_verts
+    }
+
+    pub fn removed_triangles(&self) -> usize {
+        self.invalid_triangles
+            + self.degenerate_triangles
+            + self.zero_area_triangles
+            + self.duplicate_triangles
+    }
+}
+
+impl Mesh {
+    pub fn to_clean(&self) -> (Mesh, CleanReport) {
+        self.to_clean_tol(VEC_TOLERANCE)
+    }
+
+    /// Repair the mesh:
+    /// - weld vertices closer than `tolerance`, if their uvs and vertex normals match as well
+    /// - drop invalid, degenerate, zero-area and duplicate triangles
+    /// - drop unreferenced vertices
+    ///
+    /// Uvs, vertex normals, face normals, vertex attributes and triangle attributes are kept in sync.
+    /// Any of those which was already out of sync with the vertices or triangles is dropped.
+    pub fn to_clean_tol(&self, tolerance: fxx) -> (Mesh, CleanReport) {
+        let mut report = CleanReport::default();
+        let count = self.verts.len();
+        let tri_count = self.tri.len() / 3;
+        let uvs = (self.uvs.len() == count).then_some(&self.uvs);
+        let vert_normals = match &self.normals {
+            Normals::Vertex(normals) if normals.len() == count => Some(normals),
+            _ => None,
+        };
+
+        // weld. Only representatives are stored in the hash, so the mapping never chains
+        let mut hash = SpatialHash::new(tolerance);
+        let mut weld = Vec::with_capacity(count);
+        for (i, vert) in self.verts.iter().enumerate() {
+            let similar = hash.iter_near(*vert).filter(|j| {
+                tolerance_equals(*vert, self.verts[*j], tolerance)
+                    && uvs.map_or(true, |uvs| uvs[i].abs_diff_eq(uvs[*j], tolerance))
+                    && vert_normals.map_or(true, |n| n[i].abs_diff_eq(n[*j], tolerance))
+            });
+            match similar.min() {
+                Some(j) => {
+                    weld.push(j);
+                    report.welded_verts += 1;
+                }
+                None => {
+                    hash.insert(*vert, i);
+                    weld.push(i);
+                }
+            }
+        }
+
+        // filter triangles
+        let mut kept_tris = Vec::with_capacity(tri_count);
+        let mut existing = HashSet::new();
+        for (t, (a, b, c)) in self.iter_triangles().enumerate() {
+            if a >= count || b >= count || c >= count {
+                report.invalid_triangles += 1;
+                continue;
+            }
+            let (a, b, c) = (weld[a], weld[b], weld[c]);
+            if a == b || b == c || c == a {
+                report.degenerate_triangles += 1;
+                continue;
+            }
+            if triangle_area(self.verts[a], self.verts[b], self.verts[c]) <= tolerance * tolerance {
+                report.zero_area_triangles += 1;
+                continue;
+            }
+            // rotate the smallest index to the front, so the winding is kept
+            let key = match a.min(b).min(c) {
+                m if m == a => (a, b, c),
+                m if m == b => (b, c, a),
+                _ => (c, a, b),
+            };
+            if !existing.insert(key) {
+                report.duplicate_triangles += 1;
+                continue;
+            }
+            kept_tris.push((t, [a, b, c]));
+        }
+
+        let (mesh, kept_verts) = self.rebuild(&kept_tris);
+        report.unreferenced_verts = count - kept_verts - report.welded_verts;
+        (mesh, report)
+    }
+
+    /// A new mesh, containing only the triangles at `ids`. Unused vertices are dropped.
+    /// Uvs, normals, vertex attributes and triangle attributes are kept in sync.
+    pub fn select_triangles(&self, ids: &[usize]) -> Mesh {
+        let triangles = self.iter_triangles().collect::<Vec<_>>();
+        let kept_tris = ids
+            .iter()
+            .map(|t| {
+                let (a, b, c) = triangles[*t];
+                (*t, [a, b, c])
+            })
+            .collect::<Vec<_>>();
+        self.rebuild(&kept_tris).0
+    }
+
+    /// Build a new mesh from `(original triangle index, vertex ids)` pairs, dropping all unreferenced vertices.
+    /// Returns the mesh, and the number of vertices kept
+    pub(super) fn rebuild(&self, kept_tris: &[(usize, [usize; 3])]) -> (Mesh, usize) {
+        let count = self.verts.len();
+        let uvs = (self.uvs.len() == count).then_some(&self.uvs);
+        let (vert_normals, face_normals) = match &self.normals {
+            Normals::Vertex(normals) if normals.len() == count => (Some(normals), None),
+            Normals::Face(normals) if normals.len() == self.tri.len() / 3 => (None, Some(normals)),
+            _ => (None, None),
+        };
+
+        let mut referenced = vec![false; count];
+        for (_, tri) in kept_tris.iter() {
+            for i in tri {
+                referenced[*i] = true;
+            }
+        }
+        let mut remap = vec![usize::MAX; count];
+        let mut kept_verts = Vec::new();
+        for (i, is_referenced) in referenced.into_iter().enumerate() {
+            if is_referenced {
+                remap[i] = kept_verts.len();
+                kept_verts.push(i);
+            }
+        }
+
+        let verts = kept_verts.iter().map(|i| self.verts[*i]).collect();
+        let tri = kept_tris
+            .iter()
+            .flat_map(|(_, tri)| tri.map(|i| remap[i]))
+            .collect();
+        let uvs = uvs
+            .map(|uvs| kept_verts.iter().map(|i| uvs[*i]).collect())
+            .unwrap_or_default();
+        let normals = match (vert_normals, face_normals) {
+            (Some(normals), _) => Normals::Vertex(kept_verts.iter().map(|i| normals[*i]).collect()),
+            (_, Some(normals)) => {
+                Normals::Face(kept_tris.iter().map(|(t, _)| normals[*t]).collect())
+            }
+            _ => Normals::None,
+        };
+
+        let mut mesh = Mesh::new(verts, tri, uvs, normals);
+        mesh.vert_attributes = self.vert_attributes.select(count, &kept_verts);
+        let kept_faces = kept_tris.iter().map(|(t, _)| *t).collect::<Vec<_>>();
+        mesh.tri_attributes = self.tri_attributes.select(self.tri.len() / 3, &kept_faces);
+        (mesh, kept_verts.len())
+    }
+}
+
+fn triangle_area(a: Vec3, b: Vec3, c: Vec3) -> fxx {
+    (b - a).cross(c - a).length() * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kernel::{vec2, vec3, Vec3};
+    use crate::solid::{Mesh, Normals};
+
+    #[test]
+    fn test_clean_cube() {
+        // the cube has seams in both its normals and uvs, so nothing should be welded
+        let cube = Mesh::new_cube(1.0);
+        let (clean, report) = cube.to_clean();
+        assert!(report.is_clean(), "{:?}", report);
+        assert_eq!(clean.verts, cube.verts);
+        assert_eq!(clean.tri, cube.tri);
+
+        // without those, the corners can be welded
+        let bare = Mesh::new(cube.verts.clone(), cube.tri.clone(), vec![], Normals::None);
+        let (clean, report) = bare.to_clean();
+        assert_eq!(clean.verts.len(), 8);
+        assert_eq!(report.welded_verts, 16);
+        assert_eq!(clean.tri.len(), cube.tri.len());
+    }
+
+    #[test]
+    fn test_clean_soup() {
+        let verts = vec![
+            // two triangles, sharing an edge
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0001),
+            // a vertex no one uses
+            vec3(5.0, 5.0, 5.0),
+            // a collinear vertex
+            vec3(2.0, 0.0, 0.0),
+        ];
+        let tri = vec![
+            0, 1, 2, //
+            3, 4, 5, //
+            0, 3, 1, // degenerate after welding
+            0, 1, 7, // zero area
+            1, 2, 0, // duplicate of the first one
+            0, 1, 9, // invalid
+        ];
+        let uvs = verts.iter().map(|v| vec2(v.x, v.y)).collect::<Vec<_>>();
+        let normals = Normals::Face(vec![Vec3::Z; 6]);
+        let mesh = Mesh::new(verts, tri, uvs, normals);
+
+        let (clean, report) = mesh.to_clean();
+        assert_eq!(report.welded_verts, 2);
+        assert_eq!(report.unreferenced_verts, 2);
+        assert_eq!(report.removed_verts(), 4);
+        assert_eq!(report.degenerate_triangles, 1);
+        assert_eq!(report.zero_area_triangles, 1);
+        assert_eq!(report.duplicate_triangles, 1);
+        assert_eq!(report.invalid_triangles, 1);
+
+        assert_eq!(clean.verts.len(), 4);
+        assert_eq!(clean.uvs.len(), 4);
+        assert_eq!(clean.tri, vec![0, 1, 2, 1, 3, 2]);
+        assert!(matches!(clean.normals, Normals::Face(ref n) if n.len() == 2));
+
+        let (_, report) = clean.to_clean();
+        assert!(report.is_clean());
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use super::{Bvh, Mesh, TriMesh, WELD_TOLERANCE};
+use crate::algos::signed_volume;
+use crate::kernel::{fxx, vec3, Vec3, PI};
+use crate::lines::Ray;
+
+/// Ray hits closer than this to an edge, or to the ray origin, are ambiguous
+const CONTAINS_RAY_TOLERANCE: fxx = 1e-6;
+
+impl Mesh {
+    /// Does the volume enclosed by this mesh contain `point`.
+    ///
+    /// Uses the generalized winding number, so meshes with small holes, or overlapping parts, still give sensible answers.
+    /// The mesh should be oriented outwards. Points on the surface may go either way.
+    ///
+    /// This always visits every triangle, even for watertight meshes: checking that a mesh is watertight,
+    /// or building a [`Bvh`] for the fast path, costs as much as the query itself.
+    /// Use [`Mesh::contains_many`] to test several points against the same mesh.
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.winding_number(point) > 0.5
+    }
+
+    /// [`Mesh::contains`] for many points at once.
+    ///
+    /// If the mesh is watertight, this casts a ray per point through a [`Bvh`] instead of visiting every triangle,
+    /// and only falls back to the winding number when a ray grazes an edge.
+    pub fn contains_many(&self, points: &[Vec3]) -> Vec<bool> {
+        if !self.is_watertight() {
+            return points.iter().map(|p| self.contains(*p)).collect();
+        }
+        let bvh = Bvh::new(self);
+        // any direction works. This one is unlikely to run exactly along the edges of generated geometry
+        let direction = vec3(0.4311, 0.7523, 0.4980).normalize();
+        points
+            .iter()
+            .map(|point| {
+                let ray = Ray::new(*point, direction);
+                let mut winding = 0;
+                for hit in bvh.raycast_all(&ray) {
+                    let [a, b, c] = bvh.triangle(hit.triangle);
+                    let normal = (b - a).cross(c - a).normalize_or_zero();
+                    let grazing = normal.dot(direction).abs() < CONTAINS_RAY_TOLERANCE;
+                    if grazing
+                        || hit.t < CONTAINS_RAY_TOLERANCE
+                        || hit.barycentric.min_element() < CONTAINS_RAY_TOLERANCE
+                    {
+                        return self.contains(*point);
+                    }
+                    // leaving through a face adds one, entering through a face removes one
+                    winding += normal.dot(direction).signum() as i32;
+                }
+                winding > 0
+            })
+            .collect()
+    }
+
+    /// The generalized winding number of the mesh around `point`:
+    /// 1 inside a closed, outward oriented mesh, 0 outside, and something in between near holes.
+    ///
+    /// See Robust Inside-Outside Segmentation using Generalized Winding Numbers, Jacobson et al.
+    pub fn winding_number(&self, point: Vec3) -> fxx {
+        self.iter_triangle_verts()
+            .map(|(a, b, c)| solid_angle(a, b, c, point))
+            .sum::<fxx>()
+            / (4.0 * PI)
+    }
+
+    /// Every edge, after welding by position, is used once in both directions
+    pub(super) fn is_watertight(&self) -> bool {
+        if self.tri.is_empty() {
+            return false;
+        }
+        let root = TriMesh::weld_roots(&self.verts, WELD_TOLERANCE);
+        let mut edges = HashMap::new();
+        for (a, b, c) in self.iter_triangles() {
+            let [a, b, c] = [root[a], root[b], root[c]];
+            for edge in [(a, b), (b, c), (c, a)] {
+                *edges.entry(edge).or_insert(0) += 1;
+            }
+        }
+        edges
+            .iter()
+            .all(|((a, b), count)| *count == 1 && edges.get(&(*b, *a)) == Some(&1))
+    }
+}
+
+/// The signed solid angle of triangle `abc`, as seen from `point`. Positive when looking at its back side.
+/// From The Solid Angle of a Plane Triangle, Van Oosterom & Strackee
+pub(super) fn solid_angle(a: Vec3, b: Vec3, c: Vec3, point: Vec3) -> fxx {
+    let det = -6.0 * signed_volume(a, b, c, point);
+    let [a, b, c] = [a, b, c].map(|v| v - point);
+    let (la, lb, lc) = (a.length(), b.length(), c.length());
+    let div = la * lb * lc + a.dot(b) * lc + a.dot(c) * lb + b.dot(c) * la;
+    2.0 * det.atan2(div)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::Geometry;
+    use crate::kernel::{fxx, vec3, Vec3};
+    use crate::solid::{Mesh, Normals};
+
+    fn grid(count: usize, size: fxx) -> Vec<Vec3> {
+        let step = size / count as fxx;
+        (0..count * count * count)
+            .map(|i| {
+                let (x, y, z) = (i % count, (i / count) % count, i / (count * count));
+                vec3(x as fxx, y as fxx, z as fxx) * step - Vec3::splat(size * 0.5) + 0.0123
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_contains_cube() {
+        let cube = Mesh::new_cube(1.0);
+        assert!(cube.contains(Vec3::ZERO));
+        assert!(cube.contains(vec3(0.9, -0.9, 0.5)));
+        assert!(!cube.contains(vec3(1.1, 0.0, 0.0)));
+        assert!((cube.winding_number(Vec3::ZERO) - 1.0).abs() < 1e-6);
+        assert!(cube.winding_number(vec3(5.0, 3.0, 1.0)).abs() < 1e-6);
+
+        // the fast path agrees with the winding number
+        let points = grid(9, 3.0);
+        let inside = cube.contains_many(&points);
+        for (point, inside) in points.iter().zip(inside) {
+            assert_eq!(inside, cube.contains(*point), "{:?}", point);
+            assert_eq!(inside, point.abs().max_element() < 1.0, "{:?}", point);
+        }
+
+        // including rays through the edges of the cube
+        let direction = vec3(0.4311, 0.7523, 0.4980).normalize();
+        let points = [
+            vec3(1.0, 1.0, 0.2) - direction * 0.5,
+            vec3(-0.3, 1.0, 1.0) - direction * 0.1,
+        ];
+        assert!(cube.contains_many(&points).into_iter().all(|inside| inside));
+    }
+
+    #[test]
+    fn test_contains_open() {
+        // a sphere with a missing triangle still mostly contains its center
+        let sphere = Mesh::new_icosahedron(1.0);
+        let mut tri = sphere.tri.clone();
+        tri.truncate(tri.len() - 3);
+        let open = Mesh::new(sphere.verts.clone(), tri, vec![], Normals::None);
+        assert!(open.iter_naked_edges().count() > 0);
+        assert!(open.contains(Vec3::ZERO));
+        assert!(!open.contains(vec3(0.0, 2.0, 0.0)));
+
+        let winding = open.winding_number(Vec3::ZERO);
+        assert!(winding > 0.8 && winding < 1.0);
+
+        let points = grid(6, 3.0);
+        let batch = open.contains_many(&points);
+        for (point, inside) in points.iter().zip(batch) {
+            assert_eq!(inside, open.contains(*point));
+        }
+    }
+
+    #[test]
+    fn test_contains_nested() {
+        // two overlapping cubes, counted once
+        let a = Mesh::new_cube(1.0);
+        let b = Mesh::new_cube(1.0).mv(vec3(1.0, 0.0, 0.0));
+        let both = Mesh::from_join(vec![a, b]);
+        let points = [
+            vec3(-0.5, 0.1, 0.2),
+            vec3(0.5, 0.1, 0.2),
+            vec3(1.5, 0.1, 0.2),
+            vec3(2.5, 0.1, 0.2),
+        ];
+        assert_eq!(both.contains_many(&points), vec![true, true, true, false]);
+
+        // a cavity is not inside
+        let hollow = Mesh::from_join(vec![Mesh::new_cube(1.0), Mesh::new_cube(0.5).flip()]);
+        let points = [Vec3::ZERO, vec3(0.75, 0.0, 0.1), vec3(1.5, 0.0, 0.0)];
+        assert_eq!(hollow.contains_many(&points), vec![false, true, false]);
+        assert_eq!(
+            points.map(|p| hollow.contains(p)).to_vec(),
+            vec![false, true, false]
+        );
+    }
+}
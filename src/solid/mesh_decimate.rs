@@ -0,0 +1,419 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use super::{Mesh, TriMesh, WELD_TOLERANCE};
+use crate::kernel::{fxx, Vec3};
+
+/// How much heavier the planes along a boundary weigh, compared to the planes of triangles
+const BOUNDARY_WEIGHT: fxx = 10.0;
+
+/// A symmetric 4x4 matrix, summing squared distances to a set of planes
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([fxx; 10]);
+
+impl Quadric {
+    fn from_plane(normal: Vec3, point: Vec3, weight: fxx) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        let d = -normal.dot(point);
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|q| q * weight),
+        )
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (q, o) in self.0.iter_mut().zip(other.0) {
+            *q += o;
+        }
+    }
+
+    /// the summed squared distance of `p` to all planes
+    fn error(&self, p: Vec3) -> fxx {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        let error = x * (aa * x + 2.0 * (ab * y + ac * z + ad))
+            + y * (bb * y + 2.0 * (bc * z + bd))
+            + z * (cc * z + 2.0 * cd)
+            + dd;
+        error.max(0.0)
+    }
+}
+
+/// Moving the vertex at position `from` onto position `to`
+#[derive(Debug, Clone, Copy)]
+struct Collapse {
+    cost: fxx,
+    from: usize,
+    to: usize,
+    /// version of `from` and `to` when this was computed
+    version: (usize, usize),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// reversed, so the cheapest collapse is on top of the heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then(other.from.cmp(&self.from))
+            .then(other.to.cmp(&self.to))
+    }
+}
+
+/// Triangles by position, as they are being collapsed
+struct Topology<'a> {
+    tris: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    position: &'a [usize],
+    /// the triangles around every position. Includes triangles which are no longer alive
+    around: Vec<Vec<usize>>,
+}
+
+impl Topology<'_> {
+    fn live(&self, p: usize) -> Vec<usize> {
+        self.around[p]
+            .iter()
+            .copied()
+            .filter(|t| self.alive[*t])
+            .collect()
+    }
+
+    fn neighbors(&self, p: usize) -> HashSet<usize> {
+        self.live(p)
+            .iter()
+            .flat_map(|t| self.tris[*t].map(|r| self.position[r]))
+            .filter(|n| *n != p)
+            .collect()
+    }
+}
+
+impl Mesh {
+    /// Simplify the mesh by collapsing edges, cheapest first, using quadric error metrics.
+    /// Stops once at most `target_triangles` are left, or when the next collapse would move the surface further than `max_error`.
+    /// Pass `fxx::INFINITY` or `0` to only use one of the two limits.
+    ///
+    /// Vertices are collapsed onto one of their neighbors, so uvs, normals and vertex attributes don't need to be interpolated.
+    /// Boundaries are kept in place: vertices on a boundary only slide along it, onto another boundary vertex.
+    /// Vertices on a seam, where uvs or vertex normals differ between triangles, are never moved.
+    /// The mesh is welded and cleaned first.
+    ///
+    /// Returns the simplified mesh, and the largest error introduced.
+    /// Based on Surface Simplification Using Quadric Error Metrics, Garland & Heckbert
+    pub fn decimate(&self, target_triangles: usize, max_error: fxx) -> (Mesh, fxx) {
+        let (mesh, _) = self.to_clean_tol(WELD_TOLERANCE);
+        let position = TriMesh::weld_roots(&mesh.verts, WELD_TOLERANCE);
+        let tris = mesh
+            .iter_triangles()
+            .map(|(a, b, c)| [a, b, c])
+            .collect::<Vec<_>>();
+        let point = |render: usize| mesh.verts[render];
+
+        // topology, by position
+        let mut around = vec![Vec::new(); mesh.verts.len()];
+        let mut renders = vec![HashSet::new(); mesh.verts.len()];
+        let mut edges = HashMap::<(usize, usize), usize>::new();
+        for (t, tri) in tris.iter().enumerate() {
+            for k in 0..3 {
+                let (a, b) = (position[tri[k]], position[tri[(k + 1) % 3]]);
+                around[a].push(t);
+                renders[a].insert(tri[k]);
+                *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+        let mut boundary = vec![false; mesh.verts.len()];
+        let mut locked = renders.iter().map(|r| r.len() > 1).collect::<Vec<_>>();
+        for ((a, b), count) in edges.iter() {
+            match count {
+                1 => {
+                    boundary[*a] = true;
+                    boundary[*b] = true;
+                }
+                2 => (),
+                _ => {
+                    locked[*a] = true;
+                    locked[*b] = true;
+                }
+            }
+        }
+
+        // quadrics
+        let mut quadrics = vec![Quadric::default(); mesh.verts.len()];
+        for tri in tris.iter() {
+            let [a, b, c] = tri.map(point);
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+            let plane = Quadric::from_plane(normal, a, 1.0);
+            for k in 0..3 {
+                quadrics[position[tri[k]]].add(&plane);
+
+                // a plane perpendicular to the triangle keeps the boundary from moving inwards
+                let (from, to) = (position[tri[k]], position[tri[(k + 1) % 3]]);
+                if edges[&(from.min(to), from.max(to))] == 1 {
+                    let along = point(tri[(k + 1) % 3]) - point(tri[k]);
+                    let side = along.cross(normal).normalize_or_zero();
+                    let fence = Quadric::from_plane(side, point(tri[k]), BOUNDARY_WEIGHT);
+                    quadrics[from].add(&fence);
+                    quadrics[to].add(&fence);
+                }
+            }
+        }
+
+        let mut alive_count = tris.len();
+        let mut topology = Topology {
+            alive: vec![true; tris.len()],
+            tris,
+            position: &position,
+            around,
+        };
+        let mut version = vec![0; mesh.verts.len()];
+        let mut dead = vec![false; mesh.verts.len()];
+        let mut heap = BinaryHeap::new();
+        let candidate = |from: usize, to: usize, quadrics: &[Quadric], version: &[usize]| {
+            let mut quadric = quadrics[from];
+            quadric.add(&quadrics[to]);
+            Collapse {
+                cost: quadric.error(mesh.verts[to]),
+                from,
+                to,
+                version: (version[from], version[to]),
+            }
+        };
+        for (a, b) in edges.keys() {
+            heap.push(candidate(*a, *b, &quadrics, &version));
+            heap.push(candidate(*b, *a, &quadrics, &version));
+        }
+
+        let max_cost = max_error * max_error;
+        let mut reached: fxx = 0.0;
+        while let Some(collapse) = heap.pop() {
+            if alive_count <= target_triangles || collapse.cost > max_cost {
+                break;
+            }
+            let Collapse { from, to, .. } = collapse;
+            if dead[from] || dead[to] || collapse.version != (version[from], version[to]) {
+                continue;
+            }
+            if locked[from] {
+                continue;
+            }
+
+            let from_tris = topology.live(from);
+            let shared = from_tris
+                .iter()
+                .copied()
+                .filter(|t| topology.tris[*t].iter().any(|r| position[*r] == to))
+                .collect::<Vec<_>>();
+            if shared.is_empty() || (boundary[from] && shared.len() != 1) {
+                continue;
+            }
+
+            // link condition: the only neighbors both positions share are the corners opposite their edge.
+            // Otherwise the collapse would pinch the surface
+            let (from_neighbors, to_neighbors) = (topology.neighbors(from), topology.neighbors(to));
+            let common = from_neighbors.intersection(&to_neighbors).count();
+            if common != shared.len() {
+                continue;
+            }
+
+            // no triangle may flip, or collapse to nothing
+            let flips = from_tris.iter().filter(|t| !shared.contains(t)).any(|t| {
+                let [a, b, c] = topology.tris[*t].map(point);
+                let before = (b - a).cross(c - a);
+                let [a, b, c] = topology.tris[*t].map(|r| match position[r] == from {
+                    true => point(to),
+                    false => point(r),
+                });
+                let after = (b - a).cross(c - a);
+                before.dot(after) <= 0.0
+            });
+            if flips {
+                continue;
+            }
+
+            // the render vertex to use: the one at `to` on the side of `from`.
+            // `from` is not on a seam, so all of its triangles are on the same side
+            let Some(target) = topology.tris[shared[0]]
+                .iter()
+                .copied()
+                .find(|r| position[*r] == to)
+            else {
+                continue;
+            };
+            for t in from_tris.iter() {
+                if shared.contains(t) {
+                    topology.alive[*t] = false;
+                    alive_count -= 1;
+                    continue;
+                }
+                for r in topology.tris[*t].iter_mut() {
+                    if position[*r] == from {
+                        *r = target;
+                    }
+                }
+                topology.around[to].push(*t);
+            }
+            dead[from] = true;
+            let moved = quadrics[from];
+            quadrics[to].add(&moved);
+            version[to] += 1;
+            reached = reached.max(collapse.cost);
+
+            for n in topology.neighbors(to) {
+                heap.push(candidate(to, n, &quadrics, &version));
+                heap.push(candidate(n, to, &quadrics, &version));
+            }
+        }
+
+        let kept = (0..topology.tris.len())
+            .filter(|t| topology.alive[*t])
+            .map(|t| (t, topology.tris[t]))
+            .collect::<Vec<_>>();
+        (mesh.rebuild(&kept).0, reached.sqrt())
+    }
+
+    /// Progressively simplified versions of this mesh, for level of detail switching.
+    /// The first level is the welded and cleaned mesh itself. Every next level has about half the triangles of the one before it.
+    ///
+    /// Returns at most `levels` meshes. Fewer are returned if the mesh cannot be simplified any further.
+    pub fn lod_chain(&self, levels: usize) -> Vec<Mesh> {
+        let mut chain: Vec<Mesh> = Vec::with_capacity(levels);
+        if levels == 0 {
+            return chain;
+        }
+        chain.push(self.to_clean_tol(WELD_TOLERANCE).0);
+        while chain.len() < levels {
+            let last = chain.last().unwrap();
+            let count = last.tri.len() / 3;
+            let (next, _) = last.decimate(count / 2, fxx::INFINITY);
+            if next.tri.is_empty() || next.tri.len() >= last.tri.len() {
+                break;
+            }
+            chain.push(next);
+        }
+        chain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kernel::{fxx, vec2, vec3, Vec3, PI};
+    use crate::solid::{Mesh, Normals};
+
+    /// a bulging tube without caps, with a uv seam where the angle wraps around
+    fn tube(segments: usize, rings: usize) -> Mesh {
+        let mut verts = Vec::new();
+        let mut uvs = Vec::new();
+        for ring in 0..=rings {
+            for segment in 0..=segments {
+                let u = segment as fxx / segments as fxx;
+                let v = ring as fxx / rings as fxx;
+                let angle = u * 2.0 * PI;
+                let radius = 1.0 + 0.3 * (v * 2.0 * PI).sin();
+                verts.push(vec3(angle.cos() * radius, angle.sin() * radius, v * 3.0));
+                uvs.push(vec2(u, v));
+            }
+        }
+        let mut tri = Vec::new();
+        let row = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let a = ring * row + segment;
+                tri.extend([a, a + 1, a + row + 1, a, a + row + 1, a + row]);
+            }
+        }
+        Mesh::new(verts, tri, uvs, Normals::None)
+    }
+
+    #[test]
+    fn test_decimate_flat() {
+        // a flat hexagon can be reduced to very few triangles, without changing its outline
+        let grid = Mesh::new_hexagrid(1.0, 8);
+        let (simple, error) = grid.decimate(0, 1e-6);
+        assert!(
+            simple.tri.len() * 5 < grid.tri.len(),
+            "{} {}",
+            simple.tri.len(),
+            grid.tri.len()
+        );
+        assert!(error < 1e-6);
+        assert!((simple.area() - grid.area()).abs() < 1e-4);
+        assert!(simple.verts.iter().all(|v| v.z.abs() < 1e-9));
+
+        // all triangles keep facing the same way
+        let normal = |(a, b, c): (Vec3, Vec3, Vec3)| (b - a).cross(c - a).z;
+        let up = normal(grid.iter_triangle_verts().next().unwrap()) > 0.0;
+        assert!(simple
+            .iter_triangle_verts()
+            .all(|t| (normal(t) > 0.0) == up));
+    }
+
+    #[test]
+    fn test_decimate_target() {
+        let tube = tube(32, 16);
+        let count = tube.tri.len() / 3;
+        let (simple, error) = tube.decimate(count / 4, fxx::INFINITY);
+        assert!(simple.tri.len() / 3 <= count / 4);
+        assert!(error > 0.0);
+
+        // uvs stay with their vertices
+        assert_eq!(simple.uvs.len(), simple.verts.len());
+        for (vert, uv) in simple.verts.iter().zip(simple.uvs.iter()) {
+            assert!((vert.z - uv.y * 3.0).abs() < 1e-9);
+        }
+
+        // the seam and the open ends are kept
+        let seam = |mesh: &Mesh| {
+            mesh.verts
+                .iter()
+                .filter(|v| v.y.abs() < 1e-9 && v.x > 0.0)
+                .count()
+        };
+        assert_eq!(seam(&simple), seam(&tube));
+        let ends = |mesh: &Mesh| mesh.verts.iter().filter(|v| v.z.abs() < 1e-9).count();
+        assert!(ends(&simple) >= 3);
+
+        // an error budget stops early
+        let (bounded, error) = tube.decimate(0, 0.05);
+        assert!(error <= 0.05);
+        assert!(bounded.tri.len() > simple.tri.len());
+    }
+
+    #[test]
+    fn test_lod_chain() {
+        let chain = tube(32, 16).lod_chain(4);
+        assert_eq!(chain.len(), 4);
+        for pair in chain.windows(2) {
+            assert!(pair[1].tri.len() <= pair[0].tri.len() / 2 + 3);
+            assert!(!pair[1].tri.is_empty());
+        }
+
+        // a single triangle cannot be simplified
+        let triangle = Mesh::new_triangle([Vec3::ZERO, Vec3::X, Vec3::Y]);
+        assert_eq!(triangle.lod_chain(3).len(), 1);
+        assert!(triangle.lod_chain(0).is_empty());
+    }
+}
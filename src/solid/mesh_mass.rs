@@ -0,0 +1,309 @@
+use std::error::Error;
+use std::fmt;
+
+use super::Mesh;
+use crate::algos::signed_volume;
+use crate::core::Pose;
+use crate::kernel::{fxx, Mat3, Quat, Vec3, EPSILON};
+use crate::math::Range3;
+
+/// Why the mass properties of a mesh could not be computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MassError {
+    /// the mesh has holes, or edges used by more than two triangles, so it does not enclose a volume
+    NotClosed,
+    /// the mesh encloses a negative volume, its normals point inwards. Flip it first
+    InsideOut,
+    /// the mesh is empty, or flat
+    Degenerate,
+}
+
+impl Error for MassError {}
+
+impl fmt::Display for MassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MassError::NotClosed => write!(f, "Mass error: the mesh is not closed"),
+            MassError::InsideOut => write!(f, "Mass error: the mesh is oriented inwards"),
+            MassError::Degenerate => write!(f, "Mass error: the mesh does not enclose a volume"),
+        }
+    }
+}
+
+/// The mass properties of a closed mesh, at a uniform density of 1.
+/// Multiply `volume` and `inertia` by the density to get the mass and the actual inertia tensor.
+#[derive(Debug, Clone)]
+pub struct MassProperties {
+    pub volume: fxx,
+    pub area: fxx,
+    /// the center of mass
+    pub centroid: Vec3,
+    /// the inertia tensor, relative to the centroid
+    pub inertia: Mat3,
+    /// the moments of inertia around the principal axes, from small to large
+    pub principal_moments: Vec3,
+    /// located at the centroid, with its local x, y and z axes along the principal axes of `principal_moments`
+    pub principal_axes: Pose,
+}
+
+impl Mesh {
+    /// The total area of all triangles. Unlike the other mass properties, this works on open meshes too
+    pub fn area(&self) -> fxx {
+        self.iter_triangle_verts()
+            .map(|(a, b, c)| (b - a).cross(c - a).length() * 0.5)
+            .sum()
+    }
+
+    /// The enclosed volume. See [`Mesh::mass_properties`]
+    pub fn volume(&self) -> Result<fxx, MassError> {
+        self.mass_properties().map(|mass| mass.volume)
+    }
+
+    /// The center of mass of the enclosed volume. See [`Mesh::mass_properties`]
+    pub fn centroid(&self) -> Result<Vec3, MassError> {
+        self.mass_properties().map(|mass| mass.centroid)
+    }
+
+    /// The inertia tensor relative to the centroid, at a density of 1. See [`Mesh::mass_properties`]
+    pub fn inertia_tensor(&self) -> Result<Mat3, MassError> {
+        self.mass_properties().map(|mass| mass.inertia)
+    }
+
+    /// Compute the volume, area, centroid and inertia tensor of the enclosed volume,
+    /// exactly, by applying the divergence theorem to every triangle.
+    ///
+    /// The mesh must be closed, and oriented outwards. Seams in uvs or normals are fine.
+    /// Based on Polyhedral Mass Properties (Revisited), David Eberly
+    pub fn mass_properties(&self) -> Result<MassProperties, MassError> {
+        if !self.is_watertight() {
+            return Err(match self.tri.is_empty() {
+                true => MassError::Degenerate,
+                false => MassError::NotClosed,
+            });
+        }
+
+        // integrate relative to the center of the bounding box, to keep the products small
+        let origin = Range3::from_points(self.verts.iter().copied()).center();
+        let mut volume = 0.0;
+        let mut first = Vec3::ZERO;
+        let mut squares = Vec3::ZERO;
+        // the integrals of xy, yz and zx
+        let mut products = Vec3::ZERO;
+        for (a, b, c) in self.iter_triangle_verts() {
+            volume -= signed_volume(a, b, c, origin);
+            let (a, b, c) = (a - origin, b - origin, c - origin);
+            let normal = (b - a).cross(c - a);
+
+            let f1 = a + b + c;
+            let f2 = a * a + b * (a + b) + c * f1;
+            let f3 = a * a * a + b * (a * a + b * (a + b)) + c * f2;
+            let g = [a, b, c].map(|v| f2 + v * (f1 + v));
+
+            first += normal * f2;
+            squares += normal * f3;
+            products += normal
+                * Vec3::new(
+                    a.y * g[0].x + b.y * g[1].x + c.y * g[2].x,
+                    a.z * g[0].y + b.z * g[1].y + c.z * g[2].y,
+                    a.x * g[0].z + b.x * g[1].z + c.x * g[2].z,
+                );
+        }
+        let size = Range3::from_points(self.verts.iter().copied())
+            .size()
+            .max_element();
+        if volume < -EPSILON.sqrt() * size.powi(3) {
+            return Err(MassError::InsideOut);
+        }
+        if volume <= EPSILON.sqrt() * size.powi(3) {
+            return Err(MassError::Degenerate);
+        }
+        let (first, squares, products) = (first / 24.0, squares / 60.0, products / 120.0);
+
+        let center = first / volume;
+        let c = center;
+        let xx = squares.y + squares.z - volume * (c.y * c.y + c.z * c.z);
+        let yy = squares.z + squares.x - volume * (c.z * c.z + c.x * c.x);
+        let zz = squares.x + squares.y - volume * (c.x * c.x + c.y * c.y);
+        let xy = -(products.x - volume * c.x * c.y);
+        let yz = -(products.y - volume * c.y * c.z);
+        let zx = -(products.z - volume * c.z * c.x);
+        let inertia = Mat3::from_cols(
+            Vec3::new(xx, xy, zx),
+            Vec3::new(xy, yy, yz),
+            Vec3::new(zx, yz, zz),
+        );
+
+        let (principal_moments, axes) = symmetric_eigen(inertia);
+        let centroid = origin + center;
+        Ok(MassProperties {
+            volume,
+            area: self.area(),
+            centroid,
+            inertia,
+            principal_moments,
+            principal_axes: Pose {
+                pos: centroid,
+                rot: Quat::from_mat3(&axes).normalize(),
+            },
+        })
+    }
+}
+
+/// Eigenvalues and eigenvectors of a symmetric matrix, using cyclic Jacobi rotations.
+/// The eigenvalues are sorted from small to large, and the eigenvectors are the columns of a rotation matrix
+fn symmetric_eigen(matrix: Mat3) -> (Vec3, Mat3) {
+    let mut a = matrix.to_cols_array_2d();
+    let mut v = Mat3::IDENTITY.to_cols_array_2d();
+    let scale = matrix
+        .to_cols_array()
+        .iter()
+        .fold(0.0 as fxx, |m, x| m.max(x.abs()));
+    for _ in 0..32 {
+        let off = a[0][1].abs() + a[0][2].abs() + a[1][2].abs();
+        if off <= EPSILON * scale {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q].abs() <= EPSILON * EPSILON * scale {
+                continue;
+            }
+            // the rotation zeroing a[p][q]
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let cos = 1.0 / (t * t + 1.0).sqrt();
+            let sin = t * cos;
+            let rotate = |row: &mut [fxx; 3]| {
+                let (kp, kq) = (row[p], row[q]);
+                row[p] = cos * kp - sin * kq;
+                row[q] = sin * kp + cos * kq;
+            };
+            a.iter_mut().for_each(rotate);
+            let (ap, aq) = (a[p], a[q]);
+            a[p] = [0, 1, 2].map(|k| cos * ap[k] - sin * aq[k]);
+            a[q] = [0, 1, 2].map(|k| sin * ap[k] + cos * aq[k]);
+            v.iter_mut().for_each(rotate);
+        }
+    }
+
+    // `v` holds columns, but was rotated as rows. Read the eigenvectors from it transposed
+    let vectors = Mat3::from_cols_array_2d(&v).transpose();
+    let mut order = [0, 1, 2];
+    order.sort_by(|i, j| a[*i][*i].total_cmp(&a[*j][*j]));
+    let values = Vec3::new(
+        a[order[0]][order[0]],
+        a[order[1]][order[1]],
+        a[order[2]][order[2]],
+    );
+    let x = vectors.col(order[0]);
+    let y = vectors.col(order[1]);
+    // the third axis follows from the first two, keeping the result a proper rotation
+    let z = x.cross(y);
+    (values, Mat3::from_cols(x, y, z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MassError;
+    use crate::core::Geometry;
+    use crate::kernel::{vec3, Vec3};
+    use crate::math::Range3;
+    use crate::solid::test_fixtures::assert_close;
+    use crate::solid::{Mesh, Normals};
+
+    #[test]
+    fn test_mass_box() {
+        // a 2 x 4 x 6 box, away from the origin
+        let (w, h, d) = (2.0, 4.0, 6.0);
+        let from = vec3(10.0, -3.0, 5.0);
+        let mesh = Mesh::from_range(Range3::new(from, from + vec3(w, h, d)));
+        let mass = mesh.mass_properties().unwrap();
+
+        let volume = w * h * d;
+        assert_close(mass.volume, volume);
+        assert_close(mass.area, 2.0 * (w * h + h * d + d * w));
+        assert!(mass.centroid.distance(from + vec3(w, h, d) * 0.5) < 1e-6);
+
+        // a box is diagonal in its own frame
+        let expected = vec3(h * h + d * d, w * w + d * d, w * w + h * h) * volume / 12.0;
+        assert_close(mass.inertia.x_axis.x, expected.x);
+        assert_close(mass.inertia.y_axis.y, expected.y);
+        assert_close(mass.inertia.z_axis.z, expected.z);
+        assert_close(mass.inertia.x_axis.y, 0.0);
+        assert_close(mass.inertia.y_axis.z, 0.0);
+        assert_close(mass.inertia.z_axis.x, 0.0);
+
+        // the smallest moment is around the longest side
+        assert_close(mass.principal_moments.x, expected.z);
+        assert_close(mass.principal_moments.z, expected.x);
+        assert!(mass.principal_axes.local_x().dot(Vec3::Z).abs() > 0.999);
+        assert!(mass.principal_axes.local_z().dot(Vec3::X).abs() > 0.999);
+
+        assert_close(mesh.volume().unwrap(), volume);
+        assert!(mesh.centroid().unwrap().distance(mass.centroid) < 1e-9);
+    }
+
+    #[test]
+    fn test_mass_rotated() {
+        // principal axes follow the rotation of the object
+        let mesh = Mesh::from_range(Range3::new(Vec3::ZERO, vec3(1.0, 2.0, 4.0)))
+            .rot_z(0.4)
+            .rot_x(0.3)
+            .mv(vec3(1.0, 2.0, 3.0));
+        let mass = mesh.mass_properties().unwrap();
+        assert_close(mass.volume, 8.0);
+
+        // the longest side starts out along z
+        let long = Mesh::new(vec![Vec3::ZERO, Vec3::Z], vec![], vec![], Normals::None)
+            .rot_z(0.4)
+            .rot_x(0.3);
+        let long = long.verts[1] - long.verts[0];
+        let axis = mass.principal_axes.local_x();
+        assert!(
+            axis.dot(long.normalize()).abs() > 0.999,
+            "{:?} {:?}",
+            axis,
+            long
+        );
+
+        // the tensor, expressed in the principal frame, is diagonal
+        let rot = crate::kernel::Mat3::from_quat(mass.principal_axes.rot);
+        let diagonal = rot.transpose() * mass.inertia * rot;
+        assert_close(diagonal.x_axis.x, mass.principal_moments.x);
+        assert_close(diagonal.y_axis.y, mass.principal_moments.y);
+        assert_close(diagonal.z_axis.z, mass.principal_moments.z);
+        assert_close(diagonal.x_axis.y, 0.0);
+        assert_close(diagonal.x_axis.z, 0.0);
+        assert_close(diagonal.y_axis.z, 0.0);
+    }
+
+    #[test]
+    fn test_mass_icosahedron() {
+        // platonic solids have the same moment of inertia around every axis
+        let mesh = Mesh::new_icosahedron(1.0);
+        let mass = mesh.mass_properties().unwrap();
+        assert!(mass.centroid.length() < 1e-6);
+        let moments = mass.principal_moments;
+        assert_close(moments.x, moments.z);
+        assert_close(mass.inertia.x_axis.x, moments.y);
+
+        // moving the mesh only moves the centroid
+        let moved = mesh.mv(vec3(3.0, -2.0, 7.0)).mass_properties().unwrap();
+        assert!(moved.centroid.distance(vec3(3.0, -2.0, 7.0)) < 1e-6);
+        assert_close(moved.volume, mass.volume);
+        assert_close(moved.principal_moments.y, moments.y);
+    }
+
+    #[test]
+    fn test_mass_errors() {
+        let cube = Mesh::new_cube(1.0);
+        let mut tri = cube.tri.clone();
+        tri.truncate(tri.len() - 3);
+        let open = Mesh::new(cube.verts.clone(), tri, vec![], Normals::None);
+        assert_eq!(open.volume(), Err(MassError::NotClosed));
+        assert_eq!(cube.clone().flip().volume(), Err(MassError::InsideOut));
+        assert_eq!(Mesh::default().volume(), Err(MassError::Degenerate));
+
+        // area still works
+        assert_close(open.area(), 24.0 - 2.0);
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{Indexing, Mesh, Normals, TriCorner, TriMesh, WELD_TOLERANCE};
+use crate::kernel::{fxx, Vec2, Vec3};
+use crate::util::{compact, join, OneOrMany};
+
+impl Mesh {
+    /// Smooth vertex normals, which stay sharp where the normals of two triangles differ more than `angle`, in radians.
+    ///
+    /// The triangles around a vertex fall apart into smoothing groups, separated by those creases and by naked edges.
+    /// Vertices are split, one copy per group, and every copy gets the normals of its group, weighted by corner angle.
+    pub fn with_auto_smooth_normals(self, angle: fxx) -> Self {
+        self.with_auto_smooth_normals_and_hard_edges(angle, &[])
+    }
+
+    /// Like [`Mesh::with_auto_smooth_normals`], but the `hard_edges`, as pairs of vertex indices, are always kept sharp
+    pub fn with_auto_smooth_normals_and_hard_edges(
+        self,
+        angle: fxx,
+        hard_edges: &[(usize, usize)],
+    ) -> Self {
+        let (groups, normals) = smoothing_groups(&self.verts, &self.tri, angle, hard_edges);
+        let has_uvs = self.uvs.len() == self.verts.len();
+        let corner_uvs = match has_uvs {
+            true => self.tri.iter().map(|v| self.uvs[*v]).collect(),
+            false => vec![Vec2::ZERO; self.tri.len()],
+        };
+        let mut mesh = self.split_corners(&corner_uvs, &groups);
+        if !has_uvs {
+            mesh.uvs = Vec::new();
+        }
+
+        let mut vert_normals = vec![Vec3::ZERO; mesh.verts.len()];
+        for (v, group) in mesh.tri.iter().zip(groups.iter()) {
+            vert_normals[*v] = normals[*group];
+        }
+        mesh.with_normals(Normals::Vertex(vert_normals))
+    }
+}
+
+impl TriMesh {
+    /// Like [`Mesh::with_auto_smooth_normals`]. Positions are not duplicated:
+    /// the mesh turns hetero, and every corner points to the normal of its smoothing group
+    pub fn with_auto_smooth_normals(self, angle: fxx) -> Self {
+        self.with_auto_smooth_normals_and_hard_edges(angle, &[])
+    }
+
+    /// Like [`Mesh::with_auto_smooth_normals_and_hard_edges`], producing hetero indexing
+    pub fn with_auto_smooth_normals_and_hard_edges(
+        mut self,
+        angle: fxx,
+        hard_edges: &[(usize, usize)],
+    ) -> Self {
+        let corners = self
+            .iter_triangles_hetero()
+            .flat_map(|(a, b, c)| [a, b, c])
+            .collect::<Vec<_>>();
+        let tri = corners.iter().map(|c| c.v).collect::<Vec<_>>();
+        let (groups, normals) = smoothing_groups(&self.verts, &tri, angle, hard_edges);
+        let hetero = corners
+            .into_iter()
+            .zip(groups)
+            .map(|(corner, group)| TriCorner::new(corner.v, corner.uv, group))
+            .collect();
+        self.tri = Indexing::Hetero(hetero);
+        self.normals = OneOrMany::Many(normals);
+        self
+    }
+}
+
+/// The smoothing group of every triangle corner, and the angle weighted normal of every group.
+/// Corners at the same position share a group, unless creases, hard edges or naked edges separate them
+fn smoothing_groups(
+    verts: &[Vec3],
+    tri: &[usize],
+    angle: fxx,
+    hard_edges: &[(usize, usize)],
+) -> (Vec<usize>, Vec<Vec3>) {
+    // positions are welded, so that uv seams do not show up as hard edges
+    let root = TriMesh::weld_roots(verts, WELD_TOLERANCE);
+    let key = |a: usize, b: usize| {
+        let (a, b) = (root[a], root[b]);
+        (a.min(b), a.max(b))
+    };
+    let hard = hard_edges
+        .iter()
+        .map(|(a, b)| key(*a, *b))
+        .collect::<HashSet<_>>();
+
+    let tri_count = tri.len() / 3;
+    let face_normals = (0..tri_count)
+        .map(|t| {
+            let [a, b, c] = [0, 1, 2].map(|k| verts[tri[t * 3 + k]]);
+            (b - a).cross(c - a).try_normalize()
+        })
+        .collect::<Vec<_>>();
+    let mut edges = HashMap::<(usize, usize), Vec<usize>>::new();
+    for t in 0..tri_count {
+        for k in 0..3 {
+            let edge = key(tri[t * 3 + k], tri[t * 3 + (k + 1) % 3]);
+            edges.entry(edge).or_default().push(t);
+        }
+    }
+
+    // join the corners on both ends of every smooth edge.
+    // Naked and non-manifold edges stay hard, and so do the edges of triangles without a normal
+    let mut sets = (0..tri.len()).collect::<Vec<_>>();
+    for (edge, tris) in edges.iter() {
+        let [t1, t2] = tris.as_slice() else {
+            continue;
+        };
+        if t1 == t2 || hard.contains(edge) {
+            continue;
+        }
+        let (Some(n1), Some(n2)) = (face_normals[*t1], face_normals[*t2]) else {
+            continue;
+        };
+        if n1.dot(n2).clamp(-1.0, 1.0).acos() > angle {
+            continue;
+        }
+        for end in [edge.0, edge.1] {
+            let corner = |t: usize| (t * 3..t * 3 + 3).find(|c| root[tri[*c]] == end);
+            if let (Some(c1), Some(c2)) = (corner(*t1), corner(*t2)) {
+                join(&mut sets, c1, c2);
+            }
+        }
+    }
+    let (groups, count) = compact(&mut sets);
+
+    let mut normals = vec![Vec3::ZERO; count];
+    for (t, normal) in face_normals.iter().enumerate() {
+        let Some(normal) = normal else {
+            continue;
+        };
+        for k in 0..3 {
+            let [v, next, prev] = [k, k + 1, k + 2].map(|i| verts[tri[t * 3 + i % 3]]);
+            let weight = (next - v).angle_between(prev - v);
+            if weight.is_finite() {
+                normals[groups[t * 3 + k]] += *normal * weight;
+            }
+        }
+    }
+    let normals = normals.into_iter().map(|n| n.normalize_or_zero()).collect();
+    (groups, normals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::PI;
+    use crate::solid::test_fixtures::hull_cube;
+
+    /// The normal of every vertex of every triangle
+    fn corner_normals(mesh: &Mesh) -> Vec<Vec3> {
+        let normals = mesh.get_normals().unwrap();
+        mesh.tri.iter().map(|v| normals[*v]).collect()
+    }
+
+    #[test]
+    fn test_auto_smooth_cube() {
+        let cube = hull_cube();
+        assert_eq!(cube.verts.len(), 8);
+
+        // every side gets its own vertices, with flat normals
+        let sharp = cube.clone().with_auto_smooth_normals(PI / 6.0);
+        assert_eq!(sharp.verts.len(), 24);
+        let faces = sharp.calc_flat_face_normals();
+        for (t, normal) in corner_normals(&sharp).iter().enumerate() {
+            assert!((*normal - faces[t / 3]).length() < 1e-6);
+        }
+
+        // a large angle smooths everything, and the corners point outwards
+        let smooth = cube.with_auto_smooth_normals(PI * 0.75);
+        assert_eq!(smooth.verts.len(), 8);
+        for (vert, normal) in smooth.verts.iter().zip(smooth.get_normals().unwrap()) {
+            assert!((vert.normalize() - *normal).length() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_hard_edges() {
+        // only the rim of the top side is hard
+        let cube = hull_cube();
+        let top = |v: usize| cube.verts[v].z > 0.0;
+        let rim = cube
+            .iter_edges()
+            .filter(|(a, b)| top(*a) && top(*b))
+            .filter(|(a, b)| (cube.verts[*a] - cube.verts[*b]).length() < 2.1)
+            .collect::<Vec<_>>();
+        let mesh = cube
+            .clone()
+            .with_auto_smooth_normals_and_hard_edges(PI * 0.75, &rim);
+        assert_eq!(mesh.verts.len(), 12);
+        let faces = mesh.calc_flat_face_normals();
+        for (t, normal) in corner_normals(&mesh).iter().enumerate() {
+            if faces[t / 3].z > 0.99 {
+                assert!((*normal - Vec3::Z).length() < 1e-6);
+            } else {
+                assert!(normal.z < 0.99);
+            }
+        }
+    }
+
+    #[test]
+    fn test_auto_smooth_tri_mesh() {
+        let soup = TriMesh::new_linear(
+            hull_cube()
+                .iter_triangle_verts()
+                .flat_map(|(a, b, c)| [a, b, c])
+                .collect(),
+        );
+        let mesh = soup.with_auto_smooth_normals(PI / 6.0);
+        assert_eq!(mesh.verts.len(), 36);
+        let OneOrMany::Many(normals) = &mesh.normals else {
+            panic!("expected a normal per smoothing group");
+        };
+        assert_eq!(normals.len(), 24);
+        let faces = mesh.calc_flat_face_normals();
+        for (t, (a, b, c)) in mesh.iter_triangles_hetero().enumerate() {
+            for corner in [a, b, c] {
+                assert!((normals[corner.n] - faces[t]).length() < 1e-6);
+            }
+        }
+    }
+}
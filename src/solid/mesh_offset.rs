@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use super::{Mesh, Normals, TriMesh, WELD_TOLERANCE};
+use crate::kernel::{fxx, Vec3};
+
+/// Vertices are moved at most `1 / OFFSET_MIN_COS` times the distance, to keep very sharp spikes in check
+const OFFSET_MIN_COS: fxx = 0.25;
+
+impl Mesh {
+    /// Move every vertex `distance` along its angle weighted vertex normal.
+    /// Negative distances move against the normals.
+    ///
+    /// At creases and corners vertices move a bit further, so that every adjacent face ends up `distance` away.
+    /// This makes the offset of a box an exact, larger box. Vertices are welded by position first, so seams move as one.
+    pub fn offset(&self, distance: fxx) -> Mesh {
+        let mut mesh = self.clone();
+        mesh.verts = self.offset_verts(distance);
+        mesh
+    }
+
+    /// Thicken the mesh into a solid, by adding an offset copy `thickness` away from it,
+    /// flipping whichever copy ends up on the inside, and closing the naked boundaries with walls.
+    /// Positive thickness grows along the normals, negative thickness against them.
+    ///
+    /// A closed mesh becomes a hollow solid, and a flat patch becomes a slab.
+    /// The walls take the triangle attributes of the boundary triangles they are built along.
+    pub fn shell(&self, thickness: fxx) -> Mesh {
+        let (position, triangles) = self.weld_for_offset();
+        let mut offset = self.clone();
+        offset.verts = self.offset_verts(thickness);
+        let (outer, inner) = match thickness >= 0.0 {
+            true => (offset, self.clone()),
+            false => (self.clone(), offset),
+        };
+        let count = self.verts.len();
+        let mut mesh = Mesh::from_join(vec![outer, inner.flip()]);
+
+        // the walls run along the welded boundaries, against the direction of the outer triangles
+        let mut directed = HashMap::new();
+        for (t, (a, b, c)) in self.iter_triangles().enumerate() {
+            let [a, b, c] = [a, b, c].map(|v| position[v]);
+            if a != b && b != c && c != a {
+                directed.extend([((a, b), t), ((b, c), t), ((c, a), t)]);
+            }
+        }
+        let welded = Mesh::new(
+            self.verts.clone(),
+            triangles.concat(),
+            Vec::new(),
+            Normals::None,
+        );
+        let mut naked = welded.iter_naked_edges().collect::<Vec<_>>();
+        naked.sort();
+        let mut sources = Vec::new();
+        for chain in Mesh::aggregate_edges(naked.into_iter()) {
+            for pair in chain.windows(2) {
+                let (a, b, t) = match directed.get(&(pair[0], pair[1])) {
+                    Some(t) => (pair[0], pair[1], *t),
+                    None => (pair[1], pair[0], directed[&(pair[1], pair[0])]),
+                };
+                mesh.tri.extend([b, a, a + count, a + count, b + count, b]);
+                sources.extend([t, t]);
+            }
+        }
+        let walls = self.tri_attributes.select(self.count_triangles(), &sources);
+        let start = mesh.count_triangles() - sources.len();
+        mesh.tri_attributes.append(start, &walls, sources.len());
+
+        match self.normals {
+            Normals::None => mesh,
+            Normals::Vertex(_) => mesh.with_vertex_normals(),
+            Normals::Face(_) => mesh.with_face_normals(),
+        }
+    }
+
+    /// Every vertex, moved `distance` along the angle weighted normal of its welded position
+    fn offset_verts(&self, distance: fxx) -> Vec<Vec3> {
+        let (position, triangles) = self.weld_for_offset();
+        let mut normals = vec![Vec3::ZERO; self.verts.len()];
+        let mut faces = vec![Vec::new(); self.verts.len()];
+        for [a, b, c] in triangles {
+            let [pa, pb, pc] = [a, b, c].map(|v| self.verts[v]);
+            let normal = (pb - pa).cross(pc - pa).normalize_or_zero();
+            if normal == Vec3::ZERO {
+                continue;
+            }
+            for (v, p, q) in [(a, pb, pc), (b, pc, pa), (c, pa, pb)] {
+                let vert = self.verts[v];
+                normals[v] += normal * (p - vert).angle_between(q - vert);
+                faces[v].push(normal);
+            }
+        }
+
+        let offsets = normals
+            .iter()
+            .zip(faces.iter())
+            .map(|(normal, faces)| {
+                let normal = normal.normalize_or_zero();
+                let cos = faces
+                    .iter()
+                    .map(|face| face.dot(normal))
+                    .fold(1.0, fxx::min)
+                    .max(OFFSET_MIN_COS);
+                normal * distance / cos
+            })
+            .collect::<Vec<_>>();
+        position
+            .iter()
+            .enumerate()
+            .map(|(v, p)| self.verts[v] + offsets[*p])
+            .collect()
+    }
+
+    /// The welded position of every vertex, and the triangles between welded positions which are not degenerate
+    fn weld_for_offset(&self) -> (Vec<usize>, Vec<[usize; 3]>) {
+        let position = TriMesh::weld_roots(&self.verts, WELD_TOLERANCE);
+        let triangles = self
+            .iter_triangles()
+            .map(|(a, b, c)| [a, b, c].map(|v| position[v]))
+            .filter(|[a, b, c]| a != b && b != c && c != a)
+            .collect();
+        (position, triangles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kernel::{vec3, Vec3};
+    use crate::solid::Mesh;
+
+    #[test]
+    fn test_offset_cube() {
+        // corners move diagonally, so the faces stay flat
+        let cube = Mesh::new_cube(1.0);
+        let grown = cube.offset(0.5);
+        for vert in grown.verts.iter() {
+            assert!((vert.abs() - Vec3::splat(1.5)).length() < 1e-6);
+        }
+        assert_eq!(grown.uvs, cube.uvs);
+        let shrunk = cube.offset(-0.25);
+        assert!((shrunk.volume().unwrap() - 1.5 * 1.5 * 1.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_offset_sphere() {
+        let sphere = Mesh::new_icosahedron(1.0).loop_subdivide(2, &[]);
+        let grown = sphere.offset(0.1);
+        for (a, b) in sphere.verts.iter().zip(grown.verts.iter()) {
+            let grow = b.length() - a.length();
+            assert!((grow - 0.1).abs() < 0.01, "{grow}");
+        }
+    }
+
+    #[test]
+    fn test_shell_patch() {
+        // a flat patch becomes a closed slab, either way
+        let grid = Mesh::new_hexagrid(1.0, 2);
+        let area = grid.area();
+        assert!(grid.calc_flat_face_normals().iter().all(|n| n.z > 0.0));
+        for thickness in [0.2, -0.2] {
+            let slab = grid.shell(thickness);
+            assert!(slab.is_watertight());
+            assert!((slab.volume().unwrap() - area * 0.2).abs() < 1e-4);
+            let z = slab.verts.iter().map(|v| v.z).fold(0.0, |a, b| a + b);
+            assert!(z * thickness > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_shell_materials() {
+        // the walls take the material of the boundary triangle they run along
+        let grid = Mesh::new_hexagrid(1.0, 2);
+        let ids = (0..grid.count_triangles()).collect::<Vec<_>>();
+        let slab = grid.clone().with_material_ids(ids).shell(0.2);
+        assert!(slab.validate().is_valid());
+        let materials = slab.tri_attributes.materials().unwrap();
+        assert_eq!(materials.len(), slab.count_triangles());
+        let walls = grid.count_triangles() * 2;
+        for (t, material) in materials.iter().enumerate().skip(walls) {
+            let wall = slab.tri[t * 3..t * 3 + 3].iter().map(|v| slab.verts[*v]);
+            let source = grid.tri[material * 3..material * 3 + 3]
+                .iter()
+                .map(|v| grid.verts[*v])
+                .collect::<Vec<_>>();
+            // the wall touches its source triangle, at the bottom of the slab
+            let shared = wall.filter(|v| source.iter().any(|s| s.distance(*v) < 1e-9));
+            assert!(shared.count() >= 1);
+        }
+    }
+
+    #[test]
+    fn test_shell_closed() {
+        // a closed cube becomes a hollow one, without walls
+        let cube = Mesh::new_cube(1.0);
+        let hollow = cube.shell(-0.2);
+        assert_eq!(hollow.tri.len(), cube.tri.len() * 2);
+        assert!((hollow.volume().unwrap() - (8.0 - 1.6 * 1.6 * 1.6)).abs() < 1e-4);
+        assert!(!hollow.contains(Vec3::ZERO));
+        assert!(hollow.contains(vec3(0.9, 0.0, 0.0)));
+    }
+}
@@ -0,0 +1,215 @@
+use std::cmp::Ordering;
+
+use super::{Mesh, Normals, TriMesh, WELD_TOLERANCE};
+use crate::algos::{earcut_3d, polygon_contains, signed_area};
+use crate::core::Plane;
+use crate::kernel::Vec3;
+use crate::lines::Polyline;
+
+impl Mesh {
+    /// Split the mesh in two, and close both halves with a cap on the cutting plane.
+    /// Returns the part below the plane (opposite to its normal) first, and the part above it second.
+    ///
+    /// In contrast to [`Mesh::split`], uvs and normals are kept as well as the attributes.
+    /// Caps are triangulated using earcut, so section loops nested within other loops become holes, and islands within those holes become caps again.
+    /// Cap uvs are the plane coordinates of the cap vertices, so both halves get matching cap uvs.
+    ///
+    /// The mesh should be closed and oriented outwards for the result to be watertight.
+    /// Sections through open parts of the mesh are not capped.
+    pub fn split_capped(&self, cutting_plane: impl Into<Plane>) -> (Mesh, Mesh) {
+        let plane: Plane = cutting_plane.into();
+        let normal = plane.normal();
+
+        let cut = self.clone().plane_loop_cut(plane.clone());
+        let mut below = Vec::new();
+        let mut above = Vec::new();
+        for (i, (a, b, c)) in cut.iter_triangles().enumerate() {
+            let (a, b, c) = (cut.verts[a], cut.verts[b], cut.verts[c]);
+            match plane.half_plane_test((a + b + c) / 3.0) {
+                Ordering::Less => below.push(i),
+                Ordering::Greater => above.push(i),
+                // a face on the plane bounds the half it faces away from
+                Ordering::Equal if (b - a).cross(c - a).dot(normal) > 0.0 => below.push(i),
+                Ordering::Equal => above.push(i),
+            }
+        }
+
+        let caps = self.section_caps(&plane);
+        let mut below = cut.select_triangles(&below);
+        let mut above = cut.select_triangles(&above);
+        for (verts, tri) in caps.iter() {
+            below.append_cap(&plane, verts, tri, normal);
+            above.append_cap(&plane, verts, tri, -normal);
+        }
+        (below, above)
+    }
+
+    /// Triangulated faces filling the closed sections of the plane and this mesh.
+    /// Returns the vertices and triangles of every cap, oriented along the plane normal
+    fn section_caps(&self, plane: &Plane) -> Vec<(Vec<Vec3>, Vec<usize>)> {
+        // uv and normal seams would break the sections into open pieces, so only look at positions
+        let root = TriMesh::weld_roots(&self.verts, WELD_TOLERANCE);
+        let topology = Mesh::new(
+            self.verts.clone(),
+            self.tri.iter().map(|i| root[*i]).collect(),
+            Vec::new(),
+            Normals::None,
+        );
+        let loops = topology
+            .intersect(plane.clone())
+            .into_iter()
+            .filter_map(|section| match section {
+                Polyline::Closed(verts) if verts.len() > 2 => Some(verts),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let flat = loops
+            .iter()
+            .map(|verts| {
+                verts
+                    .iter()
+                    .map(|v| plane.point_to_plane(*v).truncate())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // a loop contained by an even number of other loops is an outer loop, an odd number makes it a hole.
+        // A hole belongs to the smallest loop containing it
+        let containers = (0..flat.len())
+            .map(|i| {
+                (0..flat.len())
+                    .filter(|j| *j != i && polygon_contains(&flat[*j], flat[i][0]))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let area = |i: usize| signed_area(&flat[i]).abs();
+
+        let mut caps = Vec::new();
+        for outer in (0..loops.len()).filter(|i| containers[*i].len() % 2 == 0) {
+            let holes = (0..loops.len()).filter(|i| {
+                containers[*i].len() == containers[outer].len() + 1
+                    && containers[*i]
+                        .iter()
+                        .filter(|j| containers[**j].len() == containers[outer].len())
+                        .min_by(|a, b| area(**a).total_cmp(&area(**b)))
+                        == Some(&outer)
+            });
+
+            let mut verts = loops[outer].clone();
+            let mut hole_starts = Vec::new();
+            for hole in holes {
+                hole_starts.push(verts.len());
+                verts.extend(loops[hole].iter());
+            }
+            let Some(mut tri) = earcut_3d(&verts, &hole_starts, plane) else {
+                continue;
+            };
+            for t in tri.chunks_mut(3) {
+                let (a, b, c) = (verts[t[0]], verts[t[1]], verts[t[2]]);
+                if (b - a).cross(c - a).dot(plane.normal()) < 0.0 {
+                    t.swap(0, 1);
+                }
+            }
+            caps.push((verts, tri));
+        }
+        caps
+    }
+
+    /// Add a cap facing `normal`, giving it planar uvs, flat normals and padded vertex and triangle attributes
+    fn append_cap(&mut self, plane: &Plane, verts: &[Vec3], tri: &[usize], normal: Vec3) {
+        let count = self.verts.len();
+        let has_uvs = !self.uvs.is_empty() && self.uvs.len() == count;
+        let flip = normal.dot(plane.normal()) < 0.0;
+
+        self.verts.extend(verts.iter());
+        if has_uvs {
+            self.uvs
+                .extend(verts.iter().map(|v| plane.point_to_plane(*v).truncate()));
+        }
+        for t in tri.chunks(3) {
+            if flip {
+                self.tri.extend([count + t[1], count + t[0], count + t[2]]);
+            } else {
+                self.tri.extend([count + t[0], count + t[1], count + t[2]]);
+            }
+        }
+        match &mut self.normals {
+            Normals::Vertex(normals) => normals.extend(verts.iter().map(|_| normal)),
+            Normals::Face(normals) => normals.extend(tri.chunks(3).map(|_| normal)),
+            Normals::None => (),
+        }
+        self.vert_attributes.resize(self.verts.len());
+        self.tri_attributes.resize(self.tri.len() / 3);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::Plane;
+    use crate::kernel::{fxx, vec3, Vec3};
+    use crate::solid::{Attribute, Mesh, Normals};
+
+    #[test]
+    fn test_split_capped() {
+        let cube = Mesh::new_cube(1.0).with_material_ids(vec![3; 12]);
+        let plane = Plane::from_pos_normal(vec3(0.0, 0.0, 0.5), Vec3::Z);
+        let (below, above) = cube.split_capped(plane);
+
+        for half in [&below, &above] {
+            assert!(half.is_watertight());
+            assert_eq!(half.uvs.len(), half.verts.len());
+            assert_eq!(half.get_normals().unwrap().len(), half.verts.len());
+
+            // the cap has no material
+            let materials = half.tri_attributes.materials().unwrap();
+            for ((a, b, c), material) in half.iter_triangle_verts().zip(materials.iter()) {
+                let is_cap = [a, b, c].iter().all(|v| (v.z - 0.5).abs() < 1e-6);
+                let expected = if is_cap { Attribute::NO_INDEX } else { 3 };
+                assert_eq!(*material, expected);
+            }
+        }
+        assert!(below.verts.iter().all(|v| v.z < 0.5 + 1e-6));
+        assert!(above.verts.iter().all(|v| v.z > 0.5 - 1e-6));
+
+        // orientation is kept, so volumes add up
+        let full = cube.volume().unwrap();
+        assert!((below.volume().unwrap() - full * 0.75).abs() < 1e-4);
+        assert!((above.volume().unwrap() - full * 0.25).abs() < 1e-4);
+
+        // the halves can be split again
+        let (left, right) =
+            below.split_capped(Plane::from_pos_normal(vec3(0.3, 0.0, 0.0), Vec3::X));
+        assert!(left.is_watertight() && right.is_watertight());
+        assert!(
+            (left.volume().unwrap() + right.volume().unwrap() - below.volume().unwrap()).abs()
+                < 1e-4
+        );
+    }
+
+    #[test]
+    fn test_split_capped_holes() {
+        // a hollow cube, with a smaller cube shaped cavity
+        let outer = Mesh::new_cube(1.0);
+        let inner = Mesh::new_cube(0.5).flip();
+        let outer = Mesh::new(outer.verts, outer.tri, vec![], Normals::None);
+        let inner = Mesh::new(inner.verts, inner.tri, vec![], Normals::None);
+        let hollow = Mesh::from_join(vec![outer, inner]);
+
+        let (below, above) = hollow.split_capped(Plane::WORLD_XY);
+        for half in [&below, &above] {
+            assert!(half.is_watertight());
+        }
+        let full = hollow.volume().unwrap();
+        assert!((full - (8.0 - 1.0)).abs() < 1e-4);
+        assert!((below.volume().unwrap() - full * 0.5).abs() < 1e-4);
+        assert!((above.volume().unwrap() - full * 0.5).abs() < 1e-4);
+
+        // the cap is a square ring
+        let cap_area: fxx = below
+            .iter_triangle_verts()
+            .filter(|(a, b, c)| a.z.abs() < 1e-6 && b.z.abs() < 1e-6 && c.z.abs() < 1e-6)
+            .map(|(a, b, c)| (b - a).cross(c - a).length() * 0.5)
+            .sum();
+        assert!((cap_area - (4.0 - 1.0)).abs() < 1e-4);
+    }
+}
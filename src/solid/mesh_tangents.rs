@@ -0,0 +1,209 @@
+use super::{Attribute, Attributes, Mesh, Normals};
+use crate::kernel::{fxx, Vec2, Vec3, Vec4, EPSILON};
+
+impl Mesh {
+    /// Compute a tangent for every vertex, for normal mapping, and store it as [`Attributes::TANGENT`].
+    /// The tangent points along the u direction, and w holds the sign of the bitangent,
+    /// so that `bitangent = normal.cross(tangent.xyz) * tangent.w`, as expected by bevy and glTF.
+    ///
+    /// MikkTSpace-style: the tangents of the triangles around a vertex are projected onto its normal,
+    /// and averaged, weighted by the angle of every corner.
+    /// Vertices used by both mirrored and unmirrored triangles in uv space are split, so every copy gets its own frame.
+    /// Uv seams already have separate vertices, and keep their own frames as well.
+    ///
+    /// The vertex normals are used when present. Otherwise, the frames are built around smooth normals, which are not stored.
+    /// A mesh without uvs gets an arbitrary tangent perpendicular to every normal, and stays without uvs.
+    pub fn with_tangents(self) -> Self {
+        let count = self.verts.len();
+        let has_uvs = self.uvs.len() == count;
+        let uvs = match has_uvs {
+            true => self.uvs.clone(),
+            false => vec![Vec2::ZERO; count],
+        };
+        let original = self.normals.clone();
+        let normals = match &self.normals {
+            Normals::Vertex(normals) if normals.len() == count => normals.clone(),
+            _ => self.calc_vertex_normals(),
+        };
+
+        // mirrored triangles cannot share a vertex with unmirrored ones.
+        // Triangles without any uv area go along with the other triangles around their vertices
+        let mirrored = self
+            .iter_triangles()
+            .map(|(a, b, c)| {
+                let area = (uvs[b] - uvs[a]).perp_dot(uvs[c] - uvs[a]);
+                (area.abs() > EPSILON).then_some(area < 0.0)
+            })
+            .collect::<Vec<_>>();
+        let mut vert_mirrored = vec![None; count];
+        for ((a, b, c), mirrored) in self.iter_triangles().zip(mirrored.iter()) {
+            for v in [a, b, c] {
+                vert_mirrored[v] = vert_mirrored[v].or(*mirrored);
+            }
+        }
+        let groups = self
+            .iter_triangles()
+            .zip(mirrored.iter())
+            .flat_map(|((a, b, c), mirrored)| {
+                [a, b, c].map(|v| mirrored.or(vert_mirrored[v]).unwrap_or(false) as usize)
+            })
+            .collect::<Vec<_>>();
+        let corner_uvs = self.tri.iter().map(|v| uvs[*v]).collect::<Vec<_>>();
+        let mut mesh = self
+            .with_normals(Normals::Vertex(normals))
+            .split_corners(&corner_uvs, &groups);
+
+        let normals = mesh.get_normals().cloned().unwrap_or_default();
+        let mut sums = vec![Vec3::ZERO; mesh.verts.len()];
+        for (a, b, c) in mesh.iter_triangles() {
+            let Some(tangent) = triangle_tangent(
+                [a, b, c].map(|v| mesh.verts[v]),
+                [a, b, c].map(|v| mesh.uvs[v]),
+            ) else {
+                continue;
+            };
+            for (v, next, prev) in [(a, b, c), (b, c, a), (c, a, b)] {
+                let normal = normals[v];
+                let project = |d: Vec3| (d - normal * d.dot(normal)).normalize_or_zero();
+                let angle = project(mesh.verts[next] - mesh.verts[v])
+                    .angle_between(project(mesh.verts[prev] - mesh.verts[v]));
+                if angle.is_finite() {
+                    sums[v] += project(tangent) * angle;
+                }
+            }
+        }
+
+        let tangents = sums
+            .iter()
+            .zip(normals.iter())
+            .zip(mirrored_verts(&mesh, &groups))
+            .map(|((sum, normal), mirrored)| {
+                let tangent = (*sum - *normal * sum.dot(*normal))
+                    .try_normalize()
+                    .unwrap_or_else(|| normal.any_orthonormal_vector());
+                let sign: fxx = match mirrored {
+                    true => -1.0,
+                    false => 1.0,
+                };
+                tangent.extend(sign)
+            })
+            .collect::<Vec<Vec4>>();
+
+        if !matches!(original, Normals::Vertex(_)) {
+            mesh.normals = original;
+        }
+        if !has_uvs {
+            mesh.uvs = Vec::new();
+        }
+        mesh.with_vert_attribute(Attributes::TANGENT, Attribute::Vec4(tangents))
+    }
+}
+
+/// The direction in which u grows across a triangle, or nothing if its uvs have no area
+fn triangle_tangent(corners: [Vec3; 3], uvs: [Vec2; 3]) -> Option<Vec3> {
+    let (e1, e2) = (corners[1] - corners[0], corners[2] - corners[0]);
+    let (d1, d2) = (uvs[1] - uvs[0], uvs[2] - uvs[0]);
+    let area = d1.perp_dot(d2);
+    if area.abs() <= EPSILON {
+        return None;
+    }
+    ((e1 * d2.y - e2 * d1.y) * area.signum()).try_normalize()
+}
+
+/// Whether every vertex is used by mirrored triangles, after splitting
+fn mirrored_verts(mesh: &Mesh, groups: &[usize]) -> Vec<bool> {
+    let mut mirrored = vec![false; mesh.verts.len()];
+    for (v, group) in mesh.tri.iter().zip(groups) {
+        mirrored[*v] = *group == 1;
+    }
+    mirrored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Pose;
+    use crate::kernel::{vec2, vec3};
+    use crate::solid::Projection;
+
+    #[test]
+    fn test_tangents_planar() {
+        let grid = Mesh::new_hexagrid(1.0, 3)
+            .with_uvs_projected(&Projection::planar(Pose::default()))
+            .with_vertex_normals();
+        let count = grid.verts.len();
+        let grid = grid.with_tangents();
+        assert_eq!(grid.verts.len(), count);
+        let tangents = grid.vert_attributes.tangents().unwrap();
+        assert_eq!(tangents.len(), count);
+        for tangent in tangents.iter() {
+            assert!((*tangent - Vec4::new(1.0, 0.0, 0.0, 1.0)).length() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_tangents_mirrored() {
+        // two quads sharing an edge, with the texture mirrored across it
+        let verts = vec![
+            vec3(-1.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(-1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+        ];
+        let tri = vec![0, 1, 4, 4, 3, 0, 1, 2, 5, 5, 4, 1];
+        let uvs = vec![
+            vec2(0.0, 0.0),
+            vec2(1.0, 0.0),
+            vec2(0.0, 0.0),
+            vec2(0.0, 1.0),
+            vec2(1.0, 1.0),
+            vec2(0.0, 1.0),
+        ];
+        let mesh = Mesh::new(verts, tri, uvs, Normals::None).with_tangents();
+
+        // the two vertices on the mirror line are split
+        assert_eq!(mesh.verts.len(), 8);
+        assert!(matches!(mesh.normals, Normals::None));
+        let tangents = mesh.vert_attributes.tangents().unwrap();
+        for ((a, b, c), t) in mesh.iter_triangles().zip([1.0, 1.0, -1.0, -1.0]) {
+            for v in [a, b, c] {
+                let expected = Vec4::new(t, 0.0, 0.0, t);
+                assert!((tangents[v] - expected).length() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tangents_without_uvs() {
+        let mesh = Mesh::new_icosahedron(1.0).with_vertex_normals();
+        let count = mesh.verts.len();
+        let mesh = mesh.with_tangents();
+        assert!(mesh.uvs.is_empty());
+        assert_eq!(mesh.verts.len(), count);
+        let normals = mesh.get_normals().unwrap();
+        let tangents = mesh.vert_attributes.tangents().unwrap();
+        assert_eq!(tangents.len(), mesh.verts.len());
+        for (normal, tangent) in normals.iter().zip(tangents.iter()) {
+            assert!(tangent.truncate().dot(*normal).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_tangents_sphere() {
+        let sphere = Mesh::new_icosahedron(1.0)
+            .loop_subdivide(2, &[])
+            .with_vertex_normals()
+            .with_uvs_projected(&Projection::spherical(Pose::default()))
+            .with_tangents();
+        let normals = sphere.get_normals().unwrap();
+        let tangents = sphere.vert_attributes.tangents().unwrap();
+        assert_eq!(tangents.len(), sphere.verts.len());
+        for (normal, tangent) in normals.iter().zip(tangents.iter()) {
+            assert!((tangent.truncate().length() - 1.0).abs() < 1e-6);
+            assert!(tangent.truncate().dot(*normal).abs() < 1e-6);
+            assert_eq!(tangent.w, 1.0);
+        }
+    }
+}
@@ -0,0 +1,910 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use super::{Mesh, TriMesh, WELD_TOLERANCE};
+use crate::kernel::{fxx, vec2, Vec2, Vec3, EPSILON, INFINITY, PI};
+use crate::util::{compact, join};
+
+/// Triangles smaller than this do not take part in the conformal energy, or in the distortion statistics
+const UNWRAP_AREA_TOLERANCE: fxx = 1e-12;
+
+/// The settings of [`Mesh::to_unwrapped`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnwrapOptions {
+    /// Edges between triangles whose normals differ more than this angle, in radians, become seams
+    pub crease_angle: Option<fxx>,
+    /// Edges to cut the mesh along, as pairs of vertex indices
+    pub seams: Vec<(usize, usize)>,
+    /// The space kept free around every chart, as a fraction of the unit square
+    pub margin: fxx,
+}
+
+impl Default for UnwrapOptions {
+    fn default() -> Self {
+        Self {
+            crease_angle: Some(PI / 3.0),
+            seams: Vec::new(),
+            margin: 0.005,
+        }
+    }
+}
+
+impl UnwrapOptions {
+    pub fn with_crease_angle(mut self, angle: Option<fxx>) -> Self {
+        self.crease_angle = angle;
+        self
+    }
+
+    pub fn with_seams(mut self, seams: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        self.seams.extend(seams);
+        self
+    }
+
+    pub fn with_margin(mut self, margin: fxx) -> Self {
+        self.margin = margin;
+        self
+    }
+}
+
+/// How well one chart of an unwrap preserves the shape of its surface
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartStats {
+    pub triangles: usize,
+    /// the area of the chart on the surface
+    pub area: fxx,
+    /// the area of the chart within the unit square
+    pub uv_area: fxx,
+    /// the area-weighted average ratio between the largest and the smallest stretch of every triangle.
+    /// 1 means all angles are preserved
+    pub conformal_distortion: fxx,
+    /// the largest ratio between the largest and the smallest stretch of any triangle
+    pub max_conformal_distortion: fxx,
+    /// the area-weighted average of how much triangles grew or shrank, compared to the chart as a whole.
+    /// 1 means all triangles kept their relative size
+    pub area_distortion: fxx,
+    /// triangles which are mirrored in uv space
+    pub flipped: usize,
+}
+
+/// What [`Mesh::to_unwrapped`] did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnwrapReport {
+    pub charts: Vec<ChartStats>,
+    /// the chart of every triangle
+    pub triangle_charts: Vec<usize>,
+    /// the edges the mesh was cut along, as pairs of vertex indices of the original mesh.
+    /// Includes the supplied seams, the creases, and the cuts needed to flatten closed or holed charts
+    pub seams: Vec<(usize, usize)>,
+}
+
+impl Mesh {
+    /// Unwrap the mesh onto the unit square, using least-squares conformal maps.
+    ///
+    /// The mesh is cut along the supplied seams and the creases into charts.
+    /// Charts which cannot be flattened, because they are closed or have holes or handles, are cut further,
+    /// preferably along edges of high curvature. Every chart is then flattened while preserving angles as well as possible,
+    /// scaled to the size it has on the surface, and packed into the unit square.
+    ///
+    /// Vertices along the seams are duplicated. Triangles keep their order.
+    /// Based on Least Squares Conformal Maps for Automatic Texture Atlas Generation, Lévy et al.
+    pub fn to_unwrapped(&self, options: &UnwrapOptions) -> (Mesh, UnwrapReport) {
+        let position = TriMesh::weld_roots(&self.verts, WELD_TOLERANCE);
+        let tris = self
+            .iter_triangles()
+            .map(|(a, b, c)| [a, b, c].map(|v| position[v]))
+            .collect::<Vec<_>>();
+
+        let mut cutting = Cutting::new(&self.verts, tris);
+        cutting.add_creases(options.crease_angle);
+        for (a, b) in options.seams.iter() {
+            let (a, b) = (position[*a], position[*b]);
+            cutting.seams.insert((a.min(b), a.max(b)));
+        }
+        cutting.cut_into_disks();
+
+        // flatten every chart on its own
+        let (triangle_charts, classes, class_count) = cutting.charts();
+        let chart_count = triangle_charts.iter().map(|c| c + 1).max().unwrap_or(0);
+        let mut chart_tris = vec![Vec::new(); chart_count];
+        for (t, chart) in triangle_charts.iter().enumerate() {
+            chart_tris[*chart].push(t);
+        }
+        let mut class_uvs = vec![Vec2::ZERO; class_count];
+        let mut charts = Vec::with_capacity(chart_count);
+        for ts in chart_tris.iter() {
+            let mut local = HashMap::<usize, usize>::new();
+            let mut points = Vec::new();
+            let mut owners = Vec::new();
+            let triangles = ts
+                .iter()
+                .map(|t| {
+                    [0, 1, 2].map(|k| {
+                        let class = classes[t * 3 + k];
+                        *local.entry(class).or_insert_with(|| {
+                            points.push(self.verts[cutting.tris[*t][k]]);
+                            owners.push(class);
+                            points.len() - 1
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+            let boundary = ts
+                .iter()
+                .flat_map(|t| (0..3).map(move |k| (*t, k)))
+                .filter(|(t, k)| cutting.across(*t, *k).is_none())
+                .map(|(t, k)| local[&classes[t * 3 + k]])
+                .collect::<Vec<_>>();
+
+            let uvs = lscm(&points, &triangles, &boundary);
+            let (uvs, stats) = normalize_chart(&points, &triangles, uvs);
+            for (owner, uv) in owners.iter().zip(uvs) {
+                class_uvs[*owner] = uv;
+            }
+            charts.push(stats);
+        }
+
+        pack_charts(
+            &chart_tris,
+            &classes,
+            &mut class_uvs,
+            &mut charts,
+            options.margin,
+        );
+
+        let corner_uvs = classes.iter().map(|c| class_uvs[*c]).collect::<Vec<_>>();
+        let mut seams = cutting
+            .seams
+            .iter()
+            .filter(|key| {
+                cutting
+                    .edges
+                    .get(key)
+                    .map_or(false, |sides| sides.len() > 1)
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        seams.sort();
+        let report = UnwrapReport {
+            charts,
+            triangle_charts,
+            seams,
+        };
+        (self.clone().with_corner_uvs(&corner_uvs), report)
+    }
+}
+
+/// The triangles of a mesh by welded position, and the seams cutting them apart
+struct Cutting<'a> {
+    verts: &'a [Vec3],
+    tris: Vec<[usize; 3]>,
+    /// unit normals, or zero for degenerate triangles
+    normals: Vec<Vec3>,
+    /// every side of a triangle, as (triangle, corner it starts at), by (smallest, largest) position
+    edges: HashMap<(usize, usize), Vec<(usize, usize)>>,
+    seams: HashSet<(usize, usize)>,
+}
+
+impl<'a> Cutting<'a> {
+    fn new(verts: &'a [Vec3], tris: Vec<[usize; 3]>) -> Self {
+        let normals = tris
+            .iter()
+            .map(|[a, b, c]| {
+                (verts[*b] - verts[*a])
+                    .cross(verts[*c] - verts[*a])
+                    .normalize_or_zero()
+            })
+            .collect();
+        let mut edges = HashMap::<(usize, usize), Vec<(usize, usize)>>::new();
+        for (t, tri) in tris.iter().enumerate() {
+            for k in 0..3 {
+                let (a, b) = (tri[k], tri[(k + 1) % 3]);
+                edges.entry((a.min(b), a.max(b))).or_default().push((t, k));
+            }
+        }
+
+        // naked edges, non-manifold edges, edges between triangles of opposite winding, and degenerate triangles
+        // are always cut
+        let seams = edges
+            .iter()
+            .filter(|(_, sides)| match sides.as_slice() {
+                [(t1, k1), (t2, k2)] => {
+                    let start = |t: usize, k: usize| tris[t][k];
+                    let degenerate = |t: usize| {
+                        let [a, b, c] = tris[t];
+                        a == b || b == c || c == a
+                    };
+                    start(*t1, *k1) == start(*t2, *k2) || degenerate(*t1) || degenerate(*t2)
+                }
+                _ => true,
+            })
+            .map(|(key, _)| *key)
+            .collect();
+
+        Self {
+            verts,
+            tris,
+            normals,
+            edges,
+            seams,
+        }
+    }
+
+    fn key(&self, t: usize, k: usize) -> (usize, usize) {
+        let (a, b) = (self.tris[t][k], self.tris[t][(k + 1) % 3]);
+        (a.min(b), a.max(b))
+    }
+
+    /// The side of the neighboring triangle, if the side `k` of triangle `t` is not cut
+    fn across(&self, t: usize, k: usize) -> Option<(usize, usize)> {
+        let key = self.key(t, k);
+        if self.seams.contains(&key) {
+            return None;
+        }
+        self.edges[&key]
+            .iter()
+            .copied()
+            .find(|(other, _)| *other != t)
+    }
+
+    /// The angle between the normals of the triangles on both sides of an edge
+    fn dihedral(&self, t1: usize, t2: usize) -> fxx {
+        self.normals[t1]
+            .dot(self.normals[t2])
+            .clamp(-1.0, 1.0)
+            .acos()
+    }
+
+    fn add_creases(&mut self, crease_angle: Option<fxx>) {
+        let Some(crease_angle) = crease_angle else {
+            return;
+        };
+        let creases = self
+            .edges
+            .iter()
+            .filter(|(_, sides)| match sides.as_slice() {
+                [(t1, _), (t2, _)] => self.dihedral(*t1, *t2) > crease_angle,
+                _ => false,
+            })
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        self.seams.extend(creases);
+    }
+
+    /// The chart of every triangle, the vertex within its chart of every corner, and the number of those vertices.
+    /// A position is split into several chart vertices where seams run through it.
+    fn charts(&self) -> (Vec<usize>, Vec<usize>, usize) {
+        let mut triangles = (0..self.tris.len()).collect::<Vec<_>>();
+        let mut corners = (0..self.tris.len() * 3).collect::<Vec<_>>();
+        for t in 0..self.tris.len() {
+            for k in 0..3 {
+                let Some((other, ok)) = self.across(t, k) else {
+                    continue;
+                };
+                join(&mut triangles, t, other);
+                join(&mut corners, t * 3 + k, other * 3 + (ok + 1) % 3);
+                join(&mut corners, t * 3 + (k + 1) % 3, other * 3 + ok);
+            }
+        }
+        let (triangles, _) = compact(&mut triangles);
+        let (corners, count) = compact(&mut corners);
+        (triangles, corners, count)
+    }
+
+    /// Add seams until every chart is a topological disk, which can be flattened
+    fn cut_into_disks(&mut self) {
+        for _ in 0..self.tris.len() {
+            let (triangle_charts, classes, _) = self.charts();
+            let mut chart_tris = HashMap::<usize, Vec<usize>>::new();
+            for (t, chart) in triangle_charts.iter().enumerate() {
+                chart_tris.entry(*chart).or_default().push(t);
+            }
+            let mut chart_tris = chart_tris.into_values().collect::<Vec<_>>();
+            chart_tris.sort();
+
+            let mut cuts = Vec::new();
+            for ts in chart_tris.iter() {
+                let verts = ts
+                    .iter()
+                    .flat_map(|t| [0, 1, 2].map(|k| classes[t * 3 + k]))
+                    .collect::<HashSet<_>>()
+                    .len() as isize;
+                let boundary = ts
+                    .iter()
+                    .flat_map(|t| (0..3).map(move |k| (*t, k)))
+                    .filter(|(t, k)| self.across(*t, *k).is_none())
+                    .count() as isize;
+                let faces = ts.len() as isize;
+                let euler = verts - (faces * 3 + boundary) / 2 + faces;
+                match (euler, boundary) {
+                    (1, _) => (),
+                    (_, 0) => cuts.append(&mut self.split_closed(ts)),
+                    _ => cuts.append(&mut self.cut_graph(ts, &classes)),
+                }
+            }
+            if cuts.is_empty() {
+                return;
+            }
+            self.seams.extend(cuts);
+        }
+    }
+
+    /// Split a closed chart in two, by growing two regions from triangles far apart
+    fn split_closed(&self, ts: &[usize]) -> Vec<(usize, usize)> {
+        let centers = |t: usize| {
+            let [a, b, c] = self.tris[t].map(|v| self.verts[v]);
+            (a + b + c) / 3.0
+        };
+        let grow = |seeds: &[usize]| {
+            let mut labels = HashMap::<usize, (fxx, usize)>::new();
+            let mut heap = BinaryHeap::new();
+            for (label, seed) in seeds.iter().enumerate() {
+                heap.push(Front {
+                    distance: 0.0,
+                    triangle: *seed,
+                    label,
+                });
+            }
+            let mut last = seeds[0];
+            while let Some(Front {
+                distance,
+                triangle,
+                label,
+            }) = heap.pop()
+            {
+                if labels.contains_key(&triangle) {
+                    continue;
+                }
+                labels.insert(triangle, (distance, label));
+                last = triangle;
+                for k in 0..3 {
+                    let Some((other, _)) = self.across(triangle, k) else {
+                        continue;
+                    };
+                    if !labels.contains_key(&other) {
+                        heap.push(Front {
+                            distance: distance + centers(triangle).distance(centers(other)),
+                            triangle: other,
+                            label,
+                        });
+                    }
+                }
+            }
+            (labels, last)
+        };
+
+        let (_, a) = grow(&[ts[0]]);
+        let (_, b) = grow(&[a]);
+        let (labels, _) = grow(&[a, b]);
+        ts.iter()
+            .flat_map(|t| (0..3).map(move |k| (*t, k)))
+            .filter(|(t, k)| match self.across(*t, *k) {
+                Some((other, _)) => labels[t].1 != labels[&other].1,
+                None => false,
+            })
+            .map(|(t, k)| self.key(t, k))
+            .collect()
+    }
+
+    /// The edges cutting a chart with holes or handles into a disk.
+    /// The triangles are connected by a spanning tree across their flattest edges.
+    /// The edges the tree does not cross form the cut, after pruning the branches which do not end at the boundary
+    fn cut_graph(&self, ts: &[usize], classes: &[usize]) -> Vec<(usize, usize)> {
+        let mut inner = ts
+            .iter()
+            .flat_map(|t| (0..3).map(move |k| (*t, k)))
+            .filter_map(|(t, k)| {
+                let (other, _) = self.across(t, k)?;
+                (t < other).then(|| (self.dihedral(t, other), t, k, other))
+            })
+            .collect::<Vec<_>>();
+        inner.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let local = ts
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (*t, i))
+            .collect::<HashMap<_, _>>();
+        let mut tree = (0..ts.len()).collect::<Vec<_>>();
+        let mut cut = Vec::new();
+        for (_, t, k, other) in inner {
+            if !join(&mut tree, local[&t], local[&other]) {
+                let ends = (classes[t * 3 + k], classes[t * 3 + (k + 1) % 3]);
+                cut.push((ends, self.key(t, k)));
+            }
+        }
+
+        // the boundary forms closed loops, so only the cut edges can dangle
+        let mut degree = HashMap::<usize, usize>::new();
+        let mut touching = HashMap::<usize, Vec<usize>>::new();
+        for t in ts.iter() {
+            for k in 0..3 {
+                if self.across(*t, k).is_none() {
+                    *degree.entry(classes[t * 3 + k]).or_default() += 1;
+                    *degree.entry(classes[t * 3 + (k + 1) % 3]).or_default() += 1;
+                }
+            }
+        }
+        for (i, ((a, b), _)) in cut.iter().enumerate() {
+            *degree.entry(*a).or_default() += 1;
+            *degree.entry(*b).or_default() += 1;
+            touching.entry(*a).or_default().push(i);
+            touching.entry(*b).or_default().push(i);
+        }
+        let mut alive = vec![true; cut.len()];
+        let mut dangling = degree
+            .iter()
+            .filter(|(_, d)| **d == 1)
+            .map(|(v, _)| *v)
+            .collect::<Vec<_>>();
+        dangling.sort();
+        while let Some(v) = dangling.pop() {
+            let Some(i) = touching[&v].iter().copied().find(|i| alive[*i]) else {
+                continue;
+            };
+            alive[i] = false;
+            let ((a, b), _) = cut[i];
+            for end in [a, b] {
+                let d = degree.get_mut(&end).unwrap();
+                *d -= 1;
+                if *d == 1 {
+                    dangling.push(end);
+                }
+            }
+        }
+        cut.iter()
+            .zip(alive)
+            .filter(|(_, alive)| *alive)
+            .map(|((_, key), _)| *key)
+            .collect()
+    }
+}
+
+/// A triangle reached while growing regions, ordered so the closest is on top of the heap
+struct Front {
+    distance: fxx,
+    triangle: usize,
+    label: usize,
+}
+
+impl PartialEq for Front {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Front {}
+
+impl PartialOrd for Front {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Front {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then(other.triangle.cmp(&self.triangle))
+            .then(other.label.cmp(&self.label))
+    }
+}
+
+/// Flatten a disk of triangles, preserving angles as well as possible.
+/// Two vertices on the boundary far apart are pinned, the others follow by minimizing the conformal energy
+/// with conjugate gradients, starting from a projection onto the average plane of the chart
+fn lscm(points: &[Vec3], triangles: &[[usize; 3]], boundary: &[usize]) -> Vec<Vec2> {
+    if points.is_empty() {
+        return Vec::new();
+    }
+
+    // pin the two ends of the longest stretch of boundary
+    let candidates = match boundary.is_empty() {
+        true => (0..points.len()).collect::<Vec<_>>(),
+        false => boundary.to_vec(),
+    };
+    let farthest = |from: usize| {
+        candidates
+            .iter()
+            .copied()
+            .max_by(|a, b| {
+                let da = points[*a].distance_squared(points[from]);
+                let db = points[*b].distance_squared(points[from]);
+                da.total_cmp(&db)
+            })
+            .unwrap_or(from)
+    };
+    let first = farthest(candidates[0]);
+    let second = farthest(first);
+
+    // the initial guess, a projection along the average normal
+    let normal = triangles
+        .iter()
+        .map(|[a, b, c]| (points[*b] - points[*a]).cross(points[*c] - points[*a]))
+        .sum::<Vec3>();
+    let along = points[second] - points[first];
+    let normal = match normal.try_normalize() {
+        Some(normal) => normal,
+        None => along.any_orthonormal_vector(),
+    };
+    let x_axis = (along - normal * along.dot(normal))
+        .try_normalize()
+        .unwrap_or_else(|| normal.any_orthonormal_vector());
+    let y_axis = normal.cross(x_axis);
+    let mut uvs = points
+        .iter()
+        .map(|p| {
+            vec2(
+                (*p - points[first]).dot(x_axis),
+                (*p - points[first]).dot(y_axis),
+            )
+        })
+        .collect::<Vec<_>>();
+    if uvs[second].x < UNWRAP_AREA_TOLERANCE {
+        uvs[second] = vec2(along.length(), 0.0);
+    }
+    if first == second {
+        return uvs;
+    }
+
+    // two rows per triangle: the real and imaginary part of the conformality condition
+    let mut rows = Vec::with_capacity(triangles.len() * 2);
+    for tri in triangles.iter() {
+        let [a, b, c] = tri.map(|v| points[v]);
+        let (ab, ac) = (b - a, c - a);
+        let double_area = ab.cross(ac).length();
+        if double_area < UNWRAP_AREA_TOLERANCE {
+            continue;
+        }
+        let x = ab.normalize();
+        let y = ab.cross(ac).cross(ab).normalize();
+        let local = [
+            Vec2::ZERO,
+            vec2(ab.length(), 0.0),
+            vec2(ac.dot(x), ac.dot(y)),
+        ];
+        let weight = 1.0 / double_area.sqrt();
+        let w = [0, 1, 2].map(|j| (local[(j + 2) % 3] - local[(j + 1) % 3]) * weight);
+        let mut real = [(0, 0.0); 6];
+        let mut imaginary = [(0, 0.0); 6];
+        for j in 0..3 {
+            let v = tri[j];
+            real[j * 2] = (v * 2, w[j].x);
+            real[j * 2 + 1] = (v * 2 + 1, -w[j].y);
+            imaginary[j * 2] = (v * 2, w[j].y);
+            imaginary[j * 2 + 1] = (v * 2 + 1, w[j].x);
+        }
+        rows.push(real);
+        rows.push(imaginary);
+    }
+
+    let mut free = vec![true; points.len() * 2];
+    for pin in [first, second] {
+        free[pin * 2] = false;
+        free[pin * 2 + 1] = false;
+    }
+    let mut x = uvs.iter().flat_map(|uv| [uv.x, uv.y]).collect::<Vec<_>>();
+    solve_least_squares(&rows, &free, &mut x);
+    (0..points.len())
+        .map(|i| vec2(x[i * 2], x[i * 2 + 1]))
+        .collect()
+}
+
+/// Minimize |Ax|² by changing only the `free` entries of `x`, using conjugate gradients on the normal equations (CGLS)
+fn solve_least_squares(rows: &[[(usize, fxx); 6]], free: &[bool], x: &mut [fxx]) {
+    let multiply = |x: &[fxx]| {
+        rows.iter()
+            .map(|row| row.iter().map(|(i, a)| a * x[*i]).sum::<fxx>())
+            .collect::<Vec<_>>()
+    };
+    let multiply_transposed = |r: &[fxx]| {
+        let mut s = vec![0.0; free.len()];
+        for (row, r) in rows.iter().zip(r) {
+            for (i, a) in row.iter() {
+                if free[*i] {
+                    s[*i] += a * r;
+                }
+            }
+        }
+        s
+    };
+    let dot = |a: &[fxx], b: &[fxx]| a.iter().zip(b).map(|(a, b)| a * b).sum::<fxx>();
+
+    let mut r = multiply(x).into_iter().map(|r| -r).collect::<Vec<_>>();
+    let mut s = multiply_transposed(&r);
+    let mut p = s.clone();
+    let mut gamma = dot(&s, &s);
+    let tolerance = gamma * EPSILON * EPSILON;
+    for _ in 0..free.len() * 4 + 100 {
+        if gamma <= tolerance || gamma == 0.0 {
+            return;
+        }
+        let q = multiply(&p);
+        let qq = dot(&q, &q);
+        if qq == 0.0 {
+            return;
+        }
+        let alpha = gamma / qq;
+        for (x, p) in x.iter_mut().zip(p.iter()) {
+            *x += alpha * p;
+        }
+        for (r, q) in r.iter_mut().zip(q) {
+            *r -= alpha * q;
+        }
+        s = multiply_transposed(&r);
+        let next = dot(&s, &s);
+        let beta = next / gamma;
+        gamma = next;
+        for (p, s) in p.iter_mut().zip(s.iter()) {
+            *p = s + beta * *p;
+        }
+    }
+}
+
+/// The two singular values of the map from a triangle on the surface to the same triangle in uv space, and whether it mirrors
+fn stretch(corners: [Vec3; 3], uvs: [Vec2; 3]) -> Option<(fxx, fxx, bool)> {
+    let (ab, ac) = (corners[1] - corners[0], corners[2] - corners[0]);
+    let double_area = ab.cross(ac).length();
+    if double_area < UNWRAP_AREA_TOLERANCE {
+        return None;
+    }
+    let x = ab.normalize();
+    let y = ab.cross(ac).cross(ab).normalize();
+    let (q1, q2) = (vec2(ab.length(), 0.0), vec2(ac.dot(x), ac.dot(y)));
+    let (w1, w2) = (uvs[1] - uvs[0], uvs[2] - uvs[0]);
+
+    // the jacobian [w1 w2] * inverse([q1 q2])
+    let det = q1.x * q2.y - q2.x * q1.y;
+    let (a, b) = (
+        (w1.x * q2.y - w2.x * q1.y) / det,
+        (w2.x * q1.x - w1.x * q2.x) / det,
+    );
+    let (c, d) = (
+        (w1.y * q2.y - w2.y * q1.y) / det,
+        (w2.y * q1.x - w1.y * q2.x) / det,
+    );
+    let q = vec2((a + d) * 0.5, (c - b) * 0.5).length();
+    let r = vec2((a - d) * 0.5, (c + b) * 0.5).length();
+    Some((q + r, (q - r).abs(), a * d - b * c < 0.0))
+}
+
+/// Scale a flattened chart to the area it has on the surface, and measure its distortion
+fn normalize_chart(
+    points: &[Vec3],
+    triangles: &[[usize; 3]],
+    uvs: Vec<Vec2>,
+) -> (Vec<Vec2>, ChartStats) {
+    let area = triangles
+        .iter()
+        .map(|[a, b, c]| {
+            (points[*b] - points[*a])
+                .cross(points[*c] - points[*a])
+                .length()
+                * 0.5
+        })
+        .sum::<fxx>();
+    let uv_area = triangles
+        .iter()
+        .map(|[a, b, c]| (uvs[*b] - uvs[*a]).perp_dot(uvs[*c] - uvs[*a]).abs() * 0.5)
+        .sum::<fxx>();
+    let scale = match uv_area > UNWRAP_AREA_TOLERANCE && area > UNWRAP_AREA_TOLERANCE {
+        true => (area / uv_area).sqrt(),
+        false => 1.0,
+    };
+    let uvs = uvs.into_iter().map(|uv| uv * scale).collect::<Vec<_>>();
+
+    let mut stats = ChartStats {
+        triangles: triangles.len(),
+        area,
+        uv_area,
+        conformal_distortion: 1.0,
+        max_conformal_distortion: 1.0,
+        area_distortion: 1.0,
+        flipped: 0,
+    };
+    let (mut weight, mut conformal, mut growth) = (0.0, 0.0, 0.0);
+    for tri in triangles.iter() {
+        let Some((large, small, mirrored)) = stretch(tri.map(|v| points[v]), tri.map(|v| uvs[v]))
+        else {
+            continue;
+        };
+        let [a, b, c] = tri.map(|v| points[v]);
+        let area = (b - a).cross(c - a).length() * 0.5;
+        let ratio = match small > 0.0 {
+            true => large / small,
+            false => INFINITY,
+        };
+        let grown = large * small;
+        weight += area;
+        conformal += ratio * area;
+        growth += grown.max(1.0 / grown) * area;
+        stats.max_conformal_distortion = stats.max_conformal_distortion.max(ratio);
+        stats.flipped += mirrored as usize;
+    }
+    if weight > 0.0 {
+        stats.conformal_distortion = conformal / weight;
+        stats.area_distortion = growth / weight;
+    }
+    (uvs, stats)
+}
+
+/// Pack the charts into the unit square, in rows, tallest first
+fn pack_charts(
+    chart_tris: &[Vec<usize>],
+    classes: &[usize],
+    class_uvs: &mut [Vec2],
+    charts: &mut [ChartStats],
+    margin: fxx,
+) {
+    let members = chart_tris
+        .iter()
+        .map(|ts| {
+            let mut members = ts
+                .iter()
+                .flat_map(|t| [0, 1, 2].map(|k| classes[t * 3 + k]))
+                .collect::<Vec<_>>();
+            members.sort();
+            members.dedup();
+            members
+        })
+        .collect::<Vec<_>>();
+    let bounds = members
+        .iter()
+        .map(|members| {
+            members.iter().fold(
+                (Vec2::splat(INFINITY), Vec2::splat(-INFINITY)),
+                |(min, max), c| (min.min(class_uvs[*c]), max.max(class_uvs[*c])),
+            )
+        })
+        .collect::<Vec<_>>();
+    let sizes = bounds
+        .iter()
+        .map(|(min, max)| *max - *min)
+        .collect::<Vec<_>>();
+    let mut order = (0..sizes.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| sizes[*b].y.total_cmp(&sizes[*a].y));
+
+    // place the charts in rows no wider than `width`, with `gap` between them
+    let shelve = |width: fxx, gap: fxx| {
+        let mut places = vec![Vec2::ZERO; sizes.len()];
+        let (mut cursor, mut row_height, mut extent) = (Vec2::splat(gap), 0.0, Vec2::ZERO);
+        for i in order.iter() {
+            let size = sizes[*i];
+            if cursor.x > gap && cursor.x + size.x + gap > width {
+                cursor = vec2(gap, cursor.y + row_height + gap);
+                row_height = 0.0;
+            }
+            places[*i] = cursor;
+            row_height = fxx::max(row_height, size.y);
+            extent = extent.max(cursor + size + gap);
+            cursor.x += size.x + gap;
+        }
+        (places, extent.max_element())
+    };
+
+    // the gap depends on the final scale, so pack twice
+    let total = sizes.iter().map(|s| s.x * s.y).sum::<fxx>().sqrt();
+    let widest = sizes.iter().map(|s| s.x).fold(0.0, fxx::max);
+    let mut extent = total;
+    let mut best = (Vec::new(), INFINITY);
+    for _ in 0..2 {
+        let gap = margin * extent;
+        best = [1.0, 1.1, 1.25, 1.5, 2.0]
+            .into_iter()
+            .map(|factor| shelve(fxx::max(total * factor, widest + gap * 2.0), gap))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or(best);
+        extent = best.1;
+    }
+    let (places, extent) = best;
+    if extent <= 0.0 || !extent.is_finite() {
+        return;
+    }
+    let scale = 1.0 / extent;
+
+    for (i, members) in members.iter().enumerate() {
+        for c in members.iter() {
+            class_uvs[*c] = (class_uvs[*c] - bounds[i].0 + places[i]) * scale;
+        }
+        charts[i].uv_area = charts[i].area * scale * scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solid::test_fixtures::{hull_cube, loft_tube, uv_areas};
+
+    fn in_unit_square(mesh: &Mesh) -> bool {
+        mesh.uvs
+            .iter()
+            .all(|uv| uv.min_element() >= -1e-9 && uv.max_element() <= 1.0 + 1e-9)
+    }
+
+    #[test]
+    fn test_unwrap_flat() {
+        // a flat patch is a single chart, flattened without any distortion
+        let grid = Mesh::new_hexagrid(1.0, 4);
+        let count = grid.verts.len();
+        let (mapped, report) = grid.to_unwrapped(&UnwrapOptions::default());
+        assert_eq!(mapped.verts.len(), count);
+        assert_eq!(report.charts.len(), 1);
+        assert!(report.seams.is_empty());
+        let chart = &report.charts[0];
+        assert_eq!(chart.flipped, 0);
+        assert!((chart.conformal_distortion - 1.0).abs() < 1e-6);
+        assert!((chart.area_distortion - 1.0).abs() < 1e-6);
+        assert!(in_unit_square(&mapped));
+        assert!(uv_areas(&mapped).iter().all(|area| *area > 0.0));
+    }
+
+    #[test]
+    fn test_unwrap_cube() {
+        // every side of the cube becomes its own square chart, and the charts do not overlap
+        let cube = hull_cube();
+        let (mapped, report) = cube.to_unwrapped(&UnwrapOptions::default());
+        assert_eq!(report.charts.len(), 6);
+        assert_eq!(report.seams.len(), 12);
+        assert_eq!(mapped.verts.len(), 24);
+        assert!(in_unit_square(&mapped));
+        for chart in report.charts.iter() {
+            assert!((chart.conformal_distortion - 1.0).abs() < 1e-6);
+        }
+
+        let bounds = (0..6)
+            .map(|chart| {
+                mapped
+                    .iter_triangles()
+                    .zip(report.triangle_charts.iter())
+                    .filter(|(_, c)| **c == chart)
+                    .flat_map(|((a, b, c), _)| [a, b, c])
+                    .fold(
+                        (Vec2::splat(INFINITY), Vec2::splat(-INFINITY)),
+                        |(min, max), v| (min.min(mapped.uvs[v]), max.max(mapped.uvs[v])),
+                    )
+            })
+            .collect::<Vec<_>>();
+        for (i, (min_a, max_a)) in bounds.iter().enumerate() {
+            for (min_b, max_b) in bounds.iter().skip(i + 1) {
+                let overlap = max_a.cmpgt(*min_b).all() && max_b.cmpgt(*min_a).all();
+                assert!(!overlap);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unwrap_sphere() {
+        // a smooth closed surface has no creases, so it has to be cut automatically
+        let sphere = Mesh::new_icosahedron(1.0).loop_subdivide(2, &[]);
+        let count = sphere.verts.len();
+        let (mapped, report) = sphere.to_unwrapped(&UnwrapOptions::default());
+        assert!(report.charts.len() >= 2);
+        assert!(!report.seams.is_empty());
+        assert!(mapped.verts.len() > count);
+        assert!(in_unit_square(&mapped));
+        for chart in report.charts.iter() {
+            assert_eq!(chart.flipped, 0);
+            assert!(chart.conformal_distortion < 1.5);
+        }
+        assert!(uv_areas(&mapped).iter().all(|area| *area > 0.0));
+    }
+
+    #[test]
+    fn test_unwrap_tube() {
+        // an open tube is cut open once, or along the seam it is given
+        let tube = loft_tube(16, 3);
+        let (mapped, report) = tube.to_unwrapped(&UnwrapOptions::default());
+        assert_eq!(report.charts.len(), 1);
+        assert_eq!(report.charts[0].flipped, 0);
+        assert!(!report.seams.is_empty());
+        assert!(in_unit_square(&mapped));
+
+        let seams = [(0, 16), (16, 32)];
+        let options = UnwrapOptions::default().with_seams(seams);
+        let (mapped, report) = tube.to_unwrapped(&options);
+        assert_eq!(report.charts.len(), 1);
+        assert_eq!(report.seams, seams.to_vec());
+        assert_eq!(mapped.verts.len(), tube.verts.len() + 3);
+        assert!(report.charts[0].conformal_distortion < 1.01);
+    }
+}
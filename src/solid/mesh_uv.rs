@@ -0,0 +1,296 @@
+use super::{Mesh, Normals};
+use crate::core::Plane;
+use crate::kernel::{fxx, vec2, Vec2, Vec3, TAU};
+
+/// Vertices this close to the axis of a cylindrical or spherical projection have no meaningful angle around it
+const PROJECTION_AXIS_TOLERANCE: fxx = 1e-9;
+
+/// The shape texture coordinates are projected from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mapping {
+    /// Straight down the z axis, onto the xy plane
+    Planar,
+    /// Onto the side of a cube each triangle faces most, also known as triplanar mapping
+    Box,
+    /// Around the z axis. U goes around from 0 to 1, starting at the x axis. V is the height along the axis
+    Cylindrical,
+    /// Around the origin. U is the longitude and V the latitude, both from 0 to 1
+    Spherical,
+}
+
+/// A projection of texture coordinates, used by [`Mesh::with_uvs_projected`].
+/// The shape is placed by a plane, and the resulting coordinates are multiplied by `scale`, and then moved by `offset`.
+#[derive(Clone)]
+pub struct Projection {
+    pub mapping: Mapping,
+    pub plane: Plane,
+    pub scale: Vec2,
+    pub offset: Vec2,
+}
+
+impl Projection {
+    pub fn new(mapping: Mapping, plane: impl Into<Plane>) -> Self {
+        Self {
+            mapping,
+            plane: plane.into(),
+            scale: Vec2::ONE,
+            offset: Vec2::ZERO,
+        }
+    }
+
+    pub fn planar(plane: impl Into<Plane>) -> Self {
+        Self::new(Mapping::Planar, plane)
+    }
+
+    pub fn triplanar(plane: impl Into<Plane>) -> Self {
+        Self::new(Mapping::Box, plane)
+    }
+
+    pub fn cylindrical(plane: impl Into<Plane>) -> Self {
+        Self::new(Mapping::Cylindrical, plane)
+    }
+
+    pub fn spherical(plane: impl Into<Plane>) -> Self {
+        Self::new(Mapping::Spherical, plane)
+    }
+
+    pub fn with_scale(mut self, scale: impl Into<Vec2>) -> Self {
+        self.scale = scale.into();
+        self
+    }
+
+    pub fn with_offset(mut self, offset: impl Into<Vec2>) -> Self {
+        self.offset = offset.into();
+        self
+    }
+
+    /// The texture coordinates of the three corners of a triangle, and the side of the box it is projected on
+    fn project(&self, corners: [Vec3; 3]) -> ([Vec2; 3], usize) {
+        let local = corners.map(|c| self.plane.point_to_plane(c));
+        let mut side = 0;
+        let uvs = match self.mapping {
+            Mapping::Planar => local.map(|l| l.truncate()),
+            Mapping::Box => {
+                side = box_side((local[1] - local[0]).cross(local[2] - local[0]));
+                local.map(|l| box_uv(side, l))
+            }
+            Mapping::Cylindrical => {
+                let u = wrap(local.map(turn));
+                [0, 1, 2].map(|i| vec2(u[i], local[i].z))
+            }
+            Mapping::Spherical => {
+                let u = wrap(local.map(turn));
+                [0, 1, 2].map(|i| {
+                    let l = local[i];
+                    let latitude = l.z.atan2(l.truncate().length());
+                    vec2(u[i], latitude / TAU * 2.0 + 0.5)
+                })
+            }
+        };
+        (uvs.map(|uv| uv * self.scale + self.offset), side)
+    }
+}
+
+impl Mesh {
+    /// Replace the texture coordinates by a projection, like a planar, box, cylindrical or spherical one.
+    ///
+    /// Vertices shared by triangles which end up on different sides of the box, or on both sides of the seam
+    /// of a cylinder or sphere, are duplicated, so that every triangle gets its own coordinates.
+    pub fn with_uvs_projected(self, projection: &Projection) -> Self {
+        let (uvs, sides): (Vec<_>, Vec<_>) = self
+            .iter_triangles()
+            .map(|(a, b, c)| projection.project([a, b, c].map(|v| self.verts[v])))
+            .unzip();
+        let uvs = uvs.concat();
+        let groups = sides
+            .into_iter()
+            .flat_map(|side| [side; 3])
+            .collect::<Vec<_>>();
+        self.split_corners(&uvs, &groups)
+    }
+
+    /// Set texture coordinates for every triangle corner, in the order of `tri`.
+    /// Vertices are duplicated where the triangles around them disagree about their coordinates.
+    pub fn with_corner_uvs(self, uvs: &[Vec2]) -> Self {
+        let groups = vec![0; uvs.len()];
+        self.split_corners(uvs, &groups)
+    }
+
+    /// Give every triangle corner its own uv, duplicating vertices used with several uvs, or in several groups
+    pub(super) fn split_corners(mut self, uvs: &[Vec2], groups: &[usize]) -> Self {
+        assert_eq!(
+            uvs.len(),
+            self.tri.len(),
+            "expected a uv for every triangle corner"
+        );
+
+        // every vertex keeps its first uv. Differing uvs get a copy of the vertex
+        let mut copies = vec![Vec::<(Vec2, usize, usize)>::new(); self.verts.len()];
+        let mut source = (0..self.verts.len()).collect::<Vec<_>>();
+        let mut vert_uvs = vec![Vec2::ZERO; self.verts.len()];
+        for ((corner, uv), group) in self.tri.iter_mut().zip(uvs.iter()).zip(groups.iter()) {
+            let v = *corner;
+            let found = copies[v]
+                .iter()
+                .find(|(other, other_group, _)| other == uv && other_group == group);
+            if let Some((_, _, copy)) = found {
+                *corner = *copy;
+                continue;
+            }
+            let copy = match copies[v].is_empty() {
+                true => v,
+                false => {
+                    source.push(v);
+                    vert_uvs.push(*uv);
+                    source.len() - 1
+                }
+            };
+            vert_uvs[copy] = *uv;
+            copies[v].push((*uv, *group, copy));
+            *corner = copy;
+        }
+
+        let count = self.verts.len();
+        self.verts = source.iter().map(|v| self.verts[*v]).collect();
+        self.uvs = vert_uvs;
+        if let Normals::Vertex(normals) = &self.normals {
+            if normals.len() == count {
+                self.normals = Normals::Vertex(source.iter().map(|v| normals[*v]).collect());
+            }
+        }
+        self.vert_attributes = self.vert_attributes.select(count, &source);
+        self
+    }
+}
+
+/// The side of a cube `normal` faces most: +x, -x, +y, -y, +z or -z
+fn box_side(normal: Vec3) -> usize {
+    let abs = normal.abs();
+    let axis = match (abs.x >= abs.y && abs.x >= abs.z, abs.y >= abs.z) {
+        (true, _) => 0,
+        (false, true) => 1,
+        (false, false) => 2,
+    };
+    axis * 2 + (normal[axis] < 0.0) as usize
+}
+
+/// The coordinates on a side of a cube, oriented so the texture is never mirrored
+fn box_uv(side: usize, local: Vec3) -> Vec2 {
+    match side {
+        0 => vec2(local.y, local.z),
+        1 => vec2(-local.y, local.z),
+        2 => vec2(-local.x, local.z),
+        3 => vec2(local.x, local.z),
+        4 => vec2(local.x, local.y),
+        _ => vec2(-local.x, local.y),
+    }
+}
+
+/// The angle around the z axis, as a fraction of a full turn from 0 to 1. Points on the axis have none
+fn turn(local: Vec3) -> Option<fxx> {
+    let on_axis = local.x.abs() + local.y.abs() <= PROJECTION_AXIS_TOLERANCE;
+    (!on_axis).then(|| local.y.atan2(local.x).rem_euclid(TAU) / TAU)
+}
+
+/// Make the turns of a triangle continuous, by unwrapping corners on the other side of the seam.
+/// Corners on the axis have no turn of their own, and take the average of the others
+fn wrap(turns: [Option<fxx>; 3]) -> [fxx; 3] {
+    let known = turns.iter().flatten().copied().collect::<Vec<_>>();
+    let max = known.iter().copied().fold(0.0, fxx::max);
+    let unwrapped = known
+        .iter()
+        .map(|t| match max - t > 0.5 {
+            true => t + 1.0,
+            false => *t,
+        })
+        .collect::<Vec<_>>();
+    let average = match unwrapped.is_empty() {
+        true => 0.0,
+        false => unwrapped.iter().sum::<fxx>() / unwrapped.len() as fxx,
+    };
+    let mut unwrapped = unwrapped.into_iter();
+    turns.map(|t| match t {
+        Some(_) => unwrapped.next().unwrap_or(average),
+        None => average,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Pose;
+    use crate::solid::test_fixtures::{hull_cube, loft_tube, uv_areas};
+    use crate::solid::Attribute;
+
+    #[test]
+    fn test_planar() {
+        let grid = Mesh::new_hexagrid(1.0, 2);
+        let count = grid.verts.len();
+        let projection = Projection::planar(Pose::default())
+            .with_scale(vec2(2.0, 2.0))
+            .with_offset(vec2(0.5, 0.5));
+        let mapped = grid.with_uvs_projected(&projection);
+        assert_eq!(mapped.verts.len(), count);
+        for (vert, uv) in mapped.verts.iter().zip(mapped.uvs.iter()) {
+            assert!((vert.truncate() * 2.0 + 0.5 - *uv).length() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_triplanar() {
+        // a cube with shared corners falls apart into its six sides
+        let cube = hull_cube().with_vertex_normals();
+        assert_eq!(cube.verts.len(), 8);
+        let mapped = cube.with_uvs_projected(&Projection::triplanar(Pose::default()));
+        assert_eq!(mapped.verts.len(), 24);
+        assert_eq!(mapped.get_normals().unwrap().len(), 24);
+        assert!(uv_areas(&mapped)
+            .iter()
+            .all(|area| (area - 4.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_spherical_seam() {
+        let sphere = Mesh::new_icosahedron(1.0).loop_subdivide(2, &[]);
+        let count = sphere.verts.len();
+        let sphere = sphere.with_vert_attribute(
+            "id",
+            Attribute::Scalar((0..count).map(|i| i as fxx).collect()),
+        );
+        let mapped = sphere
+            .clone()
+            .with_uvs_projected(&Projection::spherical(Pose::default()));
+
+        // vertices along the seam and at the poles are split, everything else stays shared
+        assert!(mapped.verts.len() > count);
+        assert!(mapped.verts.len() < count * 2);
+        let Some(Attribute::Scalar(ids)) = mapped.vert_attributes.get("id") else {
+            panic!("the attribute should be carried over");
+        };
+        for (vert, id) in mapped.verts.iter().zip(ids.iter()) {
+            assert_eq!(*vert, sphere.verts[*id as usize]);
+        }
+
+        // no triangle stretches across the seam, and none is mirrored
+        assert!(uv_areas(&mapped)
+            .iter()
+            .all(|area| *area > 0.0 && *area < 0.1));
+        assert!(mapped.uvs.iter().all(|uv| uv.y >= 0.0 && uv.y <= 1.0));
+    }
+
+    #[test]
+    fn test_cylindrical() {
+        let cylinder = loft_tube(12, 3);
+        let mapped = cylinder.with_uvs_projected(
+            &Projection::cylindrical(Pose::default()).with_scale(vec2(1.0, 0.5)),
+        );
+        let areas = uv_areas(&mapped);
+        let total = areas.iter().sum::<fxx>() * 0.5;
+        assert!((total - 1.0).abs() < 1e-6);
+        assert!(areas.iter().all(|area| *area > 0.0 && *area < 0.1));
+        assert!(mapped
+            .uvs
+            .iter()
+            .all(|uv| uv.x >= 0.0 && uv.x <= 1.0 + 1e-6 && uv.y <= 1.0 + 1e-6));
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use super::{Attributes, Mesh, Normals};
+use crate::util::{compact, join};
+
+/// The result of [`Mesh::validate`]. Edges are given as (smallest, largest) pairs of vertex indices
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Positions in `tri` pointing past the end of `verts`. Their triangles are left out of the other checks
+    pub out_of_bounds: Vec<usize>,
+    /// Indices at the end of `tri` which do not form a complete triangle
+    pub incomplete_indices: usize,
+    /// Vertices not used by any triangle
+    pub unused_verts: usize,
+    /// Edges used by a single triangle
+    pub naked_edges: Vec<(usize, usize)>,
+    /// Edges shared by more than two triangles
+    pub non_manifold_edges: Vec<(usize, usize)>,
+    /// Edges between two triangles running along them in the same direction, so with opposite windings
+    pub inconsistent_edges: Vec<(usize, usize)>,
+    /// The holes, or loops of naked edges
+    pub boundary_loops: usize,
+    /// Groups of triangles connected by shared vertices
+    pub components: usize,
+    /// Vertices - edges + faces, counting only the vertices used by triangles
+    pub euler_characteristic: i64,
+    /// The number of handles, summed over all components.
+    /// Only known for oriented manifolds, as `2 * components - boundary_loops - 2 * genus = euler_characteristic`
+    pub genus: Option<usize>,
+    /// The number of uvs, if there are some, but not one per vertex
+    pub mismatched_uvs: Option<usize>,
+    /// The number of normals, if it does not match the vertices or faces they belong to
+    pub mismatched_normals: Option<usize>,
+    /// Vertex or triangle attributes with the wrong length, by name
+    pub mismatched_attributes: Vec<String>,
+}
+
+impl ValidationReport {
+    /// Every index and every buffer is in order, so the mesh can be used without panicking
+    pub fn is_valid(&self) -> bool {
+        self.out_of_bounds.is_empty()
+            && self.incomplete_indices == 0
+            && self.mismatched_uvs.is_none()
+            && self.mismatched_normals.is_none()
+            && self.mismatched_attributes.is_empty()
+    }
+
+    /// Every edge is shared by at most two triangles, which agree on their winding
+    pub fn is_oriented_manifold(&self) -> bool {
+        self.non_manifold_edges.is_empty() && self.inconsistent_edges.is_empty()
+    }
+
+    /// A valid, watertight solid
+    pub fn is_closed_solid(&self) -> bool {
+        self.is_valid() && self.is_oriented_manifold() && self.naked_edges.is_empty()
+    }
+}
+
+impl Mesh {
+    /// Check the indices, buffers and topology of this mesh.
+    ///
+    /// Broken meshes are reported, not fixed. Use this in tests and debug builds,
+    /// like `debug_assert!(mesh.validate().is_closed_solid())`.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let tri_count = self.count_triangles();
+        report.incomplete_indices = self.tri.len() % 3;
+        report.out_of_bounds = (0..tri_count * 3)
+            .filter(|i| self.tri[*i] >= self.verts.len())
+            .collect();
+
+        report.mismatched_uvs =
+            (!self.uvs.is_empty() && self.uvs.len() != self.verts.len()).then_some(self.uvs.len());
+        report.mismatched_normals = match &self.normals {
+            Normals::Vertex(normals) => {
+                (normals.len() != self.verts.len()).then_some(normals.len())
+            }
+            Normals::Face(normals) => (normals.len() != tri_count).then_some(normals.len()),
+            Normals::None => None,
+        };
+        report.mismatched_attributes = mismatched(&self.vert_attributes, self.verts.len())
+            .chain(mismatched(&self.tri_attributes, tri_count))
+            .collect();
+
+        // the topology of the triangles we can trust
+        let checked;
+        let mesh = match report.out_of_bounds.is_empty() && report.incomplete_indices == 0 {
+            true => self,
+            false => {
+                let tri = self
+                    .tri
+                    .chunks_exact(3)
+                    .filter(|tri| tri.iter().all(|v| *v < self.verts.len()))
+                    .flatten()
+                    .copied()
+                    .collect();
+                checked = Mesh::new(self.verts.clone(), tri, Vec::new(), Normals::None);
+                &checked
+            }
+        };
+
+        // the neighborized edges only keep two triangles, so count the sides of every edge as well
+        let mut sides = HashMap::<(usize, usize), (usize, usize)>::new();
+        for (a, b) in mesh.iter_edges() {
+            let (forward, backward) = sides.entry((a.min(b), a.max(b))).or_default();
+            match a < b {
+                true => *forward += 1,
+                false => *backward += 1,
+            }
+        }
+        let edges = mesh.get_neighborized_edges();
+        for (edge, (_, other)) in edges.iter() {
+            let (forward, backward) = sides[edge];
+            if other.is_none() {
+                report.naked_edges.push(*edge);
+            } else if forward + backward > 2 {
+                report.non_manifold_edges.push(*edge);
+            } else if forward != backward {
+                report.inconsistent_edges.push(*edge);
+            }
+        }
+        report.naked_edges.sort();
+        report.non_manifold_edges.sort();
+        report.inconsistent_edges.sort();
+        report.boundary_loops = Mesh::aggregate_edges(report.naked_edges.iter().copied()).len();
+
+        let mut used = vec![false; mesh.verts.len()];
+        let mut sets = (0..mesh.verts.len()).collect::<Vec<_>>();
+        for (a, b, c) in mesh.iter_triangles() {
+            used[a] = true;
+            used[b] = true;
+            used[c] = true;
+            join(&mut sets, a, b);
+            join(&mut sets, a, c);
+        }
+        let (components, _) = compact(&mut sets);
+        let mut roots = components
+            .iter()
+            .zip(used.iter())
+            .filter_map(|(component, used)| used.then_some(*component))
+            .collect::<Vec<_>>();
+        roots.sort_unstable();
+        roots.dedup();
+        report.components = roots.len();
+        let vert_count = used.iter().filter(|used| **used).count();
+        report.unused_verts = mesh.verts.len() - vert_count;
+
+        report.euler_characteristic =
+            vert_count as i64 - edges.len() as i64 + mesh.count_triangles() as i64;
+        let doubled_genus = 2 * report.components as i64
+            - report.boundary_loops as i64
+            - report.euler_characteristic;
+        report.genus =
+            (report.is_oriented_manifold() && doubled_genus >= 0 && doubled_genus % 2 == 0)
+                .then_some(doubled_genus as usize / 2);
+        report
+    }
+}
+
+/// The names of the attributes without exactly `len` items
+fn mismatched(attributes: &Attributes, len: usize) -> impl Iterator<Item = String> + '_ {
+    attributes
+        .iter()
+        .filter(move |(_, attribute)| attribute.len() != len)
+        .map(|(name, _)| name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::{fxx, vec3, Vec2, TAU};
+    use crate::solid::Attribute;
+
+    #[test]
+    fn test_validate_solids() {
+        let sphere = Mesh::new_icosahedron(1.0).loop_subdivide(1, &[]);
+        let report = sphere.validate();
+        assert!(report.is_closed_solid());
+        assert_eq!(report.components, 1);
+        assert_eq!(report.euler_characteristic, 2);
+        assert_eq!(report.genus, Some(0));
+
+        // two spheres, one of them with a hole and flipped triangles
+        let mut broken = Mesh::from_join(vec![sphere.clone(), sphere.clone()]);
+        let count = sphere.count_triangles();
+        broken.tri.truncate(broken.tri.len() - 3);
+        broken.tri.swap(count * 3, count * 3 + 1);
+        let report = broken.validate();
+        assert!(report.is_valid());
+        assert!(!report.is_closed_solid());
+        assert_eq!(report.components, 2);
+        assert_eq!(report.naked_edges.len(), 3);
+        assert_eq!(report.boundary_loops, 1);
+        assert_eq!(report.inconsistent_edges.len(), 3);
+        assert_eq!(report.genus, None);
+    }
+
+    #[test]
+    fn test_validate_torus() {
+        // a grid of quads, wrapped around in both directions
+        let (n, m) = (8, 6);
+        let verts = (0..n)
+            .flat_map(|i| (0..m).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let (u, v) = (i as fxx / n as fxx * TAU, j as fxx / m as fxx * TAU);
+                let r = 2.0 + v.cos();
+                vec3(r * u.cos(), r * u.sin(), v.sin())
+            })
+            .collect();
+        let id = |i: usize, j: usize| (i % n) * m + j % m;
+        let tri = (0..n)
+            .flat_map(|i| (0..m).map(move |j| (i, j)))
+            .flat_map(|(i, j)| {
+                let [a, b, c, d] = [id(i, j), id(i + 1, j), id(i + 1, j + 1), id(i, j + 1)];
+                [a, b, c, a, c, d]
+            })
+            .collect();
+        let torus = Mesh::new(verts, tri, Vec::new(), Normals::None);
+        let report = torus.validate();
+        assert!(report.is_closed_solid());
+        assert_eq!(report.euler_characteristic, 0);
+        assert_eq!(report.genus, Some(1));
+    }
+
+    #[test]
+    fn test_validate_buffers() {
+        let mut mesh = Mesh::new_icosahedron(1.0)
+            .with_vertex_normals()
+            .with_tri_attribute("id", Attribute::Index(vec![0; 3]));
+        mesh.uvs = vec![Vec2::ZERO; 2];
+        mesh.tri.extend([0, 1, 20, 4]);
+        mesh.verts.push(vec3(0.0, 0.0, 5.0));
+        let report = mesh.validate();
+        assert!(!report.is_valid());
+        assert_eq!(report.out_of_bounds, vec![62]);
+        assert_eq!(report.incomplete_indices, 1);
+        assert_eq!(report.unused_verts, 1);
+        assert_eq!(report.mismatched_uvs, Some(2));
+        assert_eq!(report.mismatched_normals, Some(12));
+        assert_eq!(report.mismatched_attributes, vec!["id".to_owned()]);
+
+        // the broken triangle is left out of the topology
+        assert!(report.is_oriented_manifold());
+        assert!(report.naked_edges.is_empty());
+        assert_eq!(report.genus, Some(0));
+    }
+}
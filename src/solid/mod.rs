@@ -1,13 +1,41 @@
+mod attributes;
+mod bvh;
 mod cuboid;
 mod mesh;
+mod mesh_boolean;
+mod mesh_clean;
 mod mesh_consts;
+mod mesh_contains;
+mod mesh_decimate;
+mod mesh_mass;
+mod mesh_normals;
+mod mesh_offset;
+mod mesh_split;
+mod mesh_tangents;
+mod mesh_unwrap;
+mod mesh_uv;
+mod mesh_validate;
 mod octoid;
 mod polyhedron;
+mod smooth;
+mod subdivide;
 mod tri_mesh;
 
+#[cfg(test)]
+mod test_fixtures;
+
+pub use attributes::*;
+pub use bvh::*;
 pub use cuboid::*;
 pub use mesh::*;
+pub use mesh_boolean::*;
+pub use mesh_clean::*;
 pub use mesh_consts::*;
+pub use mesh_mass::*;
+pub use mesh_unwrap::*;
+pub use mesh_uv::*;
+pub use mesh_validate::*;
 pub use octoid::*;
 pub use polyhedron::*;
+pub use smooth::*;
 pub use tri_mesh::*;
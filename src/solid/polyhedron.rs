@@ -3,9 +3,9 @@ use rand::seq::SliceRandom;
 use super::Mesh;
 use crate::algos::{line_hits_plane, line_x_plane};
 use crate::core::{Geometry, Pose};
-use crate::kernel::{fxx, vec3, Vec3};
+use crate::kernel::{fxx, vec3, Vec3, VEC_TOLERANCE};
 use crate::math::wrap_around;
-use crate::util::{iter_triplets, roughly_equals};
+use crate::util::{compact, iter_triplets, join, roughly_equals};
 
 use crate::{
     core::PointBased,
@@ -268,6 +268,89 @@ impl Polyhedron {
         hedron
     }
 
+    /// Build a polyhedron from polygons, given as counter clockwise indices into `verts`.
+    /// Unlike [`Polyhedron::from_mesh`], the faces are created directly, so no normals are needed to order the edges.
+    /// Edges used by only one polygon get a twin without a face, forming the loops of the holes.
+    ///
+    /// Returns `None` if an index is out of bounds, or if two polygons use the same edge in the same direction.
+    pub fn from_faces(verts: &[Vec3], faces: &[Vec<usize>]) -> Option<Self> {
+        let mut hedron = Polyhedron::new();
+        for vert in verts {
+            hedron.add_vert(*vert);
+        }
+
+        // the inner half edges, in order of creation
+        let mut directed = HashMap::<(VertPtr, VertPtr), EdgePtr>::new();
+        let mut inner = Vec::new();
+        for face in faces.iter().filter(|face| face.len() > 2) {
+            if face.iter().any(|vp| *vp >= verts.len()) {
+                return None;
+            }
+            let polygon = Polygon::new(face.iter().map(|vp| verts[*vp]).collect());
+            let fp = hedron.faces.push(Face {
+                edge: 0,
+                center: polygon.center(),
+                normal: polygon.average_normal(),
+            });
+            let mut loop_edges = Vec::new();
+            for i in 0..face.len() {
+                let (from, to) = (face[i], face[(i + 1) % face.len()]);
+                let ep = hedron.edges.push(HalfEdge {
+                    from,
+                    next: 0,
+                    twin: 0,
+                    face: Some(fp),
+                });
+                if directed.insert((from, to), ep).is_some() {
+                    return None;
+                }
+                inner.push(((from, to), ep));
+                loop_edges.push(ep);
+                hedron.mut_vert(from).edge = Some(ep);
+            }
+            for (i, ep) in loop_edges.iter().enumerate() {
+                hedron.mut_edge(*ep).next = loop_edges[(i + 1) % loop_edges.len()];
+            }
+            hedron.faces.get_mut(fp)?.edge = loop_edges[0];
+        }
+
+        // pair up twins, or add an empty twin at a hole
+        let mut prev = HashMap::new();
+        let mut holes = Vec::new();
+        for ((from, to), ep) in inner.iter().copied() {
+            prev.insert(hedron.edge(ep).next, ep);
+            let twin = match directed.get(&(to, from)) {
+                Some(twin) => *twin,
+                None => {
+                    let twin = hedron.edges.push(HalfEdge {
+                        from: to,
+                        next: 0,
+                        twin: ep,
+                        face: None,
+                    });
+                    holes.push(twin);
+                    twin
+                }
+            };
+            hedron.mut_edge(ep).twin = twin;
+        }
+
+        // a hole edge ending at vertex `a` continues with the first hole edge found by turning around `a`
+        for hole in holes {
+            let mut cursor = hedron.edge(hole).twin;
+            for _ in 0..hedron.edges.len() {
+                let outgoing = hedron.edge(prev[&cursor]).twin;
+                if hedron.edge(outgoing).face.is_none() {
+                    hedron.mut_edge(hole).next = outgoing;
+                    break;
+                }
+                cursor = outgoing;
+            }
+        }
+
+        Some(hedron)
+    }
+
     /////////////////////////////////////////////////////////////// Debugging
 
     pub fn print_structure(&self) {
@@ -703,7 +786,8 @@ impl Polyhedron {
                 let (Some(fa), Some(fb)) = (self.edge(edge).face, self.edge(twin).face) else {
                     continue;
                 };
-                let (Some(vert_a), Some(vert_b)) = (face_to_vert.get(&fa), face_to_vert.get(&fb)) else {
+                let (Some(vert_a), Some(vert_b)) = (face_to_vert.get(&fa), face_to_vert.get(&fb))
+                else {
                     panic!("found an unlisted face!");
                 };
                 let (norm_a, norm_b) = (
@@ -906,7 +990,9 @@ impl Polyhedron {
             self.mut_edge(ep_inwards).next = ep;
         } else if v.edge.is_some() {
             let (_, to) = self.edge_verts(ep);
-            let Some((ep_nb_inwards, ep_nb_outwards)) = self.get_disk_neighbors_edges(vp, to, normal) else {
+            let Some((ep_nb_inwards, ep_nb_outwards)) =
+                self.get_disk_neighbors_edges(vp, to, normal)
+            else {
                 return;
             };
             // println!("in: {ep_nb_inwards} out: {ep_nb_outwards}");
@@ -976,66 +1062,37 @@ impl Polyhedron {
         todo!()
     }
 
-    /// subdivide by creating quads from all polyhedrons
-    pub fn quad_divide(&mut self) {
-        // get center points, normal, and original start edges for all loops
-        // TODO: we should be looping over actual faces...
-        // make this into: cap_all_loops
-        let faces_data: Vec<(Vec3, Vec3, usize)> = self
-            .get_loops()
-            .iter()
-            .filter_map(|edges| {
-                let first_edge = edges[0];
-                let verts = self.edges_to_verts(edges);
-                let pg = Polygon::new(verts);
-                let pose = pg.estimate_pose();
-                let center = pose.pos;
-                let signed_area = pg.posed_signed_area(&pose);
-                if signed_area > 0.0 {
-                    None
-                } else {
-                    let normal = pose.normal();
-                    Some((center, normal, first_edge))
-                }
-            })
-            .collect();
-
-        // subdivide all edges
-        for edge in self.all_unique_edges() {
-            self.split_edge(edge, 0.5);
-        }
-
-        // then build new faces
-        for (center, normal, first_edge) in faces_data.into_iter() {
-            let vp = self.add_vert(center);
-            for edge in self.get_loop(first_edge).iter().skip(1).step_by(2) {
-                // NOTE: we can do this faster and without disk thingies, if we just carefully edit pointers
-                self.add_edge(vp, self.edge(*edge).from, normal, normal); // TODO FIX EDGE ORDERING MISTAKES :)
-            }
-        }
-    }
-
-    /// subdivide faces by creating triangles in the corners
-    pub fn corner_divide(&mut self) {}
-
     /// cap closed, counter clockwise planar holes by creating faces at these holes.
+    /// With `planar`, every connected part of the graph which lies in a single plane keeps its outer loop open.
+    /// Parts which do not lie in a plane are capped completely.
     pub fn cap(&mut self, planar: bool) {
         self.add_faces_at_holes(planar)
     }
 
     /// if `planar` is true, we calculate the area using the relative planar orientation of a face.
+    /// All loops are compared against the largest loop of their connected part, which runs around the outside of a planar
+    /// graph in the opposite direction of its faces. That outer loop is left open.
     fn add_faces_at_holes(&mut self, planar: bool) {
-        let loops = self.get_loops();
-        for lp in loops {
-            let pts: Vec<Vec3> = self.edges_to_verts(&lp);
-            let polygon = Polygon::new(pts);
-            if polygon.verts.len() < 3 {
-                continue;
-            }
+        let polygons: Vec<(Vec<EdgePtr>, Polygon)> = self
+            .get_loops()
+            .into_iter()
+            .map(|lp| {
+                let polygon = Polygon::new(self.edges_to_verts(&lp));
+                (lp, polygon)
+            })
+            .filter(|(_, polygon)| polygon.verts.len() >= 3)
+            .collect();
+
+        let references = match planar {
+            true => self.outer_loop_poses(&polygons),
+            false => vec![None; polygons.len()],
+        };
+
+        for ((lp, polygon), reference) in polygons.into_iter().zip(references) {
             let pose = polygon.estimate_pose();
 
-            if planar {
-                let area = polygon.posed_signed_area(&pose);
+            if let Some(reference) = &reference {
+                let area = polygon.posed_signed_area(reference);
                 if area > 0.0 {
                     continue;
                 }
@@ -1072,6 +1129,55 @@ impl Polyhedron {
         }
     }
 
+    /// For every loop, the pose of the outer loop of its connected part of the graph,
+    /// looking from the side where that outer loop runs clockwise.
+    /// Loops of a part which does not lie in a single plane get no pose.
+    fn outer_loop_poses(&self, polygons: &[(Vec<EdgePtr>, Polygon)]) -> Vec<Option<Pose>> {
+        // loops sharing a vertex belong to the same part
+        let mut parts = (0..polygons.len()).collect::<Vec<_>>();
+        let mut loop_at_vert = HashMap::new();
+        for (i, (lp, _)) in polygons.iter().enumerate() {
+            for ep in lp {
+                let other = *loop_at_vert.entry(self.edge(*ep).from).or_insert(i);
+                join(&mut parts, other, i);
+            }
+        }
+        let (parts, count) = compact(&mut parts);
+
+        // the largest loop of every part
+        let areas = polygons
+            .iter()
+            .map(|(_, polygon)| polygon.signed_area_planar().abs())
+            .collect::<Vec<_>>();
+        let mut outers: Vec<Option<usize>> = vec![None; count];
+        for (i, part) in parts.iter().enumerate() {
+            let outer = &mut outers[*part];
+            if outer.map_or(true, |o| areas[i] > areas[o]) {
+                *outer = Some(i);
+            }
+        }
+        let mut poses = outers
+            .into_iter()
+            .map(|outer| outer.map(|o| polygons[o].1.clone().flip().estimate_pose()))
+            .collect::<Vec<_>>();
+
+        // drop the pose of parts which do not lie in its plane
+        for (i, (_, polygon)) in polygons.iter().enumerate() {
+            let pose = &mut poses[parts[i]];
+            let flat = pose.as_ref().map_or(false, |pose| {
+                polygon
+                    .verts
+                    .iter()
+                    .all(|vert| pose.transform_point_inv(*vert).z.abs() < VEC_TOLERANCE)
+            });
+            if !flat {
+                *pose = None;
+            }
+        }
+
+        parts.into_iter().map(|part| poses[part].clone()).collect()
+    }
+
     pub fn uncap(&mut self) {
         for edge in self.edges.iter_mut() {
             edge.face = None;
@@ -1259,7 +1365,7 @@ impl Polyhedron {
 
 #[cfg(test)]
 mod tests {
-    use crate::kernel::vec3;
+    use crate::kernel::{vec3, Vec3};
     use crate::solid::{Polyhedron, VertPtr};
 
     #[test]
@@ -1294,4 +1400,73 @@ mod tests {
 
         hedron.print_structure();
     }
+
+    /// Add a unit square at `x`, split by a diagonal
+    fn add_split_square(hedron: &mut Polyhedron, x: f64) {
+        let a: VertPtr = hedron.add_vert(vec3(x, 0., 0.));
+        let b: VertPtr = hedron.add_vert(vec3(x + 1., 0., 0.));
+        let c: VertPtr = hedron.add_vert(vec3(x + 1., 1., 0.));
+        let d: VertPtr = hedron.add_vert(vec3(x, 1., 0.));
+        hedron.add_planar_edge(a, b);
+        hedron.add_planar_edge(b, c);
+        hedron.add_planar_edge(c, d);
+        hedron.add_planar_edge(d, a);
+        hedron.add_planar_edge(a, c);
+    }
+
+    #[test]
+    fn test_cap_planar() {
+        let mut hedron = Polyhedron::new();
+        add_split_square(&mut hedron, 0.);
+
+        // two triangles and the outer loop around the square
+        hedron.cap(false);
+        assert_eq!(hedron.faces.len(), 3);
+
+        // the planar cap leaves the outer loop open
+        hedron.uncap();
+        hedron.cap(true);
+        assert_eq!(hedron.faces.len(), 2);
+        for face in hedron.faces.iter() {
+            assert!(face.normal.dot(Vec3::Z) > 0.99);
+        }
+    }
+
+    #[test]
+    fn test_cap_planar_parts() {
+        // every square keeps its own outer loop open
+        let mut hedron = Polyhedron::new();
+        add_split_square(&mut hedron, 0.);
+        add_split_square(&mut hedron, 3.);
+        hedron.cap(true);
+        assert_eq!(hedron.faces.len(), 4);
+        for face in hedron.faces.iter() {
+            assert!(face.normal.dot(Vec3::Z) > 0.99);
+        }
+    }
+
+    #[test]
+    fn test_cap_planar_closed() {
+        // a closed graph has no outer loop, so all of its faces are capped again
+        let verts = [-1.0, 1.0]
+            .into_iter()
+            .flat_map(|x| {
+                [-1.0, 1.0]
+                    .into_iter()
+                    .flat_map(move |y| [-1.0, 1.0].map(|z| vec3(x, y, z)))
+            })
+            .collect::<Vec<_>>();
+        let faces = vec![
+            vec![0, 1, 3, 2],
+            vec![4, 6, 7, 5],
+            vec![0, 4, 5, 1],
+            vec![2, 3, 7, 6],
+            vec![0, 2, 6, 4],
+            vec![1, 5, 7, 3],
+        ];
+        let mut hedron = Polyhedron::from_faces(&verts, &faces).unwrap();
+        hedron.uncap();
+        hedron.cap(true);
+        assert_eq!(hedron.faces.len(), 6);
+    }
 }
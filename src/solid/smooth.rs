@@ -0,0 +1,399 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{Mesh, Normals, Polyhedron, TriMesh, VertPtr, WELD_TOLERANCE};
+use crate::kernel::{fxx, Vec3, EPSILON};
+use crate::planar::Polygon;
+
+/// How every vertex is pulled towards its neighbors, during one iteration of smoothing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Move `factor` of the way towards the average of the neighbors.
+    /// Quickly evens out a mesh, but also shrinks it.
+    Uniform { factor: fxx },
+    /// Like `Uniform`, but neighbors are weighted by the cotangents of the angles opposite to their edges.
+    /// This follows the curvature of the surface, so vertices hardly slide along flat regions.
+    Cotangent { factor: fxx },
+    /// Taubin's λ|μ smoothing: a uniform step of `lambda`, followed by an inflating uniform step of `mu`.
+    /// `mu` should be negative, and slightly larger than `lambda`, like 0.5 and -0.53. This barely shrinks.
+    Taubin { lambda: fxx, mu: fxx },
+}
+
+/// The settings of [`Mesh::smooth`] and [`Polyhedron::smooth`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmoothOptions {
+    pub smoothing: Smoothing,
+    pub iterations: usize,
+    /// These vertices never move
+    pub pinned: Vec<usize>,
+    /// Keep the vertices on naked edges in place
+    pub preserve_naked_edges: bool,
+}
+
+impl SmoothOptions {
+    pub fn new(smoothing: Smoothing, iterations: usize) -> Self {
+        Self {
+            smoothing,
+            iterations,
+            pinned: Vec::new(),
+            preserve_naked_edges: false,
+        }
+    }
+
+    pub fn with_pinned(mut self, pinned: impl IntoIterator<Item = usize>) -> Self {
+        self.pinned.extend(pinned);
+        self
+    }
+
+    pub fn with_preserved_naked_edges(mut self) -> Self {
+        self.preserve_naked_edges = true;
+        self
+    }
+}
+
+/// The connectivity needed for smoothing, as indices into a list of positions
+struct Surface {
+    /// Unique edges, as (smallest, largest) pairs
+    edges: Vec<(usize, usize)>,
+    /// Used for cotangent weights
+    triangles: Vec<[usize; 3]>,
+    fixed: Vec<bool>,
+}
+
+impl Surface {
+    fn new(count: usize, triangles: Vec<[usize; 3]>, extra_edges: &[(usize, usize)]) -> Self {
+        let mut seen = HashSet::new();
+        let edges = triangles
+            .iter()
+            .flat_map(|[a, b, c]| [(*a, *b), (*b, *c), (*c, *a)])
+            .chain(extra_edges.iter().copied())
+            .filter(|(a, b)| a != b)
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .filter(|key| seen.insert(*key))
+            .collect();
+        Self {
+            edges,
+            triangles,
+            fixed: vec![false; count],
+        }
+    }
+
+    fn smooth(&self, verts: &mut [Vec3], smoothing: Smoothing, iterations: usize) {
+        for _ in 0..iterations {
+            match smoothing {
+                Smoothing::Uniform { factor } => self.relax(verts, factor, false),
+                Smoothing::Cotangent { factor } => self.relax(verts, factor, true),
+                Smoothing::Taubin { lambda, mu } => {
+                    self.relax(verts, lambda, false);
+                    self.relax(verts, mu, false);
+                }
+            }
+        }
+    }
+
+    /// Move every free vertex `factor` of the way towards the weighted average of its neighbors
+    fn relax(&self, verts: &mut [Vec3], factor: fxx, cotangent: bool) {
+        let weights = match cotangent {
+            true => self.cotangent_weights(verts),
+            false => HashMap::new(),
+        };
+        let mut sums = vec![(Vec3::ZERO, 0.0); verts.len()];
+        let mut uniform = vec![(Vec3::ZERO, 0.0); verts.len()];
+        for (a, b) in self.edges.iter().copied() {
+            let weight = weights.get(&(a, b)).copied().unwrap_or(0.0);
+            for (from, to) in [(a, b), (b, a)] {
+                sums[from].0 += verts[to] * weight;
+                sums[from].1 += weight;
+                uniform[from].0 += verts[to];
+                uniform[from].1 += 1.0;
+            }
+        }
+        for (v, vert) in verts.iter_mut().enumerate() {
+            if self.fixed[v] {
+                continue;
+            }
+            // vertices without usable cotangents fall back to the uniform average
+            let (sum, weight) = match sums[v].1 > EPSILON {
+                true => sums[v],
+                false => uniform[v],
+            };
+            if weight > 0.0 {
+                *vert += (sum / weight - *vert) * factor;
+            }
+        }
+    }
+
+    /// Per edge, half the sum of the cotangents of the angles opposite to it.
+    /// Negative weights, found at obtuse angles, are clamped to zero to keep things stable.
+    fn cotangent_weights(&self, verts: &[Vec3]) -> HashMap<(usize, usize), fxx> {
+        let mut weights = HashMap::new();
+        for [a, b, c] in self.triangles.iter().copied() {
+            for (from, to, opposite) in [(a, b, c), (b, c, a), (c, a, b)] {
+                let (u, v) = (verts[from] - verts[opposite], verts[to] - verts[opposite]);
+                let cross = u.cross(v).length();
+                if cross < EPSILON {
+                    continue;
+                }
+                *weights.entry((from.min(to), from.max(to))).or_insert(0.0) +=
+                    0.5 * u.dot(v) / cross;
+            }
+        }
+        for weight in weights.values_mut() {
+            *weight = weight.max(0.0);
+        }
+        weights
+    }
+}
+
+impl Mesh {
+    /// Smooth the mesh, by repeatedly moving vertices towards their neighbors.
+    ///
+    /// Vertices are welded by position, so seams in the uvs or normals don't tear the surface apart.
+    /// `pinned` contains vertex indices, which pin every vertex at the same position.
+    /// Naked edges are found after welding. Normals are recalculated.
+    pub fn smooth(&self, options: &SmoothOptions) -> Mesh {
+        let position = TriMesh::weld_roots(&self.verts, WELD_TOLERANCE);
+        let tri = self.tri.iter().map(|v| position[*v]).collect::<Vec<_>>();
+        let triangles = tri
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .filter(|[a, b, c]| a != b && b != c && c != a)
+            .collect::<Vec<_>>();
+
+        let mut surface = Surface::new(self.verts.len(), triangles, &[]);
+        for v in options.pinned.iter().filter(|v| **v < self.verts.len()) {
+            surface.fixed[position[*v]] = true;
+        }
+        if options.preserve_naked_edges {
+            let welded = Mesh::new(self.verts.clone(), tri, Vec::new(), Normals::None);
+            for (a, b) in welded.iter_naked_edges() {
+                surface.fixed[a] = true;
+                surface.fixed[b] = true;
+            }
+        }
+
+        let mut verts = self.verts.clone();
+        surface.smooth(&mut verts, options.smoothing, options.iterations);
+
+        let mut mesh = self.clone();
+        mesh.verts = position.iter().map(|p| verts[*p]).collect();
+        match self.normals {
+            Normals::None => mesh,
+            Normals::Vertex(_) => mesh.with_vertex_normals(),
+            Normals::Face(_) => mesh.with_face_normals(),
+        }
+    }
+}
+
+impl Polyhedron {
+    /// Smooth the polyhedron, by repeatedly moving vertices towards their neighbors along its edges.
+    ///
+    /// `pinned` contains vertex pointers. Naked edges are edges with a face on only one side,
+    /// so a polyhedron without any faces, like an uncapped planar partition, has none.
+    /// Cotangent weights are taken from a fan triangulation of every face,
+    /// and vertices without any faces are smoothed uniformly.
+    pub fn smooth(&self, options: &SmoothOptions) -> Polyhedron {
+        let ids = self
+            .verts
+            .iter_ids()
+            .enumerate()
+            .map(|(i, vp)| (vp, i))
+            .collect::<HashMap<VertPtr, usize>>();
+        let edges = self
+            .all_unique_edges()
+            .into_iter()
+            .map(|ep| {
+                (
+                    ids[&self.edge(ep).from],
+                    ids[&self.edge(self.edge(ep).twin).from],
+                )
+            })
+            .collect::<Vec<_>>();
+        let triangles = self
+            .faces
+            .iter()
+            .flat_map(|face| {
+                let verts = self
+                    .get_loop(face.edge)
+                    .into_iter()
+                    .map(|ep| ids[&self.edge(ep).from])
+                    .collect::<Vec<_>>();
+                (1..verts.len().saturating_sub(1))
+                    .map(move |i| [verts[0], verts[i], verts[i + 1]])
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let mut surface = Surface::new(ids.len(), triangles, &edges);
+        for vp in options.pinned.iter() {
+            if let Some(v) = ids.get(vp) {
+                surface.fixed[*v] = true;
+            }
+        }
+        if options.preserve_naked_edges {
+            for ep in self.all_unique_edges() {
+                let (edge, twin) = (self.edge(ep), self.edge(self.edge(ep).twin));
+                if edge.face.is_some() != twin.face.is_some() {
+                    surface.fixed[ids[&edge.from]] = true;
+                    surface.fixed[ids[&twin.from]] = true;
+                }
+            }
+        }
+
+        let mut verts = self.verts.iter().map(|vert| vert.pos).collect::<Vec<_>>();
+        surface.smooth(&mut verts, options.smoothing, options.iterations);
+
+        let mut hedron = self.clone();
+        for (vert, pos) in hedron.verts.iter_mut().zip(verts) {
+            vert.pos = pos;
+        }
+        let planes = hedron
+            .faces
+            .iter()
+            .map(|face| Polygon::new(hedron.edges_to_verts(&hedron.get_loop(face.edge))))
+            .map(|polygon| match polygon.verts.len() > 2 {
+                true => Some((polygon.center(), polygon.average_normal())),
+                false => None,
+            })
+            .collect::<Vec<_>>();
+        for (face, plane) in hedron.faces.iter_mut().zip(planes) {
+            if let Some((center, normal)) = plane {
+                face.center = center;
+                face.normal = normal;
+            }
+        }
+        hedron
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SmoothOptions, Smoothing};
+    use crate::kernel::{fxx, vec3, Vec3};
+    use crate::solid::{Mesh, Polyhedron};
+
+    /// a flat hexagonal grid, with its vertices shifted a little, in a repeatable way
+    fn jittered_grid(z: fxx) -> Mesh {
+        let mut grid = Mesh::new_hexagrid(1.0, 4);
+        for (i, vert) in grid.verts.iter_mut().enumerate() {
+            let i = i as fxx;
+            *vert += vec3((i * 1.3).sin(), (i * 2.7).cos(), (i * 0.9).sin() * z) * 0.02;
+        }
+        grid
+    }
+
+    fn naked_verts(mesh: &Mesh) -> Vec<usize> {
+        mesh.iter_naked_edges().flat_map(|(a, b)| [a, b]).collect()
+    }
+
+    #[test]
+    fn test_smooth_uniform() {
+        let grid = jittered_grid(5.0);
+        let options = SmoothOptions::new(Smoothing::Uniform { factor: 0.5 }, 20);
+        let bumpiness = |mesh: &Mesh| mesh.verts.iter().map(|v| v.z.abs()).sum::<fxx>();
+
+        let smooth = grid.smooth(&options.clone().with_preserved_naked_edges());
+        assert!(bumpiness(&smooth) < bumpiness(&grid) * 0.5);
+        for v in naked_verts(&grid) {
+            assert_eq!(smooth.verts[v], grid.verts[v]);
+        }
+
+        // without preserving them, the boundary shrinks inwards
+        let shrunk = grid.smooth(&options.clone().with_pinned([0]));
+        assert_eq!(shrunk.verts[0], grid.verts[0]);
+        assert!(naked_verts(&grid)
+            .into_iter()
+            .any(|v| shrunk.verts[v].length() < grid.verts[v].length() - 0.01));
+    }
+
+    #[test]
+    fn test_smooth_cotangent() {
+        // a flat mesh is already as smooth as it gets, no matter how its vertices are spread out
+        let grid = jittered_grid(0.0);
+        let naked = naked_verts(&grid);
+        let options = SmoothOptions::new(Smoothing::Cotangent { factor: 0.5 }, 5);
+        let smooth = grid.smooth(&options.with_preserved_naked_edges());
+        for (a, b) in grid.verts.iter().zip(smooth.verts.iter()) {
+            assert!((*a - *b).length() < 1e-4);
+        }
+
+        // unlike a uniform laplacian, which evens out the spacing
+        let options = SmoothOptions::new(Smoothing::Uniform { factor: 0.5 }, 5);
+        let uniform = grid.smooth(&options.with_preserved_naked_edges());
+        let moved = (0..grid.verts.len())
+            .filter(|v| !naked.contains(v))
+            .map(|v| (grid.verts[v] - uniform.verts[v]).length())
+            .fold(0.0, fxx::max);
+        assert!(moved > 1e-3);
+    }
+
+    #[test]
+    fn test_smooth_taubin() {
+        let sphere = Mesh::new_icosahedron(1.0).loop_subdivide(2, &[]);
+        let volume = sphere.volume().unwrap();
+        let uniform = sphere.smooth(&SmoothOptions::new(Smoothing::Uniform { factor: 0.5 }, 10));
+        let taubin = sphere.smooth(&SmoothOptions::new(
+            Smoothing::Taubin {
+                lambda: 0.5,
+                mu: -0.53,
+            },
+            10,
+        ));
+        let uniform_loss = 1.0 - uniform.volume().unwrap() / volume;
+        let taubin_loss = 1.0 - taubin.volume().unwrap() / volume;
+        assert!(uniform_loss > 0.05);
+        assert!(taubin_loss.abs() < uniform_loss * 0.2);
+    }
+
+    #[test]
+    fn test_smooth_seams() {
+        // the seams of a cube don't open up, and its corners stay pinned
+        let cube = Mesh::new_cube(1.0).loop_subdivide(1, &[]);
+        let pinned = (0..cube.verts.len())
+            .filter(|v| cube.verts[*v].abs().min_element() > 0.5)
+            .collect::<Vec<_>>();
+        let options =
+            SmoothOptions::new(Smoothing::Uniform { factor: 0.5 }, 3).with_pinned(pinned.clone());
+        let smooth = cube.smooth(&options);
+        assert!(smooth.is_watertight());
+        assert!(!pinned.is_empty());
+        for v in pinned {
+            assert_eq!(smooth.verts[v], cube.verts[v]);
+        }
+        assert_eq!(smooth.uvs, cube.uvs);
+    }
+
+    #[test]
+    fn test_smooth_polyhedron() {
+        // a 3 by 3 grid of quads, with a bump in the middle
+        let verts = (0..16)
+            .map(|i| vec3((i % 4) as fxx, (i / 4) as fxx, 0.0))
+            .map(|v| match v.x == 1.0 && v.y == 1.0 {
+                true => v + Vec3::Z,
+                false => v,
+            })
+            .collect::<Vec<_>>();
+        let faces = (0..9)
+            .map(|f| {
+                let corner = f % 3 + (f / 3) * 4;
+                vec![corner, corner + 1, corner + 5, corner + 4]
+            })
+            .collect::<Vec<_>>();
+        let grid = Polyhedron::from_faces(&verts, &faces).unwrap();
+
+        for smoothing in [
+            Smoothing::Uniform { factor: 0.5 },
+            Smoothing::Cotangent { factor: 0.5 },
+        ] {
+            let options = SmoothOptions::new(smoothing, 10).with_preserved_naked_edges();
+            let smooth = grid.smooth(&options);
+            let bump = smooth.vert(5).pos;
+            assert!(bump.z < 0.2 && bump.z > 0.0, "{bump}");
+            for vp in grid.naked_vps() {
+                assert_eq!(smooth.vert(vp).pos, grid.vert(vp).pos);
+            }
+            for face in smooth.faces.iter() {
+                assert!(face.normal.z > 0.9);
+            }
+        }
+    }
+}
@@ -0,0 +1,616 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{Mesh, Normals, Polyhedron, TriMesh, VertPtr, WELD_TOLERANCE};
+use crate::kernel::{fxx, Vec3, PI};
+
+/// The key of an undirected edge
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// The undirected edges of a set of polygons, in order of appearance, together with the polygons using them
+struct Edges {
+    ids: HashMap<(usize, usize), usize>,
+    keys: Vec<(usize, usize)>,
+    faces: Vec<Vec<usize>>,
+}
+
+impl Edges {
+    fn new<F: AsRef<[usize]>>(faces: &[F]) -> Self {
+        let mut edges = Self {
+            ids: HashMap::new(),
+            keys: Vec::new(),
+            faces: Vec::new(),
+        };
+        for (f, face) in faces.iter().enumerate() {
+            let face = face.as_ref();
+            for i in 0..face.len() {
+                let key = edge_key(face[i], face[(i + 1) % face.len()]);
+                let id = *edges.ids.entry(key).or_insert_with(|| {
+                    edges.keys.push(key);
+                    edges.faces.push(Vec::new());
+                    edges.keys.len() - 1
+                });
+                edges.faces[id].push(f);
+            }
+        }
+        edges
+    }
+
+    fn id(&self, a: usize, b: usize) -> usize {
+        self.ids[&edge_key(a, b)]
+    }
+
+    /// Boundary edges, non-manifold edges and creases are not smoothed across
+    fn is_sharp(&self, edge: usize, creases: &HashSet<(usize, usize)>) -> bool {
+        self.faces[edge].len() != 2 || creases.contains(&self.keys[edge])
+    }
+
+    /// Per vertex, the other ends of all its edges, and the other ends of its sharp edges
+    fn neighbors(
+        &self,
+        count: usize,
+        creases: &HashSet<(usize, usize)>,
+    ) -> Vec<(Vec<usize>, Vec<usize>)> {
+        let mut neighbors = vec![(Vec::new(), Vec::new()); count];
+        for (edge, (a, b)) in self.keys.iter().copied().enumerate() {
+            let sharp = self.is_sharp(edge, creases);
+            for (from, to) in [(a, b), (b, a)] {
+                neighbors[from].0.push(to);
+                if sharp {
+                    neighbors[from].1.push(to);
+                }
+            }
+        }
+        neighbors
+    }
+}
+
+/// The new position of a vertex touching sharp edges, or `None` if the smooth rule applies.
+/// Corners stay in place, and vertices along a boundary or crease follow the cubic b-spline of that curve.
+fn sharp_vertex(vert: Vec3, all: &[usize], sharp: &[usize], verts: &[Vec3]) -> Option<Vec3> {
+    match sharp.len() {
+        0 | 1 => None,
+        2 if all.len() > 2 => Some(vert * 0.75 + (verts[sharp[0]] + verts[sharp[1]]) * 0.125),
+        _ => Some(vert),
+    }
+}
+
+/// Polygons as indices into a list of vertices, with some edges tagged as creases
+struct Polygons {
+    verts: Vec<Vec3>,
+    faces: Vec<Vec<usize>>,
+    creases: HashSet<(usize, usize)>,
+}
+
+impl Polygons {
+    /// Split every polygon into quads around its center. If `smooth`, apply the Catmull-Clark rules.
+    fn quad_step(&self, smooth: bool) -> Self {
+        let edges = Edges::new(&self.faces);
+        let (vert_count, edge_count) = (self.verts.len(), edges.keys.len());
+        let centers = self
+            .faces
+            .iter()
+            .map(|face| face.iter().map(|v| self.verts[*v]).sum::<Vec3>() / face.len() as fxx)
+            .collect::<Vec<_>>();
+
+        let edge_points = edges.keys.iter().enumerate().map(|(edge, (a, b))| {
+            let mid = (self.verts[*a] + self.verts[*b]) * 0.5;
+            if !smooth || edges.is_sharp(edge, &self.creases) {
+                return mid;
+            }
+            let faces = &edges.faces[edge];
+            (mid + (centers[faces[0]] + centers[faces[1]]) * 0.5) * 0.5
+        });
+
+        let mut vert_points = self.verts.clone();
+        if smooth {
+            let mut faces_around = vec![Vec::new(); vert_count];
+            for (f, face) in self.faces.iter().enumerate() {
+                for v in face {
+                    faces_around[*v].push(f);
+                }
+            }
+            let neighbors = edges.neighbors(vert_count, &self.creases);
+            for (v, (all, sharp)) in neighbors.iter().enumerate() {
+                let vert = self.verts[v];
+                if all.is_empty() {
+                    continue;
+                }
+                vert_points[v] = sharp_vertex(vert, all, sharp, &self.verts).unwrap_or_else(|| {
+                    // (Q + 2R + (n - 3)S) / n
+                    let n = all.len() as fxx;
+                    let q = faces_around[v].iter().map(|f| centers[*f]).sum::<Vec3>()
+                        / faces_around[v].len() as fxx;
+                    let r = all
+                        .iter()
+                        .map(|nb| (vert + self.verts[*nb]) * 0.5)
+                        .sum::<Vec3>()
+                        / n;
+                    (q + r * 2.0 + vert * (n - 3.0)) / n
+                });
+            }
+        }
+
+        let mut verts = vert_points;
+        verts.extend(edge_points);
+        verts.extend(centers);
+
+        let mut faces = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let center = vert_count + edge_count + f;
+            for i in 0..face.len() {
+                let (prev, vert, next) = (
+                    face[(i + face.len() - 1) % face.len()],
+                    face[i],
+                    face[(i + 1) % face.len()],
+                );
+                faces.push(vec![
+                    vert,
+                    vert_count + edges.id(vert, next),
+                    center,
+                    vert_count + edges.id(prev, vert),
+                ]);
+            }
+        }
+
+        Self {
+            verts,
+            faces,
+            creases: self.split_creases(&edges),
+        }
+    }
+
+    /// Cut the corners off every polygon, leaving a polygon of edge midpoints in the middle
+    fn corner_step(&self) -> Self {
+        let edges = Edges::new(&self.faces);
+        let vert_count = self.verts.len();
+        let mut verts = self.verts.clone();
+        verts.extend(
+            edges
+                .keys
+                .iter()
+                .map(|(a, b)| (self.verts[*a] + self.verts[*b]) * 0.5),
+        );
+
+        let mut faces = Vec::new();
+        for face in self.faces.iter() {
+            let mids = (0..face.len())
+                .map(|i| vert_count + edges.id(face[i], face[(i + 1) % face.len()]))
+                .collect::<Vec<_>>();
+            for i in 0..face.len() {
+                faces.push(vec![
+                    face[i],
+                    mids[i],
+                    mids[(i + mids.len() - 1) % mids.len()],
+                ]);
+            }
+            faces.push(mids);
+        }
+
+        Self {
+            verts,
+            faces,
+            creases: self.split_creases(&edges),
+        }
+    }
+
+    /// Every crease continues as the two halves it was split into.
+    /// Assumes the midpoint of edge `e` was added at index `verts.len() + e`.
+    fn split_creases(&self, edges: &Edges) -> HashSet<(usize, usize)> {
+        self.creases
+            .iter()
+            .filter_map(|key| {
+                edges
+                    .ids
+                    .get(key)
+                    .map(|edge| (key, self.verts.len() + edge))
+            })
+            .flat_map(|((a, b), mid)| [edge_key(*a, mid), edge_key(mid, *b)])
+            .collect()
+    }
+}
+
+impl Polyhedron {
+    /// Catmull-Clark subdivision, repeated `iterations` times. Every n-gon becomes n quads.
+    ///
+    /// Only the loops which are faces are subdivided, so [`Polyhedron::cap`] a polyhedron first.
+    /// Edges with a face on only one side are boundaries, and stay sharp, just like the edges between the vertex pairs in `creases`.
+    /// Faces which visit a vertex more than once are skipped.
+    ///
+    /// Returns `None` if the faces are not consistently oriented, see [`Polyhedron::from_faces`].
+    pub fn catmull_clark(
+        &self,
+        iterations: usize,
+        creases: &[(VertPtr, VertPtr)],
+    ) -> Option<Polyhedron> {
+        let mut polygons = self.to_polygons(creases);
+        for _ in 0..iterations {
+            polygons = polygons.quad_step(true);
+        }
+        polygons.to_polyhedron()
+    }
+
+    /// subdivide by splitting every face into quads around its center, without any smoothing.
+    /// Polyhedra with inconsistently oriented faces are left as they are
+    pub fn quad_divide(&mut self) {
+        if let Some(divided) = self.to_polygons(&[]).quad_step(false).to_polyhedron() {
+            *self = divided;
+        }
+    }
+
+    /// subdivide faces by creating triangles in the corners, and a face of edge midpoints in the middle.
+    /// Polyhedra with inconsistently oriented faces are left as they are
+    pub fn corner_divide(&mut self) {
+        if let Some(divided) = self.to_polygons(&[]).corner_step().to_polyhedron() {
+            *self = divided;
+        }
+    }
+
+    fn to_polygons(&self, creases: &[(VertPtr, VertPtr)]) -> Polygons {
+        let ids = self
+            .verts
+            .iter_ids()
+            .enumerate()
+            .map(|(i, vp)| (vp, i))
+            .collect::<HashMap<_, _>>();
+        let faces = self
+            .faces
+            .iter()
+            .map(|face| {
+                self.get_loop(face.edge)
+                    .iter()
+                    .map(|ep| ids[&self.edge(*ep).from])
+                    .collect::<Vec<_>>()
+            })
+            .filter(|face| face.iter().collect::<HashSet<_>>().len() == face.len())
+            .collect();
+        let creases = creases
+            .iter()
+            .filter_map(|(a, b)| Some(edge_key(*ids.get(a)?, *ids.get(b)?)))
+            .collect();
+        Polygons {
+            verts: self.verts.iter().map(|vert| vert.pos).collect(),
+            faces,
+            creases,
+        }
+    }
+}
+
+impl Polygons {
+    /// Subdividing keeps the orientation of the faces, so this only fails if the faces were inconsistent to begin with
+    fn to_polyhedron(&self) -> Option<Polyhedron> {
+        Polyhedron::from_faces(&self.verts, &self.faces)
+    }
+}
+
+impl Mesh {
+    /// Loop subdivision, repeated `iterations` times. Every triangle becomes four.
+    ///
+    /// Vertices are welded by position, so seams in the uvs or normals don't tear the surface apart.
+    /// Naked edges stay sharp, just like the edges between the vertex index pairs in `creases`.
+    /// Uvs and vertex attributes are interpolated linearly, and normals are recalculated.
+    pub fn loop_subdivide(&self, iterations: usize, creases: &[(usize, usize)]) -> Mesh {
+        let mut mesh = self.clone();
+        let mut creases = creases.to_vec();
+        for _ in 0..iterations {
+            (mesh, creases) = mesh.loop_step(&creases);
+        }
+        mesh
+    }
+
+    fn loop_step(&self, creases: &[(usize, usize)]) -> (Mesh, Vec<(usize, usize)>) {
+        let position = TriMesh::weld_roots(&self.verts, WELD_TOLERANCE);
+        let (faces, triangles): (Vec<_>, Vec<_>) = self
+            .iter_triangles()
+            .map(|(a, b, c)| [a, b, c])
+            .enumerate()
+            .filter(|(_, tri)| {
+                let [a, b, c] = tri.map(|v| position[v]);
+                a != b && b != c && c != a
+            })
+            .unzip();
+        let welded = triangles
+            .iter()
+            .map(|tri| tri.map(|v| position[v]))
+            .collect::<Vec<_>>();
+        let crease_keys = creases
+            .iter()
+            .map(|(a, b)| edge_key(position[*a], position[*b]))
+            .collect::<HashSet<_>>();
+
+        // the smoothed positions of the edges and the welded vertices
+        let edges = Edges::new(&welded);
+        let edge_points = edges
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(edge, (a, b))| {
+                let (a, b) = (self.verts[*a], self.verts[*b]);
+                if edges.is_sharp(edge, &crease_keys) {
+                    return (a + b) * 0.5;
+                }
+                let opposite = edges.faces[edge]
+                    .iter()
+                    .map(|f| {
+                        let (a, b) = edges.keys[edge];
+                        let opposite = welded[*f].into_iter().find(|v| *v != a && *v != b);
+                        self.verts[opposite.expect("welded triangles have three distinct vertices")]
+                    })
+                    .sum::<Vec3>();
+                (a + b) * 0.375 + opposite * 0.125
+            })
+            .collect::<Vec<_>>();
+        let mut vert_points = self.verts.clone();
+        for (v, (all, sharp)) in edges
+            .neighbors(self.verts.len(), &crease_keys)
+            .iter()
+            .enumerate()
+        {
+            let vert = self.verts[v];
+            if all.is_empty() {
+                continue;
+            }
+            vert_points[v] = sharp_vertex(vert, all, sharp, &self.verts).unwrap_or_else(|| {
+                // Loop's original weights
+                let n = all.len() as fxx;
+                let cos = 0.375 + 0.25 * (2.0 * PI / n).cos();
+                let beta = (0.625 - cos * cos) / n;
+                vert * (1.0 - n * beta) + all.iter().map(|nb| self.verts[*nb]).sum::<Vec3>() * beta
+            });
+        }
+
+        // rebuild per render vertex, so seams stay seams
+        let mut verts = position.iter().map(|p| vert_points[*p]).collect::<Vec<_>>();
+        let mut uvs = match self.uvs.len() == self.verts.len() {
+            true => self.uvs.clone(),
+            false => Vec::new(),
+        };
+        let identity = (0..self.verts.len()).collect::<Vec<_>>();
+        let mut attributes = self.vert_attributes.select(self.verts.len(), &identity);
+        let mut mids = HashMap::new();
+        let mut new_creases = Vec::new();
+        let mut tri = Vec::new();
+        for [a, b, c] in triangles {
+            let [ab, bc, ca] = [(a, b), (b, c), (c, a)].map(|(from, to)| {
+                *mids.entry(edge_key(from, to)).or_insert_with(|| {
+                    let mid = verts.len();
+                    verts.push(edge_points[edges.id(position[from], position[to])]);
+                    if !uvs.is_empty() {
+                        uvs.push(uvs[from].lerp(uvs[to], 0.5));
+                    }
+                    attributes.push_lerp(from, to, 0.5);
+                    if crease_keys.contains(&edge_key(position[from], position[to])) {
+                        new_creases.extend([(from, mid), (mid, to)]);
+                    }
+                    mid
+                })
+            });
+            tri.extend([a, ab, ca, b, bc, ab, c, ca, bc, ab, bc, ca]);
+        }
+
+        let mut mesh = Mesh::new(verts, tri, uvs, Normals::None);
+        mesh.vert_attributes = attributes;
+        let faces = faces.iter().flat_map(|f| [*f; 4]).collect::<Vec<_>>();
+        mesh.tri_attributes = self.tri_attributes.select(self.count_triangles(), &faces);
+        let mesh = match self.normals {
+            Normals::None => mesh,
+            Normals::Vertex(_) => mesh.with_vertex_normals(),
+            Normals::Face(_) => mesh.with_face_normals(),
+        };
+        (mesh, new_creases)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kernel::{fxx, vec3, Vec3};
+    use crate::solid::{Mesh, Polyhedron};
+
+    fn cube_cage() -> Polyhedron {
+        let verts = (0..8)
+            .map(|i| vec3((i & 1) as fxx, ((i >> 1) & 1) as fxx, (i >> 2) as fxx) * 2.0 - 1.0)
+            .collect::<Vec<_>>();
+        let faces = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ]
+        .map(|face| face.to_vec());
+        Polyhedron::from_faces(&verts, &faces).unwrap()
+    }
+
+    /// vertices, undirected edges and faces
+    fn counts(hedron: &Polyhedron) -> (usize, usize, usize) {
+        (
+            hedron.verts.len(),
+            hedron.edges.len() / 2,
+            hedron.faces.len(),
+        )
+    }
+
+    fn is_closed(hedron: &Polyhedron) -> bool {
+        hedron.edges.iter().all(|edge| edge.face.is_some())
+    }
+
+    #[test]
+    fn test_from_faces() {
+        let cube = cube_cage();
+        assert_eq!(counts(&cube), (8, 12, 6));
+        assert!(is_closed(&cube));
+        for face in cube.faces.iter() {
+            assert_eq!(cube.get_loop(face.edge).len(), 4);
+            assert!(face.normal.dot(face.center) > 0.0);
+        }
+
+        // an open fan gets a single hole loop
+        let verts = [Vec3::ZERO, Vec3::X, Vec3::Y, -Vec3::X];
+        let fan = Polyhedron::from_faces(&verts, &[vec![0, 1, 2], vec![0, 2, 3]]).unwrap();
+        let holes = fan
+            .get_loops()
+            .into_iter()
+            .filter(|lp| fan.edge(lp[0]).face.is_none())
+            .collect::<Vec<_>>();
+        assert_eq!(holes.len(), 1);
+        assert_eq!(holes[0].len(), 4);
+
+        // inconsistent orientation
+        assert!(Polyhedron::from_faces(&verts, &[vec![0, 1, 2], vec![0, 1, 3]]).is_none());
+    }
+
+    #[test]
+    fn test_catmull_clark_cube() {
+        let cage = cube_cage();
+        let once = cage.catmull_clark(1, &[]).unwrap();
+        assert_eq!(counts(&once), (26, 48, 24));
+        let smooth = cage.catmull_clark(3, &[]).unwrap();
+        assert_eq!(smooth.faces.len(), 6 * 4 * 4 * 4);
+        assert!(is_closed(&smooth));
+        let (v, e, f) = counts(&smooth);
+        assert_eq!(v + f - e, 2);
+
+        // shrinks to a rounded blob inside the cage, still symmetric
+        let radii = smooth
+            .verts
+            .iter()
+            .map(|vert| vert.pos.length())
+            .collect::<Vec<_>>();
+        assert!(radii.iter().all(|r| *r < 1.0 && *r > 0.5));
+        let corner = once.verts.get(0).unwrap().pos;
+        assert!((corner - Vec3::splat(-5.0 / 9.0)).length() < 1e-6);
+        let center = smooth.verts.iter().map(|vert| vert.pos).sum::<Vec3>();
+        assert!(center.length() < 1e-4);
+    }
+
+    #[test]
+    fn test_catmull_clark_creases() {
+        // with all edges sharp, the cube stays a cube
+        let cage = cube_cage();
+        let all = cage
+            .all_unique_edges()
+            .into_iter()
+            .map(|ep| (cage.edge(ep).from, cage.edge(cage.edge(ep).twin).from))
+            .collect::<Vec<_>>();
+        let sharp = cage.catmull_clark(2, &all).unwrap();
+        for vert in sharp.verts.iter() {
+            assert!((vert.pos.abs().max_element() - 1.0).abs() < 1e-6);
+        }
+
+        // a single crease ring around the middle keeps those edges straight
+        let ring = [(0, 1), (1, 3), (3, 2), (2, 0)];
+        let creased = cage.catmull_clark(2, &ring).unwrap();
+        let bottom = creased
+            .verts
+            .iter()
+            .filter(|vert| (vert.pos.z + 1.0).abs() < 1e-6)
+            .count();
+        assert!(bottom > 0);
+        let plain = cage.catmull_clark(2, &[]).unwrap();
+        assert!(plain.verts.iter().all(|vert| vert.pos.z > -0.99));
+    }
+
+    #[test]
+    fn test_catmull_clark_boundary() {
+        // an open square stays flat, and its corners stay in place
+        let verts = [Vec3::ZERO, Vec3::X, vec3(1.0, 1.0, 0.0), Vec3::Y];
+        let square = Polyhedron::from_faces(&verts, &[vec![0, 1, 2, 3]]).unwrap();
+        let smooth = square.catmull_clark(2, &[]).unwrap();
+        assert_eq!(smooth.faces.len(), 16);
+        assert!(smooth.verts.iter().all(|vert| vert.pos.z.abs() < 1e-6));
+        for corner in verts {
+            assert!(smooth.verts.iter().any(|vert| vert.pos == corner));
+        }
+        // boundary vertices stay on the boundary
+        for vp in smooth.naked_vps() {
+            let pos = smooth.vert(vp).pos;
+            let on_edge = pos.x.abs() < 1e-6
+                || pos.y.abs() < 1e-6
+                || (pos.x - 1.0).abs() < 1e-6
+                || (pos.y - 1.0).abs() < 1e-6;
+            assert!(on_edge, "{pos}");
+        }
+    }
+
+    #[test]
+    fn test_quad_and_corner_divide() {
+        let mut quads = cube_cage();
+        quads.quad_divide();
+        assert_eq!(counts(&quads), (26, 48, 24));
+        assert!(is_closed(&quads));
+        assert!(quads
+            .verts
+            .iter()
+            .all(|vert| (vert.pos.abs().max_element() - 1.0).abs() < 1e-6));
+
+        let mut corners = cube_cage();
+        corners.corner_divide();
+        assert_eq!(counts(&corners), (20, 48, 30));
+        assert!(is_closed(&corners));
+    }
+
+    #[test]
+    fn test_loop_sphere() {
+        let ico = Mesh::new_icosahedron(1.0);
+        let radius = ico.verts[0].length();
+        let smooth = ico.loop_subdivide(2, &[]);
+        assert_eq!(smooth.tri.len(), ico.tri.len() * 16);
+        assert!(smooth.is_watertight());
+        for vert in smooth.verts.iter() {
+            let r = vert.length() / radius;
+            // the limit surface of an icosahedron sits at about 0.71 of its radius
+            assert!(r < 1.0 && r > 0.7, "{r}");
+        }
+    }
+
+    #[test]
+    fn test_loop_boundary_and_creases() {
+        // a flat grid stays flat, and keeps a single boundary
+        let grid = Mesh::new_hexagrid(1.0, 2);
+        let smooth = grid.loop_subdivide(1, &[]);
+        assert_eq!(smooth.tri.len(), grid.tri.len() * 4);
+        assert!(smooth.verts.iter().all(|v| v.z.abs() < 1e-6));
+        assert_eq!(
+            smooth.iter_naked_edges().count(),
+            grid.iter_naked_edges().count() * 2
+        );
+
+        // a cube with creased edges stays a cube, and keeps its uvs
+        let cube = Mesh::new_cube(1.0);
+        let creases = cube
+            .iter_triangles()
+            .flat_map(|(a, b, c)| [(a, b), (b, c), (c, a)])
+            .filter(|(a, b)| ((cube.verts[*a] - cube.verts[*b]).length() - 2.0).abs() < 1e-6)
+            .collect::<Vec<_>>();
+        let sharp = cube.loop_subdivide(2, &creases);
+        assert_eq!(sharp.uvs.len(), sharp.verts.len());
+        assert!(sharp.is_watertight());
+        for vert in sharp.verts.iter() {
+            assert!((vert.abs().max_element() - 1.0).abs() < 1e-6);
+        }
+        let round = cube.loop_subdivide(2, &[]);
+        assert!(round
+            .verts
+            .iter()
+            .all(|v| v.abs().max_element() < 1.0 - 1e-3));
+    }
+
+    #[test]
+    fn test_catmull_clark_inconsistent() {
+        // two triangles, one of them rewired to run along the shared edge in the same direction
+        let verts = [Vec3::ZERO, Vec3::X, Vec3::Y, vec3(1.0, -1.0, 0.0)];
+        let mut hedron = Polyhedron::from_faces(&verts, &[vec![0, 1, 2], vec![1, 0, 3]]).unwrap();
+        assert!(hedron.catmull_clark(1, &[]).is_some());
+        let face = hedron
+            .get_face_loops()
+            .into_iter()
+            .find(|lp| lp.iter().any(|ep| hedron.edge(*ep).from == 3))
+            .unwrap();
+        for (ep, from) in face.iter().zip([0, 1, 3]) {
+            hedron.mut_edge(*ep).from = from;
+        }
+        assert!(hedron.catmull_clark(1, &[]).is_none());
+    }
+}
@@ -0,0 +1,43 @@
+//! Meshes and helpers shared by the tests of the solid module
+use super::Mesh;
+use crate::kernel::{fxx, vec3, TAU};
+use crate::pts::Vectors;
+
+/// The cube from -1 to 1 as a convex hull, with its 8 corners shared by all sides
+pub(crate) fn hull_cube() -> Mesh {
+    let corners = [-1.0, 1.0]
+        .into_iter()
+        .flat_map(|x| {
+            [-1.0, 1.0]
+                .into_iter()
+                .flat_map(move |y| [-1.0, 1.0].map(|z| vec3(x, y, z)))
+        })
+        .collect::<Vec<_>>();
+    Vectors::new(corners).convex_hull().unwrap()
+}
+
+/// An open tube with a radius of 1 along z, lofted through `rings` rings of `sides` vertices, one unit apart.
+/// Every ring starts at the positive x axis
+pub(crate) fn loft_tube(sides: usize, rings: usize) -> Mesh {
+    let ring = |z: fxx| {
+        (0..sides)
+            .map(|i| {
+                let angle = i as fxx / sides as fxx * TAU;
+                vec3(angle.cos(), angle.sin(), z)
+            })
+            .collect::<Vec<_>>()
+    };
+    Mesh::loft((0..rings).map(|z| ring(z as fxx)).collect())
+}
+
+/// Twice the signed area of every triangle in uv space
+pub(crate) fn uv_areas(mesh: &Mesh) -> Vec<fxx> {
+    mesh.iter_triangles()
+        .map(|(a, b, c)| (mesh.uvs[b] - mesh.uvs[a]).perp_dot(mesh.uvs[c] - mesh.uvs[a]))
+        .collect()
+}
+
+/// Assert that `a` roughly equals `b`, relative to the size of `b` once it is larger than 1
+pub(crate) fn assert_close(a: fxx, b: fxx) {
+    assert!((a - b).abs() < 1e-4 * b.abs().max(1.0), "{} != {}", a, b);
+}
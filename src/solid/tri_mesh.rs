@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 use crate::{
+    data::SpatialHash,
     kernel::*,
     prelude::{Triangle, Vectors},
     util::{tolerance_equals, OneOrMany},
@@ -8,9 +9,9 @@ use crate::{
 /// a corner of a triangle face
 #[derive(Debug, Clone)]
 pub struct TriCorner {
-    v: usize,  // index of vertex
-    uv: usize, // index of uv
-    n: usize,  // index of normal
+    pub v: usize,  // index of vertex
+    pub uv: usize, // index of uv
+    pub n: usize,  // index of normal
 }
 
 impl TriCorner {
@@ -33,6 +34,9 @@ impl TriCorner {
     }
 }
 
+/// Vertices closer than this are welded into one position, when looking at the topology of a mesh
+pub(crate) const WELD_TOLERANCE: fxx = 1e-6;
+
 /// the mesh indexing model used
 #[derive(Debug, Clone)]
 pub enum Indexing {
@@ -84,6 +88,7 @@ impl Indexing {
 /// | Hetero  | index is 0 | index is     |
 /// |         |            |              |
 ///
+#[derive(Debug, Clone)]
 pub struct TriMesh {
     pub verts: Vec<Vec3>,
     pub tri: Indexing,
@@ -248,8 +253,8 @@ impl TriMesh {
     pub fn tri_count(&self) -> usize {
         match &self.tri {
             Indexing::Linear => self.verts.len() / 3,
-            Indexing::Uniform(tri) => tri.len(),
-            Indexing::Hetero(tri) => tri.len(),
+            Indexing::Uniform(tri) => tri.len() / 3,
+            Indexing::Hetero(tri) => tri.len() / 3,
         }
     }
 
@@ -258,7 +263,7 @@ impl TriMesh {
         (0..self.tri_count() * 3)
             .step_by(3)
             .map(|i| match &self.tri {
-                Indexing::Linear => (i * 3, (i * 3) + 1, (i * 3) + 2),
+                Indexing::Linear => (i, i + 1, i + 2),
                 Indexing::Uniform(tri) => (tri[i], tri[i + 1], tri[i + 2]),
                 Indexing::Hetero(tri) => (tri[i].v, tri[i + 1].v, tri[i + 2].v),
             })
@@ -272,9 +277,9 @@ impl TriMesh {
             .step_by(3)
             .map(|i| match &self.tri {
                 Indexing::Linear => (
-                    TriCorner::uniform(i * 3),
-                    TriCorner::uniform(i * 3 + 1),
-                    TriCorner::uniform(i * 3 + 2),
+                    TriCorner::uniform(i),
+                    TriCorner::uniform(i + 1),
+                    TriCorner::uniform(i + 2),
                 ),
                 Indexing::Uniform(tri) => (
                     TriCorner::uniform(tri[i]),
@@ -459,6 +464,34 @@ impl TriMesh {
         sim
     }
 
+    /// Same mapping as `desoupify`, but vertices are bucketed in a `SpatialHash`,
+    /// so only nearby vertices are compared. Use this one for large triangle soups.
+    pub fn desoupify_hashed(verts: &[Vec3], tolerance: fxx) -> Vec<usize> {
+        let mut hash = SpatialHash::new(tolerance);
+        let mut sim = Vec::with_capacity(verts.len());
+        for (i, vert) in verts.iter().enumerate() {
+            let i_similar_vertex = hash
+                .iter_near(*vert)
+                .filter(|j| tolerance_equals(*vert, verts[*j], tolerance))
+                .min()
+                .unwrap_or(i);
+            hash.insert(*vert, i);
+            sim.push(i_similar_vertex)
+        }
+        sim
+    }
+
+    /// Like `desoupify_hashed`, but every vertex points directly to the first vertex of its weld.
+    /// Vertices with the same root share a position, so triangles can be compared by their roots
+    pub(crate) fn weld_roots(verts: &[Vec3], tolerance: fxx) -> Vec<usize> {
+        let mut roots = Self::desoupify_hashed(verts, tolerance);
+        // similar vertices always point back, so their roots are known already
+        for i in 0..roots.len() {
+            roots[i] = roots[roots[i]];
+        }
+        roots
+    }
+
     /// join consequtive parts
     pub fn aggregate_edges(&self, _edges: impl Iterator<Item = (usize, usize)>) -> Vec<Vec<usize>> {
         // let mut linked_list = HashSet::new();
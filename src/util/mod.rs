@@ -6,6 +6,7 @@ mod one_or_many;
 mod rot;
 mod side;
 mod simple_step_range;
+mod union_find;
 mod vecs;
 
 pub use dir::*;
@@ -14,4 +15,5 @@ pub use one_or_many::*;
 pub use rot::*;
 pub(crate) use side::*;
 pub use simple_step_range::*;
+pub(crate) use union_find::*;
 pub use vecs::*;
@@ -0,0 +1,35 @@
+//! A disjoint set forest over indices. A slice of `(0..len).collect()` starts out with every index in its own set
+
+use std::collections::HashMap;
+
+/// The representative of the set containing `i`
+pub(crate) fn find(sets: &mut [usize], mut i: usize) -> usize {
+    while sets[i] != i {
+        sets[i] = sets[sets[i]];
+        i = sets[i];
+    }
+    i
+}
+
+/// Merge the sets containing `a` and `b`. Returns false if they already were the same set
+pub(crate) fn join(sets: &mut [usize], a: usize, b: usize) -> bool {
+    let (a, b) = (find(sets, a), find(sets, b));
+    if a == b {
+        return false;
+    }
+    sets[a.max(b)] = a.min(b);
+    true
+}
+
+/// Number the sets from 0, in order of their first member
+pub(crate) fn compact(sets: &mut [usize]) -> (Vec<usize>, usize) {
+    let mut ids = HashMap::new();
+    let numbered = (0..sets.len())
+        .map(|i| {
+            let set = find(sets, i);
+            let count = ids.len();
+            *ids.entry(set).or_insert(count)
+        })
+        .collect();
+    (numbered, ids.len())
+}